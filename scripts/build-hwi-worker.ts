@@ -0,0 +1,104 @@
+import fs from "fs/promises";
+import os from "os";
+import path from "path";
+import { execFileSync } from "child_process";
+import { fileURLToPath } from "url";
+
+const __filename = fileURLToPath(import.meta.url);
+const __dirname = path.dirname(__filename);
+
+const WORKER_DIR = path.join(__dirname, "..", "src-tauri", "hwi-worker");
+const BINARY_DIR = path.join(__dirname, "..", "src-tauri", "binaries");
+const VENV_DIR = path.join(WORKER_DIR, ".venv");
+
+function run(command: string, args: string[]): void {
+  execFileSync(command, args, { stdio: "inherit" });
+}
+
+function hostTarget(): string {
+  const output = execFileSync("rustc", ["-vV"], { encoding: "utf8" });
+  const host = output
+    .split("\n")
+    .find((line) => line.startsWith("host:"))
+    ?.split(":")[1]
+    ?.trim();
+  if (!host) {
+    throw new Error("Could not read the host target from rustc -vV");
+  }
+  return host;
+}
+
+function venvBin(name: string): string {
+  return process.platform === "win32"
+    ? path.join(VENV_DIR, "Scripts", `${name}.exe`)
+    : path.join(VENV_DIR, "bin", name);
+}
+
+// PyInstaller only cross-builds between macOS architectures, and only with a
+// universal2 Python
+function targetArch(target: string): string[] {
+  if (!target.endsWith("apple-darwin")) {
+    return [];
+  }
+  return ["--target-arch", target.startsWith("aarch64") ? "arm64" : "x86_64"];
+}
+
+async function main(target: string) {
+  console.log(`Building the HWI worker for ${target}...`);
+  const python =
+    process.env.PYTHON ?? (process.platform === "win32" ? "python" : "python3");
+
+  run(python, ["-m", "venv", VENV_DIR]);
+  run(venvBin("python"), [
+    "-m",
+    "pip",
+    "install",
+    "-r",
+    path.join(WORKER_DIR, "requirements.txt"),
+  ]);
+
+  const buildDir = await fs.mkdtemp(path.join(os.tmpdir(), "hwi-worker-"));
+  try {
+    run(venvBin("pyinstaller"), [
+      "--onefile",
+      "--noconfirm",
+      "--name",
+      "hwi-worker",
+      "--distpath",
+      path.join(buildDir, "dist"),
+      "--workpath",
+      path.join(buildDir, "build"),
+      "--specpath",
+      buildDir,
+      ...targetArch(target),
+      path.join(WORKER_DIR, "hwi_worker.py"),
+    ]);
+
+    const extension = process.platform === "win32" ? ".exe" : "";
+    const finalPath = path.join(
+      BINARY_DIR,
+      `hwi-worker-${target}${extension}`,
+    );
+    await fs.mkdir(BINARY_DIR, { recursive: true });
+    await fs.copyFile(
+      path.join(buildDir, "dist", `hwi-worker${extension}`),
+      finalPath,
+    );
+    await fs.chmod(finalPath, 0o755);
+    console.log(`Built ${finalPath}`);
+  } finally {
+    await fs.rm(buildDir, { recursive: true, force: true });
+  }
+}
+
+if (import.meta.url.startsWith("file:")) {
+  const args = process.argv.slice(2);
+  const targetIndex = args.indexOf("--target");
+  const target = targetIndex >= 0 ? args[targetIndex + 1] : hostTarget();
+  main(target).catch((error) => {
+    console.error("An error occurred:", error);
+    process.exit(1);
+  });
+}
+
+export { main as buildHwiWorker };
@@ -1,3 +1,118 @@
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+const THUMBPRINT_POINTER: &str = "/tauri/bundle/windows/certificateThumbprint";
+
+fn sidecar_path(name: &str) -> PathBuf {
+    let target = std::env::var("TARGET").expect("TARGET is set by cargo");
+    let extension = if target.contains("windows") {
+        ".exe"
+    } else {
+        ""
+    };
+    Path::new("binaries").join(format!("{}-{}{}", name, target, extension))
+}
+
+/// Identity the bundler signs the sidecars with, `None` for unsigned builds
+///
+/// Signing rewrites the binaries after this script hashed them, so signed builds check the code
+/// signature at runtime instead.
+fn signer() -> Option<String> {
+    println!("cargo:rerun-if-env-changed=APPLE_SIGNING_IDENTITY");
+    println!("cargo:rerun-if-env-changed=APPLE_TEAM_ID");
+    println!("cargo:rerun-if-env-changed=TAURI_CONFIG");
+    println!("cargo:rerun-if-changed=tauri.conf.json");
+
+    // Only `tauri build` bundles and signs, it enables custom-protocol
+    std::env::var_os("CARGO_FEATURE_CUSTOM_PROTOCOL")?;
+
+    let target = std::env::var("TARGET").expect("TARGET is set by cargo");
+    if target.contains("apple") {
+        let identity = std::env::var("APPLE_SIGNING_IDENTITY").ok()?;
+        // "-" signs ad hoc, which proves nothing about who built the binary
+        if identity.is_empty() || identity == "-" {
+            return None;
+        }
+        let team_id = std::env::var("APPLE_TEAM_ID")
+            .ok()
+            .filter(|team_id| !team_id.is_empty())
+            .or_else(|| team_id(&identity))
+            .expect("APPLE_TEAM_ID is not set and APPLE_SIGNING_IDENTITY names no team");
+        Some(format!("apple:{}", team_id))
+    } else if target.contains("windows") {
+        certificate_thumbprint().map(|thumbprint| format!("windows:{}", thumbprint))
+    } else {
+        None
+    }
+}
+
+/// Team ID of an identity like "Developer ID Application: Name (TEAMID)"
+fn team_id(identity: &str) -> Option<String> {
+    let (_, rest) = identity.rsplit_once('(')?;
+    let team_id = rest.strip_suffix(')')?;
+    Some(team_id.to_string()).filter(|team_id| !team_id.is_empty())
+}
+
+/// Thumbprint of the certificate the bundler signs with, `--config` overrides tauri.conf.json
+fn certificate_thumbprint() -> Option<String> {
+    let overrides = std::env::var("TAURI_CONFIG")
+        .ok()
+        .and_then(|config| serde_json::from_str::<serde_json::Value>(&config).ok());
+    let thumbprint = match overrides
+        .as_ref()
+        .and_then(|c| c.pointer(THUMBPRINT_POINTER))
+    {
+        Some(thumbprint) => thumbprint.clone(),
+        None => {
+            let config = std::fs::read_to_string("tauri.conf.json").ok()?;
+            serde_json::from_str::<serde_json::Value>(&config)
+                .ok()?
+                .pointer(THUMBPRINT_POINTER)?
+                .clone()
+        }
+    };
+    thumbprint
+        .as_str()
+        .filter(|thumbprint| !thumbprint.is_empty())
+        .map(str::to_uppercase)
+}
+
+/// Embeds what the app checks the HWI sidecars against before running them, see src/sidecar.rs
+///
+/// Signed builds embed the signer, unsigned ones the SHA-256 of each binary for the target.
+fn embed_sidecar_checks() {
+    if let Some(signer) = signer() {
+        println!("cargo:rustc-env=HWI_SIDECAR_SIGNER={}", signer);
+        return;
+    }
+
+    // Without a hash every HWI command fails at runtime instead of failing the build, so that
+    // frontend work is possible without the sidecar
+    let path = sidecar_path("hwi");
+    match std::fs::read(&path) {
+        Ok(binary) => println!(
+            "cargo:rustc-env=HWI_SIDECAR_SHA256={}",
+            hex::encode(Sha256::digest(binary))
+        ),
+        Err(e) => println!(
+            "cargo:warning=Could not hash HWI sidecar {}: {}",
+            path.display(),
+            e
+        ),
+    }
+
+    // The worker is optional, builds without it run every command in a new `hwi` process
+    if let Ok(binary) = std::fs::read(sidecar_path("hwi-worker")) {
+        println!(
+            "cargo:rustc-env=HWI_WORKER_SHA256={}",
+            hex::encode(Sha256::digest(binary))
+        );
+    }
+}
+
 fn main() {
+    // Also picks up sidecars which are added later, e.g. the worker
+    println!("cargo:rerun-if-changed=binaries");
+    embed_sidecar_checks();
     tauri_build::build()
 }
@@ -0,0 +1,76 @@
+"""Long-lived HWI worker for Keeper Desktop.
+
+Runs HWI commands through hwilib in a single process, so that Python and the device libraries are
+only loaded once. Requests and responses are JSON objects, one per line, on stdin and stdout:
+
+    {"id": 1, "args": ["--chain", "TEST", "enumerate"], "stdin": ""}
+    {"id": 1, "code": 0, "stdout": "[...]\\n", "stderr": ""}
+
+`args` and `stdin` are exactly what the stock `hwi` CLI would get, including `--stdin` payloads,
+and `stdout`, `stderr` and `code` are what it would have printed and exited with. Requests are
+handled one at a time in the order they arrive, responses carry the id of their request.
+"""
+
+import contextlib
+import io
+import json
+import logging
+import sys
+import traceback
+
+from hwilib._cli import main as hwi_main
+
+
+def reset_logging():
+    # `--debug` configures the root logger for the stderr of its own request, drop that handler
+    # so that the next request configures it afresh
+    for handler in logging.root.handlers[:]:
+        logging.root.removeHandler(handler)
+    logging.root.setLevel(logging.WARNING)
+
+
+def run(args, stdin):
+    reset_logging()
+    stdout = io.StringIO()
+    stderr = io.StringIO()
+    code = 0
+    saved_argv, saved_stdin = sys.argv, sys.stdin
+    sys.argv = ["hwi"] + args
+    sys.stdin = io.StringIO(stdin)
+    try:
+        with contextlib.redirect_stdout(stdout), contextlib.redirect_stderr(stderr):
+            try:
+                hwi_main()
+            except SystemExit as e:
+                code = e.code if isinstance(e.code, int) else (0 if e.code is None else 1)
+            except Exception:
+                traceback.print_exc()
+                code = 1
+    finally:
+        sys.argv, sys.stdin = saved_argv, saved_stdin
+    return code, stdout.getvalue(), stderr.getvalue()
+
+
+def main():
+    requests = sys.stdin
+    responses = sys.stdout
+    for line in requests:
+        if not line.strip():
+            continue
+        try:
+            request = json.loads(line)
+            request_id = request["id"]
+            args = [str(arg) for arg in request.get("args", [])]
+            stdin = request.get("stdin") or ""
+        except (ValueError, KeyError, TypeError) as e:
+            print("Invalid request: {}".format(e), file=sys.stderr, flush=True)
+            continue
+
+        code, stdout, stderr = run(args, stdin)
+        response = {"id": request_id, "code": code, "stdout": stdout, "stderr": stderr}
+        responses.write(json.dumps(response) + "\n")
+        responses.flush()
+
+
+if __name__ == "__main__":
+    main()
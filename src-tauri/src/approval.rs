@@ -0,0 +1,486 @@
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::OsRng;
+use bitcoin::{Address, Amount, Network, Psbt};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::Manager;
+use thiserror::Error;
+
+use crate::audit::{AuditEvent, AuditSource};
+
+pub const APPROVAL_TIMEOUT_SECS: u64 = 120;
+pub const RULES_FILE_NAME: &str = "approval_rules.json";
+
+/// Actions that must be accepted on the desktop before they reach the device flow.
+const GATED_ACTIONS: [&str; 11] = [
+    "ADD_DEVICE",
+    "HEALTH_CHECK",
+    "SIGN_TX",
+    "REGISTER_MULTISIG",
+    "VERIFY_ADDRESS",
+    "SIGN_MESSAGE",
+    "SETUP_DEVICE",
+    "RESTORE_DEVICE",
+    "BACKUP_DEVICE",
+    "WIPE_DEVICE",
+    "TOGGLE_PASSPHRASE",
+];
+
+/// Actions which the user may choose to always allow for a given phone.
+const REMEMBERABLE_ACTIONS: [&str; 2] = ["HEALTH_CHECK", "VERIFY_ADDRESS"];
+
+#[derive(Error, Debug)]
+pub enum ApprovalError {
+    #[error("No pending approval with id {0}")]
+    NotFound(String),
+    #[error("Action {0} cannot be always allowed")]
+    NotRememberable(String),
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApprovalRule {
+    pub origin: String,
+    pub action: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingApproval {
+    pub id: String,
+    pub action: String,
+    pub origin: String,
+    pub summary: Value,
+    pub rememberable: bool,
+    pub expires_at: u64,
+    /// The processed channel message, forwarded to the webview once approved
+    #[serde(skip)]
+    pub message: Value,
+}
+
+pub enum Submission {
+    /// The message needs no approval and can be dispatched right away
+    Forward(Value),
+    /// The message is held until the user accepts or rejects it
+    Pending(PendingApproval),
+}
+
+pub struct ApprovalGate {
+    pending: HashMap<String, PendingApproval>,
+    rules: Vec<ApprovalRule>,
+    rules_path: Option<PathBuf>,
+}
+
+impl ApprovalGate {
+    /// Creates a new gate, loading remembered rules from "approval_rules.json" in the
+    /// provided directory when available.
+    pub fn new(data_dir: Option<PathBuf>) -> Self {
+        let rules_path = data_dir.map(|dir| dir.join(RULES_FILE_NAME));
+        let rules = rules_path
+            .as_ref()
+            .filter(|path| path.exists())
+            .map(|path| {
+                std::fs::read_to_string(path)
+                    .map_err(ApprovalError::from)
+                    .and_then(|contents| serde_json::from_str(&contents).map_err(Into::into))
+                    .unwrap_or_else(|e| {
+                        warn!("Failed to load approval rules: {}", e);
+                        Vec::new()
+                    })
+            })
+            .unwrap_or_default();
+
+        ApprovalGate {
+            pending: HashMap::new(),
+            rules,
+            rules_path,
+        }
+    }
+
+    /// Decides whether a processed channel message can be dispatched or must wait for the user
+    pub fn submit(&mut self, message: Value, origin: String) -> Submission {
+        let action = message["data"]["action"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+
+        if !GATED_ACTIONS.contains(&action.as_str()) || self.is_allowed(&origin, &action) {
+            return Submission::Forward(message);
+        }
+
+        let network = message_network(&message);
+        let approval = PendingApproval {
+            id: new_id(),
+            summary: summarize(&action, &message["data"], network),
+            rememberable: REMEMBERABLE_ACTIONS.contains(&action.as_str()),
+            expires_at: unix_time() + APPROVAL_TIMEOUT_SECS,
+            action,
+            origin,
+            message,
+        };
+        self.pending.insert(approval.id.clone(), approval.clone());
+        Submission::Pending(approval)
+    }
+
+    /// Accepts a pending request, optionally remembering the decision for its origin
+    ///
+    /// Returns the held channel message
+    pub fn approve(&mut self, id: &str, remember: bool) -> Result<Value, ApprovalError> {
+        let approval = self
+            .pending
+            .get(id)
+            .ok_or_else(|| ApprovalError::NotFound(id.to_string()))?;
+
+        if remember {
+            if !approval.rememberable || approval.origin.is_empty() {
+                return Err(ApprovalError::NotRememberable(approval.action.clone()));
+            }
+            let rule = ApprovalRule {
+                origin: approval.origin.clone(),
+                action: approval.action.clone(),
+            };
+            if !self.rules.contains(&rule) {
+                self.rules.push(rule);
+                self.store_rules()?;
+            }
+        }
+
+        self.pending
+            .remove(id)
+            .map(|approval| approval.message)
+            .ok_or_else(|| ApprovalError::NotFound(id.to_string()))
+    }
+
+    /// Rejects a pending request
+    pub fn reject(&mut self, id: &str) -> Result<PendingApproval, ApprovalError> {
+        self.pending
+            .remove(id)
+            .ok_or_else(|| ApprovalError::NotFound(id.to_string()))
+    }
+
+    /// Removes a request that is still pending once its deadline has passed
+    pub fn expire(&mut self, id: &str) -> Option<PendingApproval> {
+        if self
+            .pending
+            .get(id)
+            .is_some_and(|approval| approval.expires_at <= unix_time())
+        {
+            self.pending.remove(id)
+        } else {
+            None
+        }
+    }
+
+    pub fn pending(&self) -> Vec<PendingApproval> {
+        self.pending.values().cloned().collect()
+    }
+
+    pub fn rules(&self) -> &[ApprovalRule] {
+        &self.rules
+    }
+
+    pub fn remove_rule(&mut self, rule: &ApprovalRule) -> Result<(), ApprovalError> {
+        self.rules.retain(|r| r != rule);
+        self.store_rules()
+    }
+
+    fn is_allowed(&self, origin: &str, action: &str) -> bool {
+        REMEMBERABLE_ACTIONS.contains(&action)
+            && self
+                .rules
+                .iter()
+                .any(|rule| rule.origin == origin && rule.action == action)
+    }
+
+    fn store_rules(&self) -> Result<(), ApprovalError> {
+        if let Some(path) = &self.rules_path {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, serde_json::to_string(&self.rules)?)?;
+        }
+        Ok(())
+    }
+}
+
+/// Routes a processed channel message through the approval gate
+///
+/// Messages which need no approval are emitted to the webview straight away. Otherwise an
+/// `approval-request` event is emitted and the request is auto-denied after the timeout.
+pub fn dispatch_channel_message(
+    app_handle: &tauri::AppHandle,
+    state: &mut crate::AppStateInner,
+    message: Value,
+) -> tauri::Result<()> {
+    // The room is derived from the channel key, and only a phone holding that key can send a
+    // message which decrypts. Anything the phone claims about itself, e.g. an app id, is not.
+    let origin = state.channel.room.clone().unwrap_or_default();
+
+    let action = message["data"]["action"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+
+    match state.approvals.submit(message, origin) {
+        Submission::Forward(message) => {
+            state
+                .audit
+                .record(AuditEvent::new(AuditSource::Channel, &action, "forwarded"));
+            forward_message(app_handle, state, message)
+        }
+        Submission::Pending(approval) => {
+            state.audit.record(
+                AuditEvent::new(AuditSource::Channel, &action, "held")
+                    .with_txid(approval.summary["txid"].as_str().map(str::to_string)),
+            );
+            info!(
+                "Holding {} request {} for approval",
+                approval.action, approval.id
+            );
+            if let Err(e) = app_handle.emit_all("approval-request", &approval) {
+                error!("Failed to emit approval-request event: {:?}", e);
+            }
+
+            let app_handle = app_handle.clone();
+            let id = approval.id;
+            tauri::async_runtime::spawn(async move {
+                tokio::time::sleep(Duration::from_secs(APPROVAL_TIMEOUT_SECS)).await;
+                let state = app_handle.state::<crate::AppState>();
+                let mut state = state.lock().await;
+                if let Some(expired) = state.approvals.expire(&id) {
+                    warn!("Approval {} timed out, denying request", id);
+                    notify_denied(&app_handle, &state, &expired, "timeout");
+                }
+            });
+            Ok(())
+        }
+    }
+}
+
+/// Emits a channel message to the webview to start the device flow
+///
+/// The request id sent by the phone, if any, is recorded as the active request so that
+/// progress events can be tied back to it until the device command completes.
+pub fn forward_message(
+    app_handle: &tauri::AppHandle,
+    state: &mut crate::AppStateInner,
+    message: Value,
+) -> tauri::Result<()> {
+    state.active_request = message["data"]["requestId"].as_str().map(str::to_string);
+
+    app_handle.emit_all("channel-message", message)
+}
+
+/// Informs both the webview and the phone that a request will not be executed
+pub fn notify_denied(
+    app_handle: &tauri::AppHandle,
+    state: &crate::AppStateInner,
+    approval: &PendingApproval,
+    reason: &str,
+) {
+    state.audit.record(
+        AuditEvent::new(AuditSource::Channel, &approval.action, reason)
+            .with_txid(approval.summary["txid"].as_str().map(str::to_string)),
+    );
+
+    if let Err(e) = app_handle.emit_all(
+        "approval-resolved",
+        json!({ "id": approval.id, "approved": false, "reason": reason }),
+    ) {
+        error!("Failed to emit approval-resolved event: {:?}", e);
+    }
+
+    let network = message_network(&approval.message).to_string();
+    let response = json!({
+        "event": "CHANNEL_MESSAGE",
+        "data": {
+            "responseData": {
+                "action": approval.action,
+                "error": format!("Request was denied on the desktop app ({})", reason)
+            }
+        }
+    });
+    if let Err(e) = state
+        .channel
+        .emit("CHANNEL_MESSAGE", response, false, Some(&network))
+    {
+        error!("Failed to notify phone of denied request: {}", e);
+    }
+}
+
+pub(crate) fn new_id() -> String {
+    let mut id_bytes = [0u8; 8];
+    OsRng.fill_bytes(&mut id_bytes);
+    hex::encode(id_bytes)
+}
+
+fn message_network(message: &Value) -> Network {
+    match message["network"].as_str() {
+        Some("MAINNET") => Network::Bitcoin,
+        _ => Network::Testnet,
+    }
+}
+
+fn summarize(action: &str, data: &Value, network: Network) -> Value {
+    match action {
+        "SIGN_TX" => data["psbt"]["serializedPSBT"]
+            .as_str()
+            .ok_or_else(|| "PSBT was not provided".to_string())
+            .and_then(|psbt| summarize_psbt(psbt, network))
+            .unwrap_or_else(|e| json!({ "error": e })),
+        "REGISTER_MULTISIG" => json!({
+            "walletName": data["walletName"],
+            "descriptor": data["descriptorString"],
+            "miniscriptPolicy": data["miniscriptPolicy"],
+            "firstAddress": data["firstExtAdd"],
+        }),
+        "VERIFY_ADDRESS" => json!({
+            "walletName": data["walletName"],
+            "address": data["receivingAddress"],
+            "derivationPath": data["derivationPath"],
+        }),
+        "SIGN_MESSAGE" => json!({
+            "message": data["message"],
+            "derivationPath": data["derivationPath"],
+            "messageFormat": data["messageFormat"],
+        }),
+        // The passphrase is left out, it has no place on screen
+        "SETUP_DEVICE" | "RESTORE_DEVICE" | "BACKUP_DEVICE" | "WIPE_DEVICE"
+        | "TOGGLE_PASSPHRASE" => json!({
+            "signerType": data["signerType"],
+            "label": data["label"],
+            "wordCount": data["wordCount"],
+        }),
+        _ => json!({
+            "accountNumber": data["accountNumber"].as_u64().unwrap_or(0),
+        }),
+    }
+}
+
+fn summarize_psbt(psbt: &str, network: Network) -> Result<Value, String> {
+    let psbt = Psbt::from_str(psbt).map_err(|e| e.to_string())?;
+    let outputs: Vec<Value> = psbt
+        .unsigned_tx
+        .output
+        .iter()
+        .map(|output| {
+            json!({
+                "address": Address::from_script(&output.script_pubkey, network)
+                    .map(|a| a.to_string())
+                    .ok(),
+                "amount": output.value.to_sat(),
+            })
+        })
+        .collect();
+
+    Ok(json!({
+        "txid": psbt.unsigned_tx.compute_txid().to_string(),
+        "inputs": psbt.unsigned_tx.input.len(),
+        "outputs": outputs,
+        "fee": psbt.fee().map(Amount::to_sat).ok(),
+    }))
+}
+
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(action: &str) -> Value {
+        json!({ "network": "TESTNET", "data": { "action": action, "accountNumber": 0 } })
+    }
+
+    fn pending(submission: Submission) -> PendingApproval {
+        match submission {
+            Submission::Pending(approval) => approval,
+            Submission::Forward(message) => panic!("{} was not held", message),
+        }
+    }
+
+    #[test]
+    fn holds_gated_actions_until_resolved() {
+        let mut gate = ApprovalGate::new(None);
+        assert!(matches!(
+            gate.submit(request("SYNC_WALLET"), "room".to_string()),
+            Submission::Forward(_)
+        ));
+
+        let sign = pending(gate.submit(request("SIGN_TX"), "room".to_string()));
+        assert!(!sign.rememberable);
+        let check = pending(gate.submit(request("HEALTH_CHECK"), "room".to_string()));
+        assert_eq!(gate.pending().len(), 2);
+
+        assert!(matches!(
+            gate.approve(&sign.id, true),
+            Err(ApprovalError::NotRememberable(_))
+        ));
+        assert_eq!(gate.approve(&sign.id, false).unwrap(), request("SIGN_TX"));
+        assert!(matches!(
+            gate.approve(&sign.id, false),
+            Err(ApprovalError::NotFound(_))
+        ));
+
+        assert_eq!(gate.reject(&check.id).unwrap().action, "HEALTH_CHECK");
+        assert!(gate.pending().is_empty());
+    }
+
+    #[test]
+    fn expires_requests_after_the_deadline() {
+        let mut gate = ApprovalGate::new(None);
+        let approval = pending(gate.submit(request("SIGN_TX"), "room".to_string()));
+        assert!(gate.expire(&approval.id).is_none());
+
+        gate.pending.get_mut(&approval.id).unwrap().expires_at = unix_time() - 1;
+        assert_eq!(gate.expire(&approval.id).unwrap().id, approval.id);
+        assert!(matches!(
+            gate.approve(&approval.id, false),
+            Err(ApprovalError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn remembers_rules_per_channel() {
+        let dir = std::env::temp_dir().join(format!("keeper-approvals-{}", new_id()));
+        let mut gate = ApprovalGate::new(Some(dir.clone()));
+
+        let approval = pending(gate.submit(request("VERIFY_ADDRESS"), "room-a".to_string()));
+        gate.approve(&approval.id, true).unwrap();
+        assert!(matches!(
+            gate.submit(request("VERIFY_ADDRESS"), "room-a".to_string()),
+            Submission::Forward(_)
+        ));
+        pending(gate.submit(request("VERIFY_ADDRESS"), "room-b".to_string()));
+        pending(gate.submit(request("HEALTH_CHECK"), "room-a".to_string()));
+
+        // Without a channel there is nothing to tie the rule to
+        let anonymous = pending(gate.submit(request("VERIFY_ADDRESS"), String::new()));
+        assert!(matches!(
+            gate.approve(&anonymous.id, true),
+            Err(ApprovalError::NotRememberable(_))
+        ));
+
+        let mut reloaded = ApprovalGate::new(Some(dir.clone()));
+        assert_eq!(reloaded.rules(), gate.rules());
+        let rule = reloaded.rules()[0].clone();
+        reloaded.remove_rule(&rule).unwrap();
+        pending(reloaded.submit(request("VERIFY_ADDRESS"), "room-a".to_string()));
+        assert!(ApprovalGate::new(Some(dir.clone())).rules().is_empty());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
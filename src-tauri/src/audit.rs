@@ -0,0 +1,383 @@
+use log::error;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt::Display;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use thiserror::Error;
+
+use crate::approval::unix_time;
+use crate::HWIClientState;
+
+pub const AUDIT_FILE_NAME: &str = "audit.jsonl";
+
+/// Hash used as `prevHash` of the very first entry
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Error, Debug)]
+pub enum AuditError {
+    #[error("Audit log is not available")]
+    Unavailable,
+    #[error("Audit log chain is broken at entry {0}")]
+    ChainBroken(u64),
+    #[error("Audit log could not be read, nothing is appended until it is repaired: {0}")]
+    Unreadable(String),
+    #[error("JSON error: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditSource {
+    Channel,
+    Command,
+}
+
+/// An event to be appended to the audit log
+#[derive(Clone, Debug)]
+pub struct AuditEvent {
+    source: AuditSource,
+    action: String,
+    device_type: Option<String>,
+    fingerprint: Option<String>,
+    txid: Option<String>,
+    outcome: String,
+    detail: Option<String>,
+}
+
+impl AuditEvent {
+    pub fn new(source: AuditSource, action: &str, outcome: &str) -> Self {
+        AuditEvent {
+            source,
+            action: action.to_string(),
+            device_type: None,
+            fingerprint: None,
+            txid: None,
+            outcome: outcome.to_string(),
+            detail: None,
+        }
+    }
+
+    /// Creates an event for a Tauri command, recording whether it succeeded
+    pub fn command<T, E: Display>(action: &str, result: &Result<T, E>) -> Self {
+        match result {
+            Ok(_) => AuditEvent::new(AuditSource::Command, action, "success"),
+            Err(e) => {
+                AuditEvent::new(AuditSource::Command, action, "failure").with_detail(e.to_string())
+            }
+        }
+    }
+
+    pub fn with_device(mut self, hwi_state: Option<&HWIClientState>) -> Self {
+        if let Some(hwi_state) = hwi_state {
+            self.device_type = Some(hwi_state.device_type.to_string());
+            self.fingerprint = hwi_state.fingerprint.clone();
+        }
+        self
+    }
+
+    pub fn with_txid(mut self, txid: Option<String>) -> Self {
+        self.txid = txid;
+        self
+    }
+
+    pub fn with_detail(mut self, detail: String) -> Self {
+        self.detail = Some(detail);
+        self
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AuditRecord {
+    seq: u64,
+    timestamp: u64,
+    source: AuditSource,
+    action: String,
+    device_type: Option<String>,
+    fingerprint: Option<String>,
+    txid: Option<String>,
+    outcome: String,
+    detail: Option<String>,
+    prev_hash: String,
+}
+
+impl AuditRecord {
+    fn hash(&self) -> Result<String, AuditError> {
+        Ok(hex::encode(Sha256::digest(serde_json::to_vec(self)?)))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    #[serde(flatten)]
+    record: AuditRecord,
+    hash: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditFilter {
+    pub action: Option<String>,
+    pub fingerprint: Option<String>,
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+impl AuditFilter {
+    fn matches(&self, record: &AuditRecord) -> bool {
+        self.action.as_ref().is_none_or(|a| &record.action == a)
+            && self.fingerprint.as_ref().is_none_or(|f| {
+                record
+                    .fingerprint
+                    .as_ref()
+                    .is_some_and(|rf| rf.eq_ignore_ascii_case(f))
+            })
+            && self.since.is_none_or(|s| record.timestamp >= s)
+            && self.until.is_none_or(|u| record.timestamp <= u)
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditQueryResult {
+    pub entries: Vec<AuditEntry>,
+    /// Whether every entry in the log links to the hash of its predecessor
+    pub intact: bool,
+}
+
+struct ChainHead {
+    seq: u64,
+    hash: String,
+}
+
+/// Append-only, hash-chained log of channel and device activity stored as JSON lines
+pub struct AuditLog {
+    path: Option<PathBuf>,
+    /// The error of reading the existing log, if it couldn't be read there is no head to
+    /// chain new entries to
+    head: Mutex<Result<ChainHead, String>>,
+}
+
+impl AuditLog {
+    /// Opens the audit log stored in "audit.jsonl" in the provided directory, resuming the
+    /// chain from its last entry.
+    ///
+    /// A log which can't be read is left untouched and nothing is appended to it, starting a
+    /// new chain would hide the entries written so far.
+    pub fn new(data_dir: Option<PathBuf>) -> Self {
+        let path = data_dir.map(|dir| dir.join(AUDIT_FILE_NAME));
+        let entries = match &path {
+            Some(path) => read_entries(path),
+            None => Ok(Vec::new()),
+        };
+        let head = entries
+            .map(|entries| match entries.last() {
+                Some(last) => ChainHead {
+                    seq: last.record.seq + 1,
+                    hash: last.hash.clone(),
+                },
+                None => ChainHead {
+                    seq: 0,
+                    hash: GENESIS_HASH.to_string(),
+                },
+            })
+            .map_err(|e| {
+                error!("Failed to read audit log, appending is disabled: {}", e);
+                e.to_string()
+            });
+
+        AuditLog {
+            path,
+            head: Mutex::new(head),
+        }
+    }
+
+    /// Appends an event to the log. Failures are logged and never interrupt the caller.
+    pub fn record(&self, event: AuditEvent) {
+        if let Err(e) = self.append(event) {
+            error!("Failed to write audit log entry: {}", e);
+        }
+    }
+
+    fn append(&self, event: AuditEvent) -> Result<(), AuditError> {
+        let path = self.path.as_ref().ok_or(AuditError::Unavailable)?;
+        let mut head = self.head.lock().map_err(|_| AuditError::Unavailable)?;
+        let head = head
+            .as_mut()
+            .map_err(|e| AuditError::Unreadable(e.clone()))?;
+
+        let record = AuditRecord {
+            seq: head.seq,
+            timestamp: unix_time(),
+            source: event.source,
+            action: event.action,
+            device_type: event.device_type,
+            fingerprint: event.fingerprint,
+            txid: event.txid,
+            outcome: event.outcome,
+            detail: event.detail,
+            prev_hash: head.hash.clone(),
+        };
+        let entry = AuditEntry {
+            hash: record.hash()?,
+            record,
+        };
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+
+        head.seq += 1;
+        head.hash = entry.hash;
+        Ok(())
+    }
+
+    /// Returns the entries matching the filter, newest first
+    pub fn query(&self, filter: &AuditFilter) -> Result<AuditQueryResult, AuditError> {
+        let path = self.path.as_ref().ok_or(AuditError::Unavailable)?;
+        let entries = read_entries(path)?;
+        let intact = verify_chain(&entries).is_ok();
+
+        let entries = entries
+            .into_iter()
+            .rev()
+            .filter(|entry| filter.matches(&entry.record))
+            .take(filter.limit.unwrap_or(usize::MAX))
+            .collect();
+
+        Ok(AuditQueryResult { entries, intact })
+    }
+
+    /// Copies the verified log to `destination`, returning the number of exported entries
+    pub fn export(&self, destination: &Path) -> Result<usize, AuditError> {
+        let path = self.path.as_ref().ok_or(AuditError::Unavailable)?;
+        let entries = read_entries(path)?;
+        verify_chain(&entries)?;
+        std::fs::copy(path, destination)?;
+        Ok(entries.len())
+    }
+}
+
+fn read_entries(path: &Path) -> Result<Vec<AuditEntry>, AuditError> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    std::fs::read_to_string(path)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(AuditError::from))
+        .collect()
+}
+
+fn verify_chain(entries: &[AuditEntry]) -> Result<(), AuditError> {
+    let mut prev_hash = GENESIS_HASH.to_string();
+    for entry in entries {
+        if entry.record.prev_hash != prev_hash || entry.record.hash()? != entry.hash {
+            return Err(AuditError::ChainBroken(entry.record.seq));
+        }
+        prev_hash = entry.hash.clone();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::approval::new_id;
+
+    fn data_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("keeper-audit-{}", new_id()))
+    }
+
+    fn read(dir: &Path) -> Vec<AuditEntry> {
+        read_entries(&dir.join(AUDIT_FILE_NAME)).unwrap()
+    }
+
+    #[test]
+    fn chains_appended_entries() {
+        let dir = data_dir();
+        let log = AuditLog::new(Some(dir.clone()));
+        log.append(AuditEvent::new(AuditSource::Channel, "SIGN_TX", "approved"))
+            .unwrap();
+        log.append(
+            AuditEvent::command("SIGN_TX", &Ok::<_, String>(())).with_txid(Some("ab".to_string())),
+        )
+        .unwrap();
+
+        // A reopened log continues the chain
+        let log = AuditLog::new(Some(dir.clone()));
+        log.append(AuditEvent::command("WIPE", &Err::<(), _>("cancelled")))
+            .unwrap();
+
+        let entries = read(&dir);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].record.prev_hash, GENESIS_HASH);
+        assert_eq!(entries[2].record.seq, 2);
+        assert_eq!(entries[2].record.prev_hash, entries[1].hash);
+        assert!(verify_chain(&entries).is_ok());
+
+        let result = log
+            .query(&AuditFilter {
+                action: Some("SIGN_TX".to_string()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(result.intact);
+        assert_eq!(result.entries.len(), 2);
+        assert_eq!(result.entries[0].record.txid.as_deref(), Some("ab"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn detects_tampered_entries() {
+        let dir = data_dir();
+        let log = AuditLog::new(Some(dir.clone()));
+        for action in ["GET_XPUB", "SIGN_TX", "SIGN_MESSAGE"] {
+            log.append(AuditEvent::new(AuditSource::Channel, action, "approved"))
+                .unwrap();
+        }
+
+        let mut entries = read(&dir);
+        entries[1].record.outcome = "rejected".to_string();
+        assert!(matches!(
+            verify_chain(&entries),
+            Err(AuditError::ChainBroken(1))
+        ));
+
+        let mut entries = read(&dir);
+        entries.remove(1);
+        assert!(matches!(
+            verify_chain(&entries),
+            Err(AuditError::ChainBroken(2))
+        ));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_to_append_to_an_unreadable_log() {
+        let dir = data_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(AUDIT_FILE_NAME);
+        std::fs::write(&path, "{not json\n").unwrap();
+
+        let log = AuditLog::new(Some(dir.clone()));
+        assert!(matches!(
+            log.append(AuditEvent::new(AuditSource::Channel, "SIGN_TX", "approved")),
+            Err(AuditError::Unreadable(_))
+        ));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{not json\n");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,458 @@
+use async_hwi::Version;
+use bitcoin::Psbt;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use thiserror::Error;
+
+use crate::hwi::types::HWIDeviceType;
+use crate::HWIClientState;
+
+#[derive(Error, Debug)]
+pub enum CapabilityError {
+    #[error("{0} does not support {1}")]
+    Unsupported(HWIDeviceType, Feature),
+    #[error(
+        "{feature} needs {device_type} {} {required} or later, the device runs {current}. \
+         Please update the {} to {required}",
+        firmware_name(.device_type),
+        firmware_name(.device_type)
+    )]
+    FirmwareTooOld {
+        device_type: HWIDeviceType,
+        feature: Feature,
+        required: Version,
+        current: Version,
+    },
+    #[error(
+        "{feature} needs {device_type} {} {required} or later, but the version on the device \
+         could not be read. Please reconnect the device, or update the {} to {required}",
+        firmware_name(.device_type),
+        firmware_name(.device_type)
+    )]
+    VersionUnknown {
+        device_type: HWIDeviceType,
+        feature: Feature,
+        required: Version,
+    },
+}
+
+/// Something a device may or may not be able to do, depending on its type and firmware
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Feature {
+    /// Wallet policies beyond plain multisig
+    Miniscript,
+    Taproot,
+    /// Wallet policies have to be registered on the device before use
+    WalletRegistration,
+    /// The registration can be checked and done while signing or showing an address, instead
+    /// of up front with `REGISTER_MULTISIG`
+    RegistrationCheck,
+    /// Registration returns an HMAC which has to be passed back on every later use
+    Hmac,
+    MessageSigning,
+    DisplayAddress,
+    /// Global xpubs can be left in the PSBT of a wallet policy
+    PsbtGlobalXpubs,
+}
+
+const FEATURES: [Feature; 8] = [
+    Feature::Miniscript,
+    Feature::Taproot,
+    Feature::WalletRegistration,
+    Feature::RegistrationCheck,
+    Feature::Hmac,
+    Feature::MessageSigning,
+    Feature::DisplayAddress,
+    Feature::PsbtGlobalXpubs,
+];
+
+impl Display for Feature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Feature::Miniscript => "miniscript",
+            Feature::Taproot => "taproot",
+            Feature::WalletRegistration => "wallet registration",
+            Feature::RegistrationCheck => "checking wallet registrations",
+            Feature::Hmac => "wallet HMACs",
+            Feature::MessageSigning => "message signing",
+            Feature::DisplayAddress => "displaying addresses",
+            Feature::PsbtGlobalXpubs => "global xpubs in wallet policy PSBTs",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Hardware models which number their firmware separately
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Model {
+    /// Mk3 and older, whose last firmware line is 4.x
+    ColdcardMk3,
+    ColdcardMk4,
+    ColdcardQ,
+}
+
+/// What is known about the firmware of a device, or the app for Ledger
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Firmware {
+    /// The device is only driven through HWI, which doesn't report versions. Such devices are
+    /// not held to minimum versions
+    NotReported,
+    /// Reading the version failed, so features with a minimum version are refused
+    Unknown,
+    Known {
+        version: Version,
+        model: Option<Model>,
+    },
+}
+
+impl Firmware {
+    pub fn version(&self) -> Option<&Version> {
+        match self {
+            Firmware::Known { version, .. } => Some(version),
+            Firmware::NotReported | Firmware::Unknown => None,
+        }
+    }
+
+    fn model(&self) -> Option<Model> {
+        match self {
+            Firmware::Known { model, .. } => *model,
+            Firmware::NotReported | Firmware::Unknown => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Support {
+    Unsupported,
+    Supported,
+    /// Supported from this firmware, or app for Ledger, version on
+    Since(Version),
+}
+
+impl Support {
+    pub fn allows(&self, firmware: &Firmware) -> bool {
+        match (self, firmware) {
+            (Support::Unsupported, _) => false,
+            (Support::Supported, _) | (Support::Since(_), Firmware::NotReported) => true,
+            (Support::Since(_), Firmware::Unknown) => false,
+            (Support::Since(minimum), Firmware::Known { version, .. }) => version >= minimum,
+        }
+    }
+}
+
+const fn since(major: u32, minor: u32, patch: u32) -> Support {
+    Support::Since(Version {
+        major,
+        minor,
+        patch,
+        prerelease: None,
+    })
+}
+
+/// The capability matrix
+///
+/// Coldcard minimums depend on the model, which is part of `firmware`. Trezor firmware isn't
+/// reported, so no minimums are recorded for it.
+pub fn support(device_type: &HWIDeviceType, firmware: &Firmware, feature: Feature) -> Support {
+    use Feature::*;
+    use Support::*;
+
+    match device_type {
+        HWIDeviceType::Ledger => match feature {
+            Miniscript => since(2, 1, 0),
+            Taproot | WalletRegistration | RegistrationCheck | Hmac => since(2, 0, 0),
+            MessageSigning | DisplayAddress | PsbtGlobalXpubs => Supported,
+        },
+        HWIDeviceType::BitBox02 => match feature {
+            Miniscript => since(9, 21, 0),
+            Taproot => since(9, 10, 0),
+            Hmac => Unsupported,
+            WalletRegistration | RegistrationCheck => Supported,
+            MessageSigning | DisplayAddress | PsbtGlobalXpubs => Supported,
+        },
+        HWIDeviceType::Coldcard => match feature {
+            // Wallets are registered with REGISTER_MULTISIG, which needs approval on the device
+            RegistrationCheck | Hmac => Unsupported,
+            // Change verification fails on the global xpubs of a miniscript PSBT
+            PsbtGlobalXpubs => Unsupported,
+            // Miniscript came with the EDGE firmware, which the Mk4 and Q number alike but got
+            // in different releases
+            Miniscript => match firmware.model() {
+                Some(Model::ColdcardMk3) => Unsupported,
+                Some(Model::ColdcardMk4) => since(6, 0, 0),
+                Some(Model::ColdcardQ) => since(6, 3, 3),
+                None => match firmware {
+                    Firmware::NotReported => Supported,
+                    Firmware::Unknown | Firmware::Known { .. } => since(6, 0, 0),
+                },
+            },
+            Taproot => match firmware.model() {
+                Some(Model::ColdcardMk3) => Unsupported,
+                _ => Supported,
+            },
+            WalletRegistration | MessageSigning | DisplayAddress => Supported,
+        },
+        HWIDeviceType::Jade => match feature {
+            Miniscript | Hmac => Unsupported,
+            Taproot | WalletRegistration | RegistrationCheck => Supported,
+            MessageSigning | DisplayAddress | PsbtGlobalXpubs => Supported,
+        },
+        HWIDeviceType::Trezor => match feature {
+            Miniscript | WalletRegistration | RegistrationCheck | Hmac => Unsupported,
+            Taproot | MessageSigning | DisplayAddress | PsbtGlobalXpubs => Supported,
+        },
+        HWIDeviceType::KeepKey => match feature {
+            Miniscript | Taproot | WalletRegistration | RegistrationCheck | Hmac => Unsupported,
+            MessageSigning | DisplayAddress | PsbtGlobalXpubs => Supported,
+        },
+        HWIDeviceType::BitBox01 => match feature {
+            MessageSigning | PsbtGlobalXpubs => Supported,
+            _ => Unsupported,
+        },
+        HWIDeviceType::Other(name) if name.starts_with("specter") => match feature {
+            Miniscript | Taproot | WalletRegistration | PsbtGlobalXpubs => Supported,
+            RegistrationCheck | Hmac | MessageSigning | DisplayAddress => Unsupported,
+        },
+        HWIDeviceType::Other(_) => Unsupported,
+    }
+}
+
+/// What a device calls the software whose version is checked
+fn firmware_name(device_type: &HWIDeviceType) -> &'static str {
+    match device_type {
+        HWIDeviceType::Ledger => "Bitcoin app",
+        _ => "firmware",
+    }
+}
+
+pub fn supports(device_type: &HWIDeviceType, firmware: &Firmware, feature: Feature) -> bool {
+    support(device_type, firmware, feature).allows(firmware)
+}
+
+/// Features of a device, as sent to the phone so that it only offers what the device can do
+pub fn matrix(device_type: &HWIDeviceType, firmware: &Firmware) -> BTreeMap<Feature, bool> {
+    FEATURES
+        .into_iter()
+        .map(|feature| (feature, supports(device_type, firmware, feature)))
+        .collect()
+}
+
+/// Features a device supports, in the order of the matrix
+pub fn supported(device_type: &HWIDeviceType, firmware: &Firmware) -> Vec<Feature> {
+    FEATURES
+        .into_iter()
+        .filter(|feature| supports(device_type, firmware, *feature))
+        .collect()
+}
+
+/// Whether the selected device supports `feature`
+pub fn device_supports(hwi_state: &HWIClientState, feature: Feature) -> bool {
+    supports(&hwi_state.device_type, &hwi_state.firmware, feature)
+}
+
+/// Fails with the firmware to update to if the selected device only lacks a newer firmware
+pub fn require(hwi_state: &HWIClientState, feature: Feature) -> Result<(), CapabilityError> {
+    check(&hwi_state.device_type, &hwi_state.firmware, feature)
+}
+
+fn check(
+    device_type: &HWIDeviceType,
+    firmware: &Firmware,
+    feature: Feature,
+) -> Result<(), CapabilityError> {
+    match (support(device_type, firmware, feature), firmware) {
+        (support, firmware) if support.allows(firmware) => Ok(()),
+        (Support::Since(required), Firmware::Known { version, .. }) => {
+            Err(CapabilityError::FirmwareTooOld {
+                device_type: device_type.clone(),
+                feature,
+                required,
+                current: version.clone(),
+            })
+        }
+        (Support::Since(required), Firmware::Unknown) => Err(CapabilityError::VersionUnknown {
+            device_type: device_type.clone(),
+            feature,
+            required,
+        }),
+        _ => Err(CapabilityError::Unsupported(device_type.clone(), feature)),
+    }
+}
+
+/// Capability matrix of the selected device
+pub fn device_matrix(hwi_state: &HWIClientState) -> BTreeMap<Feature, bool> {
+    matrix(&hwi_state.device_type, &hwi_state.firmware)
+}
+
+/// Features needed to use a wallet policy
+pub fn policy_features(policy: &str) -> Vec<Feature> {
+    let policy = policy.split_once('#').map_or(policy, |(policy, _)| policy);
+    let mut features = Vec::new();
+    if policy.starts_with("tr(") {
+        features.push(Feature::Taproot);
+        // A key path only policy is tr(KEY)
+        if policy.contains(',') {
+            features.push(Feature::Miniscript);
+        }
+    } else if !is_plain_multisig(policy) {
+        features.push(Feature::Miniscript);
+    }
+    features
+}
+
+/// Whether the PSBT spends Taproot outputs
+pub fn spends_taproot(psbt: &Psbt) -> bool {
+    psbt.inputs
+        .iter()
+        .any(|input| input.tap_internal_key.is_some() || !input.tap_key_origins.is_empty())
+}
+
+/// `multi` or `sortedmulti`, possibly wrapped in `sh` and `wsh`
+fn is_plain_multisig(policy: &str) -> bool {
+    let mut inner = policy;
+    while let Some(rest) = inner
+        .strip_prefix("sh(")
+        .or_else(|| inner.strip_prefix("wsh("))
+    {
+        inner = rest;
+    }
+    ["multi(", "sortedmulti("].iter().any(|multi| {
+        inner
+            .strip_prefix(multi)
+            .is_some_and(|keys| !keys.contains('('))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(major: u32, minor: u32, patch: u32) -> Version {
+        Version {
+            major,
+            minor,
+            patch,
+            prerelease: None,
+        }
+    }
+
+    fn firmware(model: Option<Model>, major: u32, minor: u32, patch: u32) -> Firmware {
+        Firmware::Known {
+            version: version(major, minor, patch),
+            model,
+        }
+    }
+
+    #[test]
+    fn checks_features_against_firmware() {
+        let ledger = HWIDeviceType::Ledger;
+        assert!(supports(
+            &ledger,
+            &Firmware::NotReported,
+            Feature::Miniscript
+        ));
+        assert!(!supports(&ledger, &Firmware::Unknown, Feature::Miniscript));
+        assert!(supports(
+            &ledger,
+            &firmware(None, 2, 1, 3),
+            Feature::Miniscript
+        ));
+        assert!(!supports(
+            &ledger,
+            &firmware(None, 2, 0, 6),
+            Feature::Miniscript
+        ));
+        assert!(supports(
+            &ledger,
+            &firmware(None, 2, 0, 6),
+            Feature::Taproot
+        ));
+
+        let coldcard = matrix(&HWIDeviceType::Coldcard, &Firmware::NotReported);
+        assert!(coldcard[&Feature::Miniscript]);
+        assert!(!coldcard[&Feature::RegistrationCheck]);
+        assert!(!coldcard[&Feature::PsbtGlobalXpubs]);
+
+        let unknown = HWIDeviceType::Other("unknown".to_string());
+        assert!(supported(&unknown, &Firmware::NotReported).is_empty());
+    }
+
+    #[test]
+    fn checks_coldcard_models_separately() {
+        let coldcard = HWIDeviceType::Coldcard;
+        let mk4 = |major, minor, patch| firmware(Some(Model::ColdcardMk4), major, minor, patch);
+        let q = |major, minor, patch| firmware(Some(Model::ColdcardQ), major, minor, patch);
+
+        assert!(supports(&coldcard, &mk4(6, 0, 0), Feature::Miniscript));
+        assert!(!supports(&coldcard, &mk4(5, 4, 0), Feature::Miniscript));
+        assert!(supports(&coldcard, &mk4(5, 4, 0), Feature::Taproot));
+        assert!(supports(&coldcard, &q(6, 3, 3), Feature::Miniscript));
+        assert!(!supports(&coldcard, &q(6, 2, 0), Feature::Miniscript));
+        assert!(!supports(&coldcard, &q(1, 3, 0), Feature::Miniscript));
+
+        let mk3 = firmware(Some(Model::ColdcardMk3), 4, 1, 9);
+        assert!(matches!(
+            check(&coldcard, &mk3, Feature::Miniscript),
+            Err(CapabilityError::Unsupported(..))
+        ));
+        assert!(!supports(&coldcard, &mk3, Feature::Taproot));
+        assert!(supports(&coldcard, &mk3, Feature::WalletRegistration));
+    }
+
+    #[test]
+    fn asks_for_a_firmware_update() {
+        let ledger = HWIDeviceType::Ledger;
+        assert!(check(&ledger, &firmware(None, 2, 1, 0), Feature::Miniscript).is_ok());
+
+        let e = check(&ledger, &firmware(None, 2, 0, 6), Feature::Miniscript).unwrap_err();
+        assert!(matches!(
+            e,
+            CapabilityError::FirmwareTooOld { ref required, .. } if *required == version(2, 1, 0)
+        ));
+        assert_eq!(
+            e.to_string(),
+            "miniscript needs ledger Bitcoin app 2.1.0 or later, the device runs 2.0.6. \
+             Please update the Bitcoin app to 2.1.0"
+        );
+
+        let e = check(&ledger, &Firmware::Unknown, Feature::Miniscript).unwrap_err();
+        assert!(matches!(e, CapabilityError::VersionUnknown { .. }));
+        assert_eq!(
+            e.to_string(),
+            "miniscript needs ledger Bitcoin app 2.1.0 or later, but the version on the device \
+             could not be read. Please reconnect the device, or update the Bitcoin app to 2.1.0"
+        );
+
+        assert!(matches!(
+            check(
+                &HWIDeviceType::Trezor,
+                &Firmware::NotReported,
+                Feature::Miniscript
+            ),
+            Err(CapabilityError::Unsupported(..))
+        ));
+    }
+
+    #[test]
+    fn derives_features_of_policies() {
+        let key = "[e2867bb6/48'/1'/0'/2']tpubDEF/<0;1>/*";
+        assert!(policy_features(&format!("wsh(sortedmulti(2,{},{}))", key, key)).is_empty());
+        assert_eq!(
+            policy_features(&format!(
+                "wsh(or_d(pk({}),and_v(v:pkh({}),older(52560))))",
+                key, key
+            )),
+            vec![Feature::Miniscript]
+        );
+        assert_eq!(
+            policy_features(&format!("tr({})#abcdefgh", key)),
+            vec![Feature::Taproot]
+        );
+        assert_eq!(
+            policy_features(&format!("tr({},pk({}))", key, key)),
+            vec![Feature::Taproot, Feature::Miniscript]
+        );
+    }
+}
@@ -1,12 +1,19 @@
 use aes_gcm::aead::rand_core::RngCore;
 use aes_gcm::aead::{Aead, KeyInit, OsRng};
 use aes_gcm::{Aes256Gcm, Key, Nonce};
+use bitcoin::base64::{engine::general_purpose, Engine as _};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
 use log::{error, info, warn};
+use native_tls::TlsConnector;
 use rust_socketio::client::Client;
 use rust_socketio::{ClientBuilder, Event, Payload};
 use serde_json::json;
 use sha2::{Digest, Sha256};
+use std::io::{Read, Write};
 use std::ops::Drop;
+use std::sync::{Arc, Mutex, PoisonError};
 use std::time::Duration;
 use tauri::Manager;
 use thiserror::Error;
@@ -18,6 +25,17 @@ static URL: &str = "https://keeper-channel-dev-8d01fa5233d0.herokuapp.com/";
 #[cfg(feature = "release")]
 static URL: &str = "https://keeper-channel.herokuapp.com/";
 
+/// SHA-256 hashes of the relay's SubjectPublicKeyInfo, written as `sha256/<base64>`.
+/// Pinning is disabled when empty.
+///
+/// Can be overridden at runtime with a comma separated list in `KEEPER_RELAY_SPKI_PINS`.
+static RELAY_SPKI_PINS: &[&str] = &[];
+
+const RELAY_PORT: u16 = 443;
+
+/// Upper bound for decompressed payloads, protecting against compression bombs
+const MAX_DECOMPRESSED_SIZE: u64 = 16 * 1024 * 1024;
+
 #[derive(Error, Debug)]
 pub enum ChannelError {
     #[error("No client available")]
@@ -40,39 +58,133 @@ pub enum ChannelError {
     IoError(#[from] std::io::Error),
     #[error("Hex decoding error: {0}")]
     HexError(#[from] hex::FromHexError),
+    #[error("Base64 decoding error: {0}")]
+    Base64Error(#[from] bitcoin::base64::DecodeError),
+    #[error("Compression error: {0}")]
+    CompressionError(String),
+    #[error("Unsupported payload format: {0}")]
+    UnsupportedFormat(String),
     #[error("UTF-8 conversion error: {0}")]
     Utf8Error(#[from] std::string::FromUtf8Error),
     #[error("Socket.IO error: {0}")]
     SocketIoError(String),
     #[error("Connection timed out")]
     ConnectionTimeout,
+    #[error("TLS error: {0}")]
+    TlsError(String),
+    #[error("Relay certificate for {0} does not match any pinned key")]
+    CertificatePinMismatch(String),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PayloadEncoding {
+    #[default]
+    Hex,
+    Base64,
+}
+
+/// Describes how an encrypted payload is encoded, as signalled by the envelope headers
+///
+/// Envelopes without headers use the legacy format: uncompressed and hex encoded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PayloadFormat {
+    pub compressed: bool,
+    pub encoding: PayloadEncoding,
+}
+
+impl PayloadFormat {
+    fn from_envelope(envelope: &serde_json::Value) -> Result<Self, ChannelError> {
+        let compressed = match envelope["compression"].as_str() {
+            None => false,
+            Some("deflate") => true,
+            Some(other) => return Err(ChannelError::UnsupportedFormat(other.to_string())),
+        };
+        let encoding = match envelope["encoding"].as_str() {
+            None | Some("hex") => PayloadEncoding::Hex,
+            Some("base64") => PayloadEncoding::Base64,
+            Some(other) => return Err(ChannelError::UnsupportedFormat(other.to_string())),
+        };
+        Ok(PayloadFormat {
+            compressed,
+            encoding,
+        })
+    }
+
+    fn write_headers(&self, envelope: &mut serde_json::Value) {
+        if self.compressed {
+            envelope["compression"] = json!("deflate");
+        }
+        if self.encoding == PayloadEncoding::Base64 {
+            envelope["encoding"] = json!("base64");
+        }
+    }
+
+    /// Associated data binding the headers to the ciphertext, so that they can't be altered
+    /// in transit. Legacy envelopes have none, which keeps them readable by older peers.
+    fn associated_data(&self) -> Vec<u8> {
+        if *self == PayloadFormat::default() {
+            return Vec::new();
+        }
+        let compression = if self.compressed { "deflate" } else { "none" };
+        let encoding = match self.encoding {
+            PayloadEncoding::Hex => "hex",
+            PayloadEncoding::Base64 => "base64",
+        };
+        format!("compression={};encoding={}", compression, encoding).into_bytes()
+    }
+
+    fn encode(&self, bytes: &[u8]) -> String {
+        match self.encoding {
+            PayloadEncoding::Hex => hex::encode(bytes),
+            PayloadEncoding::Base64 => general_purpose::STANDARD.encode(bytes),
+        }
+    }
+
+    fn decode(&self, value: &str) -> Result<Vec<u8>, ChannelError> {
+        match self.encoding {
+            PayloadEncoding::Hex => Ok(hex::decode(value)?),
+            PayloadEncoding::Base64 => Ok(general_purpose::STANDARD.decode(value)?),
+        }
+    }
 }
 
 pub struct Channel {
     pub client: Option<Client>,
+    /// Pinned connection to the relay the client goes through, if pins are configured. Only
+    /// held to keep it open for as long as the client.
+    _relay: Option<PinnedRelay>,
     pub room: Option<String>,
     pub encryption_key: Option<String>,
+    /// Format used for outgoing payloads, upgraded once the peer sends a newer format
+    pub payload_format: PayloadFormat,
 }
 
 impl Channel {
     pub async fn new(app_handle: tauri::AppHandle, timeout_secs: u64) -> Self {
-        let client = create_client_with_timeout(app_handle, timeout_secs).await;
-        if let Err(e) = &client {
-            error!("Error connecting to channel: {}", e);
-        }
+        let (client, relay) = match create_client_with_timeout(app_handle, timeout_secs).await {
+            Ok((client, relay)) => (Some(client), relay),
+            Err(e) => {
+                error!("Error connecting to channel: {}", e);
+                (None, None)
+            }
+        };
 
         Channel {
-            client: client.ok(),
+            client,
+            _relay: relay,
             room: None,
             encryption_key: None,
+            payload_format: PayloadFormat::default(),
         }
     }
 
     pub fn new_empty() -> Self {
         Channel {
             client: None,
+            _relay: None,
             room: None,
             encryption_key: None,
+            payload_format: PayloadFormat::default(),
         }
     }
 
@@ -134,6 +246,7 @@ impl Channel {
         let room = hex::encode(Sha256::digest(&key));
         self.encryption_key = Some(key.clone());
         self.room = Some(room.clone());
+        self.payload_format = PayloadFormat::default();
 
         self.emit("JOIN_CHANNEL", json!({"room": room}), true, None)?;
 
@@ -142,7 +255,8 @@ impl Channel {
 
     /// Encrypts the provided data using AES-256-GCM
     ///
-    /// Returns a JSON object containing the iv, encrypted data, and authTag
+    /// The plaintext is deflated first when the peer supports compressed payloads.
+    /// Returns a JSON object containing the iv, encrypted data, authTag and format headers
     fn encrypt_data(&self, data: serde_json::Value) -> Result<serde_json::Value, ChannelError> {
         let encryption_key = self
             .encryption_key
@@ -157,24 +271,39 @@ impl Channel {
         OsRng.fill_bytes(&mut nonce_bytes);
         let nonce = Nonce::from_slice(&nonce_bytes); // Use the variable here
         let data = data.to_string();
-        let plaintext = data.as_bytes();
+        let format = self.payload_format;
+        let plaintext = if format.compressed {
+            compress(data.as_bytes())?
+        } else {
+            data.into_bytes()
+        };
 
+        let aad = format.associated_data();
         let ciphertext_with_tag = cipher
-            .encrypt(nonce, plaintext)
+            .encrypt(
+                nonce,
+                aes_gcm::aead::Payload {
+                    msg: &plaintext,
+                    aad: &aad,
+                },
+            )
             .map_err(|e| ChannelError::EncryptionError(e.to_string()))?;
 
         let (ciphertext, auth_tag) = ciphertext_with_tag.split_at(ciphertext_with_tag.len() - 16);
 
-        Ok(json!({
-            "iv": hex::encode(nonce),
-            "encryptedData": hex::encode(ciphertext),
-            "authTag": hex::encode(auth_tag)
-        }))
+        let mut envelope = json!({
+            "iv": format.encode(nonce),
+            "encryptedData": format.encode(ciphertext),
+            "authTag": format.encode(auth_tag)
+        });
+        format.write_headers(&mut envelope);
+        Ok(envelope)
     }
 
     /// Decrypts the provided encrypted data
     ///
-    /// Expects a JSON object containing the iv, encrypted data, and authTag
+    /// Expects a JSON object containing the iv, encrypted data, and authTag, along with
+    /// optional `compression` and `encoding` headers which are authenticated as associated data
     pub fn decrypt_data(
         &self,
         encrypted: &serde_json::Value,
@@ -188,35 +317,52 @@ impl Channel {
         let key = Key::<Aes256Gcm>::from_slice(&key_bytes);
         let cipher = Aes256Gcm::new(key);
 
-        let nonce = hex::decode(encrypted["iv"].as_str().ok_or(ChannelError::InvalidIV)?)?;
-        let encrypted_data = hex::decode(
+        let format = PayloadFormat::from_envelope(encrypted)?;
+        let nonce = format.decode(encrypted["iv"].as_str().ok_or(ChannelError::InvalidIV)?)?;
+        let encrypted_data = format.decode(
             encrypted["encryptedData"]
                 .as_str()
                 .ok_or(ChannelError::InvalidEncryptedData)?,
         )?;
-        let auth_tag = hex::decode(
+        let auth_tag = format.decode(
             encrypted["authTag"]
                 .as_str()
                 .ok_or(ChannelError::InvalidEncryptedData)?,
         )?;
 
+        if nonce.len() != 12 {
+            return Err(ChannelError::InvalidIV);
+        }
         let nonce = Nonce::from_slice(&nonce);
 
         let mut combined_data = Vec::with_capacity(encrypted_data.len() + auth_tag.len());
         combined_data.extend_from_slice(&encrypted_data);
         combined_data.extend_from_slice(&auth_tag);
 
+        let aad = format.associated_data();
         let decrypted_data = cipher
-            .decrypt(nonce, combined_data.as_ref())
+            .decrypt(
+                nonce,
+                aes_gcm::aead::Payload {
+                    msg: &combined_data,
+                    aad: &aad,
+                },
+            )
             .map_err(|e| ChannelError::DecryptionError(e.to_string()))?;
 
+        let decrypted_data = if format.compressed {
+            decompress(&decrypted_data)?
+        } else {
+            decrypted_data
+        };
+
         let decrypted_string = String::from_utf8(decrypted_data)?;
 
         serde_json::from_str(&decrypted_string).map_err(ChannelError::from)
     }
 
     pub fn process_channel_message(
-        &self,
+        &mut self,
         message: &serde_json::Value,
     ) -> Result<serde_json::Value, String> {
         let data = message
@@ -233,8 +379,14 @@ impl Channel {
             .ok_or("Failed to parse message network")?;
 
         let data = if request_data.get("encryptedData").is_some() {
-            self.decrypt_data(request_data)
-                .map_err(|_| "Failed to decrypt message from channel")?
+            let data = self
+                .decrypt_data(request_data)
+                .map_err(|_| "Failed to decrypt message from channel")?;
+            // Reply in the same format the peer used, which also negotiates compression
+            if let Ok(format) = PayloadFormat::from_envelope(request_data) {
+                self.payload_format = format;
+            }
+            data
         } else {
             request_data.clone()
         };
@@ -251,10 +403,29 @@ impl Drop for Channel {
     }
 }
 
+fn compress(data: &[u8]) -> Result<Vec<u8>, ChannelError> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+fn decompress(data: &[u8]) -> Result<Vec<u8>, ChannelError> {
+    let mut decompressed = Vec::new();
+    DeflateDecoder::new(data)
+        .take(MAX_DECOMPRESSED_SIZE + 1)
+        .read_to_end(&mut decompressed)?;
+    if decompressed.len() as u64 > MAX_DECOMPRESSED_SIZE {
+        return Err(ChannelError::CompressionError(
+            "Decompressed payload is too large".to_string(),
+        ));
+    }
+    Ok(decompressed)
+}
+
 async fn create_client_with_timeout(
     app_handle: tauri::AppHandle,
     timeout_secs: u64,
-) -> Result<Client, ChannelError> {
+) -> Result<(Client, Option<PinnedRelay>), ChannelError> {
     let client_future = tokio::task::spawn_blocking(move || create_client(app_handle));
 
     match timeout(Duration::from_secs(timeout_secs), client_future).await {
@@ -263,8 +434,29 @@ async fn create_client_with_timeout(
     }
 }
 
-fn create_client(app_handle: tauri::AppHandle) -> Result<Client, ChannelError> {
-    ClientBuilder::new(URL)
+fn create_client(
+    app_handle: tauri::AppHandle,
+) -> Result<(Client, Option<PinnedRelay>), ChannelError> {
+    let pins = relay_spki_pins()?;
+    let relay = if pins.is_empty() {
+        None
+    } else {
+        let connector = TlsConnector::new().map_err(|e| ChannelError::TlsError(e.to_string()))?;
+        Some(PinnedRelay::start(
+            relay_host(URL).to_string(),
+            RELAY_PORT,
+            pins,
+            connector,
+        )?)
+    };
+    let builder = match &relay {
+        None => ClientBuilder::new(URL),
+        // The relay routes requests by host name, which the loopback URL doesn't carry
+        Some(relay) => ClientBuilder::new(format!("http://127.0.0.1:{}/", relay.port))
+            .opening_header("Host", relay_host(URL)),
+    };
+
+    let client = builder
         .on(Event::Connect, |_, _| {
             info!("Channel connected");
         })
@@ -278,12 +470,12 @@ fn create_client(app_handle: tauri::AppHandle) -> Result<Client, ChannelError> {
                     Payload::Text(text) => {
                         info!("Channel received event: {:?} with message: {:?}", event.as_str(), text);
                         if event.as_str() == "CHANNEL_MESSAGE" {
-                            if let Ok(state) = app_handle.state::<crate::AppState>().try_lock() {
+                            if let Ok(mut state) = app_handle.state::<crate::AppState>().try_lock() {
                                 let text = serde_json::to_value(text).map_err(|_| "Failed to parse message as JSON");
                                 if let Ok(text) = text {
                                     match state.channel.process_channel_message(&text) {
                                         Ok(processed_data) => {
-                                            if let Err(e) = app_handle.emit_all("channel-message", processed_data) {
+                                            if let Err(e) = crate::approval::dispatch_channel_message(&app_handle, &mut state, processed_data) {
                                                 error!("Failed to emit channel-message event: {:?}, got error: {:?}", text, e);
                                             }
                                         },
@@ -300,5 +492,361 @@ fn create_client(app_handle: tauri::AppHandle) -> Result<Client, ChannelError> {
             }
         })
         .connect()
-        .map_err(|e| ChannelError::SocketIoError(e.to_string()))
+        .map_err(|e| {
+            // A failed connection to the relay surfaces as a generic error of the client
+            match relay.as_ref().and_then(PinnedRelay::take_failure) {
+                Some(failure) => failure,
+                None => ChannelError::SocketIoError(e.to_string()),
+            }
+        })?;
+    Ok((client, relay))
+}
+
+fn relay_spki_pins() -> Result<Vec<[u8; 32]>, ChannelError> {
+    match std::env::var("KEEPER_RELAY_SPKI_PINS") {
+        Ok(pins) => pins
+            .split(',')
+            .map(str::trim)
+            .filter(|pin| !pin.is_empty())
+            .map(parse_spki_pin)
+            .collect(),
+        Err(_) => RELAY_SPKI_PINS
+            .iter()
+            .map(|pin| parse_spki_pin(pin))
+            .collect(),
+    }
+}
+
+/// Parses a pin in the `sha256/<base64>` form used by HPKP and curl's `--pinnedpubkey`
+fn parse_spki_pin(pin: &str) -> Result<[u8; 32], ChannelError> {
+    pin.strip_prefix("sha256/")
+        .and_then(|hash| general_purpose::STANDARD.decode(hash).ok())
+        .and_then(|hash| hash.try_into().ok())
+        .ok_or_else(|| {
+            ChannelError::TlsError(format!(
+                "Invalid relay pin {}, expected sha256/<base64>",
+                pin
+            ))
+        })
+}
+
+/// Loopback endpoint the Socket.IO client connects to when the relay is pinned
+///
+/// native-tls offers no hook into certificate verification, so the TLS connections to the
+/// relay are made here instead of in the client. Every connection the client opens, reconnects
+/// included, is forwarded over a new TLS connection whose leaf certificate is checked against
+/// the pins before any data is sent.
+struct PinnedRelay {
+    port: u16,
+    /// Why the last connection to the relay failed
+    failure: Arc<Mutex<Option<ChannelError>>>,
+    task: tauri::async_runtime::JoinHandle<()>,
+}
+
+impl PinnedRelay {
+    fn start(
+        host: String,
+        port: u16,
+        pins: Vec<[u8; 32]>,
+        connector: TlsConnector,
+    ) -> Result<Self, ChannelError> {
+        let listener = std::net::TcpListener::bind(("127.0.0.1", 0))?;
+        listener.set_nonblocking(true)?;
+        let local_port = listener.local_addr()?.port();
+
+        let failure = Arc::new(Mutex::new(None));
+        let connector = tokio_native_tls::TlsConnector::from(connector);
+        let relay = Arc::new((host, port, pins, connector));
+        let task = tauri::async_runtime::spawn({
+            let failure = failure.clone();
+            async move {
+                let listener = match tokio::net::TcpListener::from_std(listener) {
+                    Ok(listener) => listener,
+                    Err(e) => return error!("Failed to listen for relay connections: {}", e),
+                };
+                loop {
+                    let mut local = match listener.accept().await {
+                        Ok((local, _)) => local,
+                        Err(e) => {
+                            warn!("Failed to accept relay connection: {}", e);
+                            continue;
+                        }
+                    };
+                    let relay = relay.clone();
+                    let failure = failure.clone();
+                    tauri::async_runtime::spawn(async move {
+                        let (host, port, pins, connector) = &*relay;
+                        match connect_pinned(connector, host, *port, pins).await {
+                            Ok(mut remote) => {
+                                let _ =
+                                    tokio::io::copy_bidirectional(&mut local, &mut remote).await;
+                            }
+                            Err(e) => {
+                                error!("Refusing relay connection: {}", e);
+                                *failure.lock().unwrap_or_else(PoisonError::into_inner) = Some(e);
+                            }
+                        }
+                    });
+                }
+            }
+        });
+
+        Ok(PinnedRelay {
+            port: local_port,
+            failure,
+            task,
+        })
+    }
+
+    fn take_failure(&self) -> Option<ChannelError> {
+        self.failure
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+    }
+}
+
+impl Drop for PinnedRelay {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Opens a TLS connection to the relay and checks its leaf certificate against the pins
+async fn connect_pinned(
+    connector: &tokio_native_tls::TlsConnector,
+    host: &str,
+    port: u16,
+    pins: &[[u8; 32]],
+) -> Result<tokio_native_tls::TlsStream<tokio::net::TcpStream>, ChannelError> {
+    let stream = tokio::net::TcpStream::connect((host, port)).await?;
+    let stream = connector
+        .connect(host, stream)
+        .await
+        .map_err(|e| ChannelError::TlsError(e.to_string()))?;
+
+    let certificate = stream
+        .get_ref()
+        .peer_certificate()
+        .map_err(|e| ChannelError::TlsError(e.to_string()))?
+        .ok_or_else(|| ChannelError::TlsError("Relay sent no certificate".to_string()))?
+        .to_der()
+        .map_err(|e| ChannelError::TlsError(e.to_string()))?;
+    let spki = subject_public_key_info(&certificate)
+        .ok_or_else(|| ChannelError::TlsError("Malformed relay certificate".to_string()))?;
+    let spki_hash: [u8; 32] = Sha256::digest(spki).into();
+
+    if pins.contains(&spki_hash) {
+        Ok(stream)
+    } else {
+        error!(
+            "Relay SPKI hash sha256/{} is not pinned",
+            general_purpose::STANDARD.encode(spki_hash)
+        );
+        Err(ChannelError::CertificatePinMismatch(host.to_string()))
+    }
+}
+
+/// Splits the DER element at the start of `der` into (element, content, rest)
+fn der_element(der: &[u8]) -> Option<(&[u8], &[u8], &[u8])> {
+    let first_len = *der.get(1)?;
+    let (header_len, content_len) = if first_len < 0x80 {
+        (2, first_len as usize)
+    } else {
+        let len_bytes = (first_len & 0x7f) as usize;
+        if len_bytes == 0 || len_bytes > 4 {
+            return None;
+        }
+        let len = der
+            .get(2..2 + len_bytes)?
+            .iter()
+            .fold(0usize, |acc, b| (acc << 8) | *b as usize);
+        (2 + len_bytes, len)
+    };
+    let end = header_len.checked_add(content_len)?;
+    let element = der.get(..end)?;
+    Some((element, &element[header_len..], &der[end..]))
+}
+
+/// Extracts the DER encoded SubjectPublicKeyInfo from an X.509 certificate
+fn subject_public_key_info(certificate: &[u8]) -> Option<&[u8]> {
+    let (_, certificate, _) = der_element(certificate)?;
+    let (_, tbs_certificate, _) = der_element(certificate)?;
+
+    let mut rest = tbs_certificate;
+    // Skip the optional explicit version tag
+    if rest.first() == Some(&0xa0) {
+        rest = der_element(rest)?.2;
+    }
+    // Skip serialNumber, signature, issuer, validity and subject
+    for _ in 0..5 {
+        rest = der_element(rest)?.2;
+    }
+    der_element(rest).map(|(spki, _, _)| spki)
+}
+
+/// Host of the relay, as named in pin mismatch errors
+fn relay_host(url: &str) -> &str {
+    url.trim_start_matches("https://")
+        .split(['/', ':'])
+        .next()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel(format: PayloadFormat) -> Channel {
+        let mut channel = Channel::new_empty();
+        channel.encryption_key = Some(hex::encode([7u8; 32]));
+        channel.payload_format = format;
+        channel
+    }
+
+    #[test]
+    fn round_trips_every_payload_format() {
+        let data = json!({"action": "SIGN_TX", "psbt": "cHNidP8B".repeat(64)});
+        for compressed in [false, true] {
+            for encoding in [PayloadEncoding::Hex, PayloadEncoding::Base64] {
+                let format = PayloadFormat {
+                    compressed,
+                    encoding,
+                };
+                let channel = channel(format);
+                let envelope = channel.encrypt_data(data.clone()).unwrap();
+                assert_eq!(PayloadFormat::from_envelope(&envelope).unwrap(), format);
+                assert_eq!(channel.decrypt_data(&envelope).unwrap(), data);
+            }
+        }
+    }
+
+    #[test]
+    fn authenticates_format_headers() {
+        let compressed = channel(PayloadFormat {
+            compressed: true,
+            encoding: PayloadEncoding::Hex,
+        });
+        let mut envelope = compressed.encrypt_data(json!({"action": "XPUB"})).unwrap();
+        envelope.as_object_mut().unwrap().remove("compression");
+        assert!(matches!(
+            compressed.decrypt_data(&envelope),
+            Err(ChannelError::DecryptionError(_))
+        ));
+
+        // Legacy envelopes carry no associated data and can't be given headers either
+        let legacy = channel(PayloadFormat::default());
+        let mut envelope = legacy.encrypt_data(json!({"action": "XPUB"})).unwrap();
+        envelope["compression"] = json!("deflate");
+        assert!(legacy.decrypt_data(&envelope).is_err());
+    }
+
+    fn relay_fixture(name: &str) -> std::path::PathBuf {
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join("relay")
+            .join(name)
+    }
+
+    /// Starts a local relay, whose certificate is issued by the CA in `ca.pem`, echoing what
+    /// it receives
+    fn echo_relay() -> u16 {
+        let identity = std::fs::read(relay_fixture("relay.p12")).unwrap();
+        let identity = native_tls::Identity::from_pkcs12(&identity, "keeper").unwrap();
+        let acceptor = native_tls::TlsAcceptor::new(identity).unwrap();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                if let Ok(mut stream) = acceptor.accept(stream.unwrap()) {
+                    let mut buf = [0u8; 4];
+                    if stream.read_exact(&mut buf).is_ok() {
+                        let _ = stream.write_all(&buf);
+                    }
+                }
+            }
+        });
+        port
+    }
+
+    /// Sends "ping" through a pinned relay, returning what comes back
+    fn ping(relay: &PinnedRelay) -> Vec<u8> {
+        let mut stream = std::net::TcpStream::connect(("127.0.0.1", relay.port)).unwrap();
+        stream.write_all(b"ping").unwrap();
+        let mut response = Vec::new();
+        let _ = stream.read_to_end(&mut response);
+        response
+    }
+
+    fn start_relay(pin: &str, trust_test_ca: bool) -> PinnedRelay {
+        let mut connector = TlsConnector::builder();
+        if trust_test_ca {
+            let ca = std::fs::read(relay_fixture("ca.pem")).unwrap();
+            connector.add_root_certificate(native_tls::Certificate::from_pem(&ca).unwrap());
+        }
+        PinnedRelay::start(
+            "localhost".to_string(),
+            echo_relay(),
+            vec![parse_spki_pin(pin).unwrap()],
+            connector.build().unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn checks_relay_pins_on_every_connection() {
+        let relay = start_relay("sha256/4J7a50JOBgE1FdA2UX+GuaaqYf6m0f2We1SqbqyPnTo=", true);
+        for _ in 0..2 {
+            assert_eq!(ping(&relay), b"ping");
+        }
+        assert!(relay.take_failure().is_none());
+
+        // The CA's key doesn't match, only the relay's own key is pinned
+        let relay = start_relay("sha256/cEL+RDcDHa4KW8UdmtuF5sYPqJYSUAdrYYt8/5PseN0=", true);
+        assert!(ping(&relay).is_empty());
+        assert!(matches!(
+            relay.take_failure(),
+            Some(ChannelError::CertificatePinMismatch(host)) if host == "localhost"
+        ));
+
+        // Certificates are still verified, a pinned key doesn't replace the chain
+        let relay = start_relay("sha256/4J7a50JOBgE1FdA2UX+GuaaqYf6m0f2We1SqbqyPnTo=", false);
+        assert!(ping(&relay).is_empty());
+        assert!(matches!(
+            relay.take_failure(),
+            Some(ChannelError::TlsError(_))
+        ));
+    }
+
+    #[test]
+    fn parses_spki_pins() {
+        let hash = "cEL+RDcDHa4KW8UdmtuF5sYPqJYSUAdrYYt8/5PseN0=";
+        assert_eq!(
+            parse_spki_pin(&format!("sha256/{}", hash))
+                .unwrap()
+                .to_vec(),
+            general_purpose::STANDARD.decode(hash).unwrap()
+        );
+        assert!(parse_spki_pin(hash).is_err());
+        assert!(parse_spki_pin("sha256/cEL+RDcDHa4KW8Ud").is_err());
+        assert_eq!(
+            relay_host(URL),
+            relay_host(&format!("{}:443", URL.trim_end_matches('/')))
+        );
+    }
+
+    #[test]
+    fn limits_decompressed_size() {
+        let data = vec![0u8; MAX_DECOMPRESSED_SIZE as usize];
+        assert_eq!(
+            decompress(&compress(&data).unwrap()).unwrap().len(),
+            data.len()
+        );
+
+        let data = vec![0u8; MAX_DECOMPRESSED_SIZE as usize + 1];
+        assert!(matches!(
+            decompress(&compress(&data).unwrap()),
+            Err(ChannelError::CompressionError(_))
+        ));
+    }
 }
@@ -0,0 +1,328 @@
+use bitcoin::base58;
+use bitcoin::Network;
+use miniscript::descriptor::checksum::desc_checksum;
+use serde::Deserialize;
+use serde_json::json;
+use std::path::Path;
+use thiserror::Error;
+
+use crate::hwi::types::{HWIAddressType, HWIDescriptor, HWIKeyPoolElement};
+
+/// Addresses Bitcoin Core derives ahead of the last used one
+pub const CORE_RANGE_END: u32 = 999;
+
+/// Length of a serialized extended key, BIP32
+const EXTENDED_KEY_LEN: usize = 78;
+
+#[derive(Error, Debug)]
+pub enum ExportError {
+    #[error("The device returned no {0} descriptor for this account")]
+    MissingDescriptor(HWIAddressType),
+    #[error("Invalid descriptor: {0}")]
+    InvalidDescriptor(String),
+    #[error("{0:?} does not support {1} wallets")]
+    UnsupportedFormat(ExportFormat, HWIAddressType),
+    #[error("Failed to write the export: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Wallet software the watch-only descriptors are exported for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ExportFormat {
+    /// JSON for Bitcoin Core's `importdescriptors`
+    Core,
+    /// Multipath descriptor, which Sparrow imports from a file or the clipboard
+    Sparrow,
+    /// Watch-only Electrum wallet file
+    Electrum,
+    /// Receive and change descriptors, one per line
+    Text,
+}
+
+impl ExportFormat {
+    /// Extension of the exported file
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Core | ExportFormat::Electrum => "json",
+            ExportFormat::Sparrow | ExportFormat::Text => "txt",
+        }
+    }
+}
+
+/// Receive and change descriptors of one account, with checksums
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccountDescriptors {
+    pub address_type: HWIAddressType,
+    pub receive: String,
+    pub change: String,
+}
+
+impl AccountDescriptors {
+    /// Picks the descriptors of `address_type` from the output of `getdescriptors`
+    pub fn from_hwi(
+        descriptors: HWIDescriptor<String>,
+        address_type: HWIAddressType,
+    ) -> Result<Self, ExportError> {
+        Ok(AccountDescriptors {
+            receive: find(&descriptors.receive, &address_type)?,
+            change: find(&descriptors.internal, &address_type)?,
+            address_type,
+        })
+    }
+
+    /// Picks the descriptors of `address_type` from the output of `getkeypool`, once called
+    /// for the receive and once for the change keys
+    pub fn from_keypool(
+        keypool: Vec<HWIKeyPoolElement>,
+        address_type: HWIAddressType,
+    ) -> Result<Self, ExportError> {
+        let (change, receive): (Vec<_>, Vec<_>) =
+            keypool.into_iter().partition(|element| element.internal);
+        let descriptors = |elements: Vec<HWIKeyPoolElement>| {
+            elements
+                .into_iter()
+                .map(|element| element.desc)
+                .collect::<Vec<_>>()
+        };
+        Ok(AccountDescriptors {
+            receive: find(&descriptors(receive), &address_type)?,
+            change: find(&descriptors(change), &address_type)?,
+            address_type,
+        })
+    }
+
+    /// Renders the export file of `format`
+    pub fn export(&self, format: ExportFormat, network: Network) -> Result<String, ExportError> {
+        match format {
+            ExportFormat::Core => {
+                let entries: Vec<_> = [(&self.receive, false), (&self.change, true)]
+                    .into_iter()
+                    .map(|(descriptor, internal)| {
+                        json!({
+                            "desc": descriptor,
+                            "active": true,
+                            "internal": internal,
+                            "range": [0, CORE_RANGE_END],
+                            "timestamp": "now",
+                        })
+                    })
+                    .collect();
+                Ok(serde_json::to_string_pretty(&entries).map_err(std::io::Error::from)?)
+            }
+            ExportFormat::Sparrow => {
+                let receive = strip_checksum(&self.receive);
+                let multipath = receive.replacen("/0/*", "/<0;1>/*", 1);
+                if multipath == receive {
+                    return Err(ExportError::InvalidDescriptor(self.receive.clone()));
+                }
+                Ok(format!("{}\n", with_checksum(&multipath)?))
+            }
+            ExportFormat::Electrum => self.electrum_wallet(network),
+            ExportFormat::Text => Ok(format!("{}\n{}\n", self.receive, self.change)),
+        }
+    }
+
+    fn electrum_wallet(&self, network: Network) -> Result<String, ExportError> {
+        let version = slip132_version(&self.address_type, network).ok_or(
+            ExportError::UnsupportedFormat(ExportFormat::Electrum, self.address_type.clone()),
+        )?;
+        let (fingerprint, path, xpub) = key_origin(&self.receive)?;
+
+        let wallet = json!({
+            "keystore": {
+                "type": "bip32",
+                "xpub": with_version(xpub, version)?,
+                "xprv": null,
+                "derivation": format!("m/{}", path.replace('h', "'")),
+                "root_fingerprint": fingerprint,
+                "label": "",
+            },
+            "wallet_type": "standard",
+            "use_encryption": false,
+            "seed_version": 17,
+        });
+        Ok(serde_json::to_string_pretty(&wallet).map_err(std::io::Error::from)?)
+    }
+}
+
+pub fn write(contents: &str, destination: &Path) -> Result<(), ExportError> {
+    Ok(std::fs::write(destination, contents)?)
+}
+
+/// The first of `descriptors` with the script of `address_type`, with a fresh checksum
+fn find(descriptors: &[String], address_type: &HWIAddressType) -> Result<String, ExportError> {
+    descriptors
+        .iter()
+        .map(|descriptor| strip_checksum(descriptor))
+        .find(|descriptor| descriptor.starts_with(script_prefix(address_type)))
+        .map(with_checksum)
+        .transpose()?
+        .ok_or(ExportError::MissingDescriptor(address_type.clone()))
+}
+
+fn script_prefix(address_type: &HWIAddressType) -> &'static str {
+    match address_type {
+        HWIAddressType::Legacy => "pkh(",
+        HWIAddressType::Sh_Wit => "sh(wpkh(",
+        HWIAddressType::Wit => "wpkh(",
+        HWIAddressType::Tap => "tr(",
+    }
+}
+
+/// SLIP-132 key version Electrum expects for the script type, it has no Taproot support
+fn slip132_version(address_type: &HWIAddressType, network: Network) -> Option<[u8; 4]> {
+    let mainnet = network == Network::Bitcoin;
+    match (address_type, mainnet) {
+        (HWIAddressType::Legacy, true) => Some([0x04, 0x88, 0xb2, 0x1e]),
+        (HWIAddressType::Legacy, false) => Some([0x04, 0x35, 0x87, 0xcf]),
+        (HWIAddressType::Sh_Wit, true) => Some([0x04, 0x9d, 0x7c, 0xb2]),
+        (HWIAddressType::Sh_Wit, false) => Some([0x04, 0x4a, 0x52, 0x62]),
+        (HWIAddressType::Wit, true) => Some([0x04, 0xb2, 0x47, 0x46]),
+        (HWIAddressType::Wit, false) => Some([0x04, 0x5f, 0x1c, 0xf6]),
+        (HWIAddressType::Tap, _) => None,
+    }
+}
+
+/// Re-encodes an extended key with the 4 version bytes of another network or script type
+fn with_version(xpub: &str, version: [u8; 4]) -> Result<String, ExportError> {
+    let mut data =
+        base58::decode_check(xpub).map_err(|e| ExportError::InvalidDescriptor(e.to_string()))?;
+    if data.len() != EXTENDED_KEY_LEN {
+        return Err(ExportError::InvalidDescriptor(xpub.to_string()));
+    }
+    data[..4].copy_from_slice(&version);
+    Ok(base58::encode_check(&data))
+}
+
+/// Splits the single key of a descriptor into fingerprint, origin path and xpub
+fn key_origin(descriptor: &str) -> Result<(&str, &str, &str), ExportError> {
+    let invalid = || ExportError::InvalidDescriptor(descriptor.to_string());
+    let start = descriptor.find('[').ok_or_else(invalid)?;
+    let end = descriptor.find(']').ok_or_else(invalid)?;
+    let (fingerprint, path) = descriptor[start + 1..end]
+        .split_once('/')
+        .ok_or_else(invalid)?;
+    let key = &descriptor[end + 1..];
+    let key_end = key.find(['/', ')']).ok_or_else(invalid)?;
+    Ok((fingerprint, path, &key[..key_end]))
+}
+
+fn strip_checksum(descriptor: &str) -> &str {
+    descriptor
+        .split_once('#')
+        .map_or(descriptor, |(descriptor, _)| descriptor)
+}
+
+/// Appends the BIP380 checksum
+fn with_checksum(descriptor: &str) -> Result<String, ExportError> {
+    let checksum =
+        desc_checksum(descriptor).map_err(|e| ExportError::InvalidDescriptor(e.to_string()))?;
+    Ok(format!("{}#{}", descriptor, checksum))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XPUB: &str = "xpub6CFtfy4QXsEUW5CtgE7mZe1Lvs15Yw7ctjdyaDRy89JdhtyM1wFf8uY2BdyJ3JmAFfrHdw77hEit1ebVXxB2dytGAvq9mmQJ2c83G1q8P7A";
+
+    fn descriptors() -> HWIDescriptor<String> {
+        let descriptor = |script: &str, branch: u32| {
+            format!("{}[d34db33f/84h/0h/0h]{}/{}/*)", script, XPUB, branch)
+        };
+        HWIDescriptor {
+            receive: vec![descriptor("pkh(", 0), descriptor("wpkh(", 0)],
+            internal: vec![descriptor("pkh(", 1), descriptor("wpkh(", 1)],
+        }
+    }
+
+    #[test]
+    fn computes_bip380_checksums() {
+        assert_eq!(
+            with_checksum("raw(deadbeef)").unwrap(),
+            "raw(deadbeef)#89f8spxm"
+        );
+        assert!(with_checksum("raw(dead\u{e9}beef)").is_err());
+    }
+
+    #[test]
+    fn encodes_slip132_keys() {
+        // Account keys of the BIP49 and BIP84 test vectors, "abandon abandon ... about"
+        let bip49 = "xpub6C6nQwHaWbSrzs5tZ1q7m5R9cPK9eYpNMFesiXsYrgc1P8bvLLAet9JfHjYXKjToD8cBRswJXXbbFpXgwsswVPAZzKMa1jUp2kVkGVUaJa7";
+        let bip84 = "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V";
+        let bip84_testnet = "tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M";
+        let version = |address_type, network| slip132_version(&address_type, network).unwrap();
+
+        assert_eq!(
+            with_version(bip49, version(HWIAddressType::Sh_Wit, Network::Bitcoin)).unwrap(),
+            "ypub6Ww3ibxVfGzLrAH1PNcjyAWenMTbbAosGNB6VvmSEgytSER9azLDWCxoJwW7Ke7icmizBMXrzBx9979FfaHxHcrArf3zbeJJJUZPf663zsP"
+        );
+        assert_eq!(
+            with_version(bip84, version(HWIAddressType::Wit, Network::Bitcoin)).unwrap(),
+            "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs"
+        );
+        assert_eq!(
+            with_version(bip84_testnet, version(HWIAddressType::Wit, Network::Testnet)).unwrap(),
+            "vpub5Y6cjg78GGuNLsaPhmYsiw4gYX3HoQiRBiSwDaBXKUafCt9bNwWQiitDk5VZ5BVxYnQdwoTyXSs2JHRPAgjAvtbBrf8ZhDYe2jWAqvZVnsc"
+        );
+        assert_eq!(
+            with_version(bip84, version(HWIAddressType::Legacy, Network::Bitcoin)).unwrap(),
+            bip84
+        );
+
+        // A valid base58check payload which is too short to be a key
+        let short = base58::encode_check(&[0x04, 0x88]);
+        assert!(matches!(
+            with_version(&short, [0; 4]),
+            Err(ExportError::InvalidDescriptor(_))
+        ));
+    }
+
+    #[test]
+    fn exports_account_descriptors() {
+        let account = AccountDescriptors::from_hwi(descriptors(), HWIAddressType::Wit).unwrap();
+        assert!(account.receive.starts_with("wpkh([d34db33f/84h/0h/0h]"));
+        assert!(account.receive.ends_with("/0/*)#8zsax6uc"));
+        assert!(account.change.contains("/1/*)#"));
+
+        let sparrow = account
+            .export(ExportFormat::Sparrow, Network::Bitcoin)
+            .unwrap();
+        assert!(sparrow.ends_with("/<0;1>/*)#vtvad4c5\n"));
+
+        let electrum = account
+            .export(ExportFormat::Electrum, Network::Bitcoin)
+            .unwrap();
+        assert!(electrum.contains("\"xpub\": \"zpub"));
+        assert!(electrum.contains("\"derivation\": \"m/84'/0'/0'\""));
+
+        assert!(matches!(
+            AccountDescriptors::from_hwi(descriptors(), HWIAddressType::Tap),
+            Err(ExportError::MissingDescriptor(HWIAddressType::Tap))
+        ));
+    }
+
+    #[test]
+    fn reads_keypool_descriptors() {
+        let element = |desc: &String, internal| HWIKeyPoolElement {
+            desc: format!("{}#00000000", desc),
+            range: vec![0, CORE_RANGE_END],
+            timestamp: "now".to_string(),
+            internal,
+            keypool: true,
+            watchonly: true,
+        };
+        let descriptors = descriptors();
+        let keypool = vec![
+            element(&descriptors.internal[1], true),
+            element(&descriptors.receive[1], false),
+        ];
+
+        // Checksums are recomputed rather than trusted
+        assert_eq!(
+            AccountDescriptors::from_keypool(keypool, HWIAddressType::Wit).unwrap(),
+            AccountDescriptors::from_hwi(descriptors, HWIAddressType::Wit).unwrap()
+        );
+    }
+}
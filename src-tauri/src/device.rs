@@ -1,7 +1,11 @@
+use crate::capabilities::{self, Feature};
+use crate::error::AppError;
 use crate::hwi::error::Error as HWIError;
+use crate::hwi::types::HWIAddressType;
 use crate::HWIClientState;
-use bitcoin::bip32::DerivationPath;
-use bitcoin::Network;
+use bitcoin::address::NetworkUnchecked;
+use bitcoin::bip32::{ChildNumber, DerivationPath};
+use bitcoin::{Address, Network};
 use serde_json::json;
 
 pub enum ScriptType {
@@ -10,7 +14,7 @@ pub enum ScriptType {
     P2TR,
 }
 
-pub fn get_xpubs(
+pub async fn get_xpubs(
     hwi_state: &HWIClientState,
     account: usize,
 ) -> Result<serde_json::Value, HWIError> {
@@ -18,9 +22,9 @@ pub fn get_xpubs(
     let ms_path = get_derivation_path(ScriptType::P2WSH, hwi_state.network, account);
     let tr_path = get_derivation_path(ScriptType::P2TR, hwi_state.network, account);
 
-    let single_sig_xpub = hwi_state.hwi.get_xpub(&ss_path, false)?;
-    let multi_sig_xpub = hwi_state.hwi.get_xpub(&ms_path, false)?;
-    let taproot_xpub = hwi_state.hwi.get_xpub(&tr_path, false)?;
+    let single_sig_xpub = hwi_state.hwi.get_xpub(&ss_path, false).await?;
+    let multi_sig_xpub = hwi_state.hwi.get_xpub(&ms_path, false).await?;
+    let taproot_xpub = hwi_state.hwi.get_xpub(&tr_path, false).await?;
 
     if hwi_state.fingerprint.is_none() {
         return Err(HWIError::Hwi(
@@ -40,6 +44,47 @@ pub fn get_xpubs(
     }))
 }
 
+/// Single-sig script type of a BIP44 style path, given by its purpose
+pub fn address_type_for_path(path: &DerivationPath) -> Option<HWIAddressType> {
+    match path.into_iter().next() {
+        Some(ChildNumber::Hardened { index: 44 }) => Some(HWIAddressType::Legacy),
+        Some(ChildNumber::Hardened { index: 49 }) => Some(HWIAddressType::Sh_Wit),
+        Some(ChildNumber::Hardened { index: 84 }) => Some(HWIAddressType::Wit),
+        Some(ChildNumber::Hardened { index: 86 }) => Some(HWIAddressType::Tap),
+        _ => None,
+    }
+}
+
+/// Shows the single-sig address at `path` on the device and checks it is the expected one
+pub async fn verify_address_at_path(
+    hwi_state: &HWIClientState,
+    path: &DerivationPath,
+    address_type: Option<HWIAddressType>,
+    expected_address: &Address<NetworkUnchecked>,
+) -> Result<Address, AppError> {
+    let address_type = address_type
+        .or_else(|| address_type_for_path(path))
+        .ok_or_else(|| {
+            AppError::InvalidRequest(format!("Address type of path m/{} is unknown", path))
+        })?;
+    capabilities::require(hwi_state, Feature::DisplayAddress)?;
+    if address_type == HWIAddressType::Tap {
+        capabilities::require(hwi_state, Feature::Taproot)?;
+    }
+
+    let address = hwi_state
+        .hwi
+        .display_address_with_path(path, address_type)
+        .await?
+        .address;
+    if &address != expected_address {
+        return Err(AppError::AddressMismatch);
+    }
+    address
+        .require_network(hwi_state.network)
+        .map_err(|e| AppError::InvalidRequest(e.to_string()))
+}
+
 fn get_derivation_path(
     script_type: ScriptType,
     network: Network,
@@ -62,3 +107,53 @@ fn get_derivation_path(
             .unwrap(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hwi_fixtures::fixture_state;
+    use std::str::FromStr;
+
+    #[test]
+    fn returns_xpubs_for_every_script_type() {
+        let hwi_state = fixture_state();
+
+        let xpubs = tauri::async_runtime::block_on(get_xpubs(&hwi_state, 0)).unwrap();
+        assert_eq!(xpubs["singleSigPath"], "m/84'/1'/0'");
+        assert_eq!(xpubs["multiSigPath"], "m/48'/1'/0'/2'");
+        assert_eq!(xpubs["taprootPath"], "m/86'/1'/0'");
+        assert_eq!(xpubs["mfp"], "E2867BB6");
+        assert!(xpubs["multiSigXpub"]
+            .as_str()
+            .is_some_and(|xpub| xpub.starts_with("tpubDFmMwTXpkvHS")));
+    }
+
+    #[test]
+    fn verifies_single_sig_address_by_path() {
+        let hwi_state = fixture_state();
+        let path = DerivationPath::from_str("m/84'/1'/0'/0/0").unwrap();
+        let expected = |address: &str| Address::from_str(address).unwrap();
+
+        let address = tauri::async_runtime::block_on(verify_address_at_path(
+            &hwi_state,
+            &path,
+            None,
+            &expected("tb1q72xweewm4uvlkgzevmewy0dk3mmpymgr3n58qx"),
+        ))
+        .unwrap();
+        assert_eq!(
+            address.to_string(),
+            "tb1q72xweewm4uvlkgzevmewy0dk3mmpymgr3n58qx"
+        );
+
+        assert!(matches!(
+            tauri::async_runtime::block_on(verify_address_at_path(
+                &hwi_state,
+                &path,
+                Some(HWIAddressType::Wit),
+                &expected("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx"),
+            )),
+            Err(AppError::AddressMismatch)
+        ));
+    }
+}
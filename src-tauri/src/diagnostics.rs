@@ -0,0 +1,362 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::emulators::{self, EmulatorSetting};
+
+/// Number of sidecar runs kept in memory for diagnostics bundles
+const MAX_TRANSCRIPTS: usize = 50;
+
+/// Captured stdout and stderr are cut to this many bytes
+const MAX_OUTPUT_LEN: usize = 16 * 1024;
+
+const REDACTED: &str = "<redacted>";
+
+/// HWI subcommands, the only values read from stdin which are not secrets
+const HWI_COMMANDS: [&str; 17] = [
+    "enumerate",
+    "getmasterxpub",
+    "signtx",
+    "getxpub",
+    "signmessage",
+    "getkeypool",
+    "getdescriptors",
+    "displayaddress",
+    "setup",
+    "wipe",
+    "restore",
+    "backup",
+    "promptpin",
+    "togglepassphrase",
+    "sendpin",
+    "installudevrules",
+    "register",
+];
+
+/// Global options which are followed by a value, used to find the HWI subcommand
+const VALUE_OPTIONS: [&str; 10] = [
+    "--fingerprint",
+    "-f",
+    "--device-type",
+    "-t",
+    "--device-path",
+    "-d",
+    "--chain",
+    "--password",
+    "-p",
+    "--emulators-path",
+];
+
+/// Subcommands whose output identifies the wallet: signed PSBTs, xpubs and descriptors
+const PRIVATE_OUTPUT_COMMANDS: [&str; 5] = [
+    "signtx",
+    "getmasterxpub",
+    "getxpub",
+    "getdescriptors",
+    "getkeypool",
+];
+
+/// Options whose value identifies the wallet
+const PRIVATE_OPTIONS: [&str; 1] = ["--desc"];
+
+static TRANSCRIPTS: Mutex<VecDeque<Transcript>> = Mutex::new(VecDeque::new());
+
+tokio::task_local! {
+    /// Set while running background commands, see [`unrecorded`]
+    static UNRECORDED: ();
+}
+
+/// A single run of the HWI sidecar, secrets are redacted once it is recorded
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Transcript {
+    pub started_at: u64,
+    pub duration_ms: u64,
+    pub args: Vec<String>,
+    pub stdin_args: Vec<String>,
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticsBundle {
+    pub app_version: &'static str,
+    pub os: &'static str,
+    pub arch: &'static str,
+    pub hwi_version: Option<Result<String, String>>,
+    pub emulators: Vec<EmulatorSetting>,
+    pub transcripts: Vec<Transcript>,
+}
+
+/// Values passed through stdin which must never show up in logs or transcripts
+///
+/// Everything except options and subcommand names is treated as a secret: stdin only carries
+/// PSBTs, PINs, passwords and passphrases.
+pub struct Secrets(Vec<String>);
+
+impl Secrets {
+    pub fn from_stdin_args(stdin_args: &[String]) -> Self {
+        Secrets(
+            stdin_args
+                .iter()
+                .filter(|arg| !arg.starts_with('-') && !HWI_COMMANDS.contains(&arg.as_str()))
+                .cloned()
+                .collect(),
+        )
+    }
+
+    pub fn redact(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for secret in self.0.iter().filter(|secret| !secret.is_empty()) {
+            text = text.replace(secret.as_str(), REDACTED);
+        }
+        text
+    }
+
+    fn redact_args(&self, args: &[String]) -> Vec<String> {
+        args.iter()
+            .map(|arg| match self.0.contains(arg) {
+                true => REDACTED.to_string(),
+                false => arg.clone(),
+            })
+            .collect()
+    }
+}
+
+/// HWI appends the arguments read from stdin to the command line ones
+pub fn subcommand<'a>(args: &'a [String], stdin_args: &'a [String]) -> &'a str {
+    let mut args = args.iter().chain(stdin_args);
+    while let Some(arg) = args.next() {
+        if VALUE_OPTIONS.contains(&arg.as_str()) {
+            args.next();
+        } else if arg == "--version" {
+            return "version";
+        } else if !arg.starts_with('-') {
+            return arg;
+        }
+    }
+    "unknown"
+}
+
+/// Runs `future` without recording its HWI commands, so that background polling doesn't push
+/// the transcripts of user actions out of the bundle
+pub async fn unrecorded<F: Future>(future: F) -> F::Output {
+    UNRECORDED.scope((), future).await
+}
+
+fn transcripts() -> MutexGuard<'static, VecDeque<Transcript>> {
+    TRANSCRIPTS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Redacts a transcript and keeps it, dropping the oldest one when full
+///
+/// Besides the stdin secrets this drops the output of commands which return wallet data, and
+/// every value of that output from stderr, where `--debug` may repeat it.
+pub fn record(secrets: &Secrets, transcript: Transcript) {
+    if UNRECORDED.try_with(|_| ()).is_ok() {
+        return;
+    }
+    let private_output = transcript.exit_code == Some(0)
+        && PRIVATE_OUTPUT_COMMANDS.contains(&subcommand(&transcript.args, &transcript.stdin_args));
+    let (stdout, stderr) = if private_output {
+        let output = Secrets(output_values(&transcript.stdout));
+        (
+            format!("<redacted {} bytes>", transcript.stdout.len()),
+            output.redact(&secrets.redact(&transcript.stderr)),
+        )
+    } else {
+        (
+            secrets.redact(&transcript.stdout),
+            secrets.redact(&transcript.stderr),
+        )
+    };
+    let transcript = Transcript {
+        args: redact_private_options(secrets.redact_args(&transcript.args)),
+        stdin_args: redact_private_options(secrets.redact_args(&transcript.stdin_args)),
+        stdout: truncate(stdout),
+        stderr: truncate(stderr),
+        ..transcript
+    };
+
+    let mut transcripts = transcripts();
+    if transcripts.len() == MAX_TRANSCRIPTS {
+        transcripts.pop_front();
+    }
+    transcripts.push_back(transcript);
+}
+
+fn redact_private_options(mut args: Vec<String>) -> Vec<String> {
+    for i in 1..args.len() {
+        if PRIVATE_OPTIONS.contains(&args[i - 1].as_str()) {
+            args[i] = REDACTED.to_string();
+        }
+    }
+    args
+}
+
+/// Every string in the JSON output of a command, e.g. the xpubs of `getdescriptors`
+fn output_values(stdout: &str) -> Vec<String> {
+    fn collect(value: serde_json::Value, values: &mut Vec<String>) {
+        match value {
+            serde_json::Value::String(value) => values.push(value),
+            serde_json::Value::Array(items) => {
+                items.into_iter().for_each(|item| collect(item, values))
+            }
+            serde_json::Value::Object(fields) => fields
+                .into_values()
+                .for_each(|field| collect(field, values)),
+            _ => {}
+        }
+    }
+
+    let mut values = Vec::new();
+    if let Ok(output) = serde_json::from_str(stdout) {
+        collect(output, &mut values);
+    }
+    values
+}
+
+/// Collects the last `count` transcripts, oldest first, along with the app environment
+pub fn bundle(count: usize, hwi_version: Option<Result<String, String>>) -> DiagnosticsBundle {
+    let transcripts = transcripts();
+    DiagnosticsBundle {
+        app_version: env!("CARGO_PKG_VERSION"),
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        hwi_version,
+        emulators: emulators::settings(),
+        transcripts: transcripts
+            .iter()
+            .skip(transcripts.len().saturating_sub(count))
+            .cloned()
+            .collect(),
+    }
+}
+
+/// Writes a bundle to `destination`, returning the number of transcripts it contains
+pub fn export(bundle: &DiagnosticsBundle, destination: &Path) -> std::io::Result<usize> {
+    std::fs::write(destination, serde_json::to_string_pretty(bundle)?)?;
+    Ok(bundle.transcripts.len())
+}
+
+fn truncate(mut text: String) -> String {
+    if text.len() > MAX_OUTPUT_LEN {
+        let mut end = MAX_OUTPUT_LEN;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        text.push_str("\n<truncated>");
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_stdin_values() {
+        let stdin_args: Vec<String> = ["--password", "hunter2", "sendpin", "1234"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let secrets = Secrets::from_stdin_args(&stdin_args);
+
+        assert_eq!(
+            secrets.redact_args(&stdin_args),
+            vec!["--password", REDACTED, "sendpin", REDACTED]
+        );
+        assert_eq!(
+            secrets.redact("DEBUG: sending pin 1234 with hunter2"),
+            "DEBUG: sending pin <redacted> with <redacted>"
+        );
+    }
+
+    #[test]
+    fn redacts_wallet_data() {
+        let args: Vec<String> = [
+            "--chain",
+            "test",
+            "displayaddress",
+            "--desc",
+            "wpkh([e2867bb6]tpub)",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        record(
+            &Secrets::from_stdin_args(&[]),
+            Transcript {
+                started_at: 0,
+                duration_ms: 0,
+                args,
+                stdin_args: vec![],
+                exit_code: Some(0),
+                stdout: "{\"address\": \"tb1q\"}\n".to_string(),
+                stderr: String::new(),
+            },
+        );
+        let stdin_args: Vec<String> = ["getxpub", "m/84h/1h/0h"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        record(
+            &Secrets::from_stdin_args(&stdin_args),
+            Transcript {
+                started_at: 0,
+                duration_ms: 0,
+                args: vec!["--stdin".to_string()],
+                stdin_args,
+                exit_code: Some(0),
+                stdout: "{\"xpub\": \"tpubDCwYjpDhUdPGP\"}\n".to_string(),
+                stderr: "DEBUG: received tpubDCwYjpDhUdPGP\n".to_string(),
+            },
+        );
+
+        let bundle = bundle(2, None);
+        let [displayaddress, getxpub] = &bundle.transcripts[..] else {
+            panic!("expected two transcripts");
+        };
+        assert_eq!(
+            displayaddress.args,
+            vec!["--chain", "test", "displayaddress", "--desc", REDACTED]
+        );
+        assert_eq!(displayaddress.stdout, "{\"address\": \"tb1q\"}\n");
+        assert_eq!(getxpub.stdin_args, vec!["getxpub", REDACTED]);
+        assert_eq!(getxpub.stdout, "<redacted 30 bytes>");
+        assert_eq!(getxpub.stderr, "DEBUG: received <redacted>\n");
+    }
+
+    #[test]
+    fn skips_unrecorded_commands() {
+        let args = vec![
+            "--chain".to_string(),
+            "regtest".to_string(),
+            "enumerate".to_string(),
+        ];
+        tauri::async_runtime::block_on(unrecorded(async {
+            record(
+                &Secrets::from_stdin_args(&[]),
+                Transcript {
+                    started_at: 0,
+                    duration_ms: 0,
+                    args: args.clone(),
+                    stdin_args: vec![],
+                    exit_code: Some(0),
+                    stdout: "[]\n".to_string(),
+                    stderr: String::new(),
+                },
+            )
+        }));
+
+        assert!(bundle(usize::MAX, None)
+            .transcripts
+            .iter()
+            .all(|transcript| transcript.args != args));
+    }
+}
@@ -0,0 +1,162 @@
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::str::FromStr;
+use std::sync::{PoisonError, RwLock};
+
+/// Comma separated list of emulators to probe, or "all" / "none", e.g. for CI runs
+const EMULATORS_ENV: &str = "KEEPER_HWI_EMULATORS";
+
+/// Device simulators which can be discovered on the local machine
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Emulator {
+    Trezor,
+    KeepKey,
+    Ledger,
+    Coldcard,
+    BitBox02,
+    Jade,
+    Specter,
+}
+
+impl Emulator {
+    pub const ALL: [Emulator; 7] = [
+        Emulator::Trezor,
+        Emulator::KeepKey,
+        Emulator::Ledger,
+        Emulator::Coldcard,
+        Emulator::BitBox02,
+        Emulator::Jade,
+        Emulator::Specter,
+    ];
+
+    /// Address the emulator listens on, as reported in the device path
+    pub fn address(&self) -> &'static str {
+        match self {
+            Emulator::Trezor => "udp:127.0.0.1:21324",
+            Emulator::KeepKey => "udp:127.0.0.1:11044",
+            Emulator::Ledger => "tcp:127.0.0.1:9999",
+            Emulator::Coldcard => "/tmp/ckcc-simulator.sock",
+            Emulator::BitBox02 => "127.0.0.1:15423",
+            Emulator::Jade => "tcp:127.0.0.1:30121",
+            Emulator::Specter => "127.0.0.1:8789",
+        }
+    }
+
+    /// Whether the emulator is discovered by the HWI binary rather than by async-hwi
+    fn is_hwi(&self) -> bool {
+        !matches!(self, Emulator::Specter)
+    }
+}
+
+impl FromStr for Emulator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_value(Value::String(s.trim().to_lowercase()))
+            .map_err(|_| format!("Unknown emulator: {}", s))
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmulatorSetting {
+    pub emulator: Emulator,
+    pub address: &'static str,
+    pub enabled: bool,
+}
+
+/// `None` until the setting is first read or changed
+static ENABLED: RwLock<Option<Vec<Emulator>>> = RwLock::new(None);
+
+/// Release builds never probe for emulators unless asked to, development builds probe for all
+fn default_emulators() -> Vec<Emulator> {
+    match std::env::var(EMULATORS_ENV) {
+        Ok(value) => parse_emulators(&value),
+        Err(_) if cfg!(feature = "release") => Vec::new(),
+        Err(_) => Emulator::ALL.to_vec(),
+    }
+}
+
+fn parse_emulators(value: &str) -> Vec<Emulator> {
+    match value.trim() {
+        "all" => Emulator::ALL.to_vec(),
+        "none" | "" => Vec::new(),
+        value => value
+            .split(',')
+            .filter_map(|name| {
+                name.parse()
+                    .map_err(|e| warn!("Ignoring {} entry: {}", EMULATORS_ENV, e))
+                    .ok()
+            })
+            .collect(),
+    }
+}
+
+pub fn enabled() -> Vec<Emulator> {
+    let mut enabled = ENABLED.write().unwrap_or_else(PoisonError::into_inner);
+    enabled.get_or_insert_with(default_emulators).clone()
+}
+
+pub fn set_enabled(emulators: Vec<Emulator>) {
+    *ENABLED.write().unwrap_or_else(PoisonError::into_inner) = Some(emulators);
+}
+
+pub fn is_enabled(emulator: Emulator) -> bool {
+    enabled().contains(&emulator)
+}
+
+pub fn settings() -> Vec<EmulatorSetting> {
+    let enabled = enabled();
+    Emulator::ALL
+        .iter()
+        .map(|emulator| EmulatorSetting {
+            emulator: *emulator,
+            address: emulator.address(),
+            enabled: enabled.contains(emulator),
+        })
+        .collect()
+}
+
+/// Whether HWI has to be started with `--emulators`
+pub fn hwi_probe_needed() -> bool {
+    enabled().iter().any(Emulator::is_hwi)
+}
+
+/// Removes the devices of disabled emulators from the output of `hwi enumerate`
+///
+/// HWI probes for every emulator at once, so the selection is applied to its results.
+pub fn filter_enumerate_output(output: String) -> String {
+    let Ok(Value::Array(devices)) = serde_json::from_str::<Value>(&output) else {
+        return output;
+    };
+
+    let enabled = enabled();
+    let devices: Vec<Value> = devices
+        .into_iter()
+        .filter(|device| {
+            Emulator::ALL
+                .iter()
+                .filter(|emulator| emulator.is_hwi() && !enabled.contains(emulator))
+                .all(|emulator| device["path"].as_str() != Some(emulator.address()))
+        })
+        .collect();
+
+    serde_json::to_string(&devices).unwrap_or(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_emulator_lists() {
+        assert_eq!(parse_emulators("all"), Emulator::ALL.to_vec());
+        assert!(parse_emulators("none").is_empty());
+        assert_eq!(
+            parse_emulators("trezor, BitBox02,unknown"),
+            vec![Emulator::Trezor, Emulator::BitBox02]
+        );
+    }
+}
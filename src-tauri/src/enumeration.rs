@@ -0,0 +1,388 @@
+use async_hwi::{parse_version, DeviceKind};
+use bitcoin::bip32::Fingerprint;
+use bitcoin::Network;
+use log::warn;
+use serde::Serialize;
+use std::str::FromStr;
+
+use crate::capabilities::{self, Feature, Firmware, Model};
+use crate::error::AppError;
+use crate::hwi::types::{HWIChain, HWIDevice, HWIDeviceType};
+use crate::miniscript_hwi::{coldcard_versions, list_devices, Wallet};
+use crate::HWIAppClient;
+
+/// Library a device can be driven through
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Backend {
+    /// The HWI sidecar
+    Hwi,
+    /// async-hwi, used for miniscript wallets and the BitBox02
+    AsyncHwi,
+}
+
+/// A device found by any backend, listed once however many backends see it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct EnumeratedDevice {
+    #[serde(flatten)]
+    pub device: HWIDevice,
+    pub firmware_version: Option<String>,
+    pub backends: Vec<Backend>,
+    /// Features supported by the device type and firmware, see [`capabilities::support`]
+    pub capabilities: Vec<Feature>,
+    #[serde(skip)]
+    firmware: Firmware,
+}
+
+impl EnumeratedDevice {
+    fn new(device: HWIDevice, backend: Backend, firmware: Firmware) -> Self {
+        EnumeratedDevice {
+            firmware_version: firmware.version().map(|v| v.to_string()),
+            capabilities: capabilities::supported(&device.device_type, &firmware),
+            backends: vec![backend],
+            device,
+            firmware,
+        }
+    }
+
+    fn add_backend(&mut self, backend: Backend, firmware: Firmware) {
+        if !self.backends.contains(&backend) {
+            self.backends.push(backend);
+            self.backends.sort();
+        }
+        if self.firmware.version().is_none() && firmware.version().is_some() {
+            self.firmware_version = firmware.version().map(|v| v.to_string());
+            self.capabilities = capabilities::supported(&self.device.device_type, &firmware);
+            self.firmware = firmware;
+        }
+    }
+}
+
+/// Device as listed by async-hwi, which only knows its kind and fingerprint
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AsyncDevice {
+    pub kind: DeviceKind,
+    pub fingerprint: Fingerprint,
+    pub firmware: Firmware,
+}
+
+impl From<AsyncDevice> for EnumeratedDevice {
+    fn from(found: AsyncDevice) -> Self {
+        let device = HWIDevice {
+            device_type: HWIDeviceType::from(found.kind.to_string()),
+            model: found.kind.to_string(),
+            path: String::new(),
+            needs_pin_sent: false,
+            needs_passphrase_sent: false,
+            fingerprint: Some(found.fingerprint),
+        };
+        EnumeratedDevice::new(device, Backend::AsyncHwi, found.firmware)
+    }
+}
+
+/// Lists the devices of both backends, de-duplicated by fingerprint
+///
+/// Entries of HWI come first, as HWI knows the model and path of a device. Devices which
+/// failed to enumerate are kept as errors, e.g. a locked Trezor.
+pub fn merge(
+    hwi: Vec<Result<HWIDevice, String>>,
+    async_hwi: Vec<Result<AsyncDevice, String>>,
+) -> Vec<Result<EnumeratedDevice, String>> {
+    let mut devices: Vec<Result<EnumeratedDevice, String>> = hwi
+        .into_iter()
+        .map(|device| {
+            device.map(|device| {
+                let firmware = if reports_firmware(&device.device_type) {
+                    Firmware::Unknown
+                } else {
+                    Firmware::NotReported
+                };
+                EnumeratedDevice::new(device, Backend::Hwi, firmware)
+            })
+        })
+        .collect();
+
+    for found in async_hwi {
+        let found = match found {
+            Ok(found) => found,
+            Err(e) => {
+                devices.push(Err(e));
+                continue;
+            }
+        };
+        let listed = devices
+            .iter_mut()
+            .filter_map(|device| device.as_mut().ok())
+            .find(|device| device.device.fingerprint == Some(found.fingerprint));
+        match listed {
+            Some(device) => device.add_backend(Backend::AsyncHwi, found.firmware),
+            None => devices.push(Ok(found.into())),
+        }
+    }
+    devices
+}
+
+async fn enumerate_async_hwi(
+    network: Network,
+    app_handle: Option<tauri::AppHandle>,
+) -> Result<Vec<Result<AsyncDevice, String>>, String> {
+    let devices = list_devices(
+        network,
+        Some(Wallet {
+            name: Some(&"none".to_string()), // Needed in case other devices which aren't the BB02 and require wallet name are connected
+            policy: Some(&"none".to_string()), // Needed in case other devices which aren't the BB02 and require wallet policy are connected
+            hmac: None,
+        }),
+        app_handle,
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let mut found = Vec::new();
+    for device in devices {
+        let kind = device.device_kind();
+        found.push(match device.get_master_fingerprint().await {
+            Ok(fingerprint) => Ok(AsyncDevice {
+                kind,
+                fingerprint,
+                firmware: device
+                    .get_version()
+                    .await
+                    .map_or(Firmware::Unknown, |version| Firmware::Known {
+                        version,
+                        model: None,
+                    }),
+            }),
+            Err(e) => Err(format!("{}: {}", kind, e)),
+        });
+    }
+
+    // The handles of the devices are closed by now, so the Coldcards can be opened again
+    if found
+        .iter()
+        .flatten()
+        .any(|device| device.kind == DeviceKind::Coldcard)
+    {
+        // The HID round trips block, so they are kept off the async workers
+        let versions = tokio::task::spawn_blocking(coldcard_versions)
+            .await
+            .unwrap_or_else(|e| {
+                warn!("Failed to read the Coldcard versions: {}", e);
+                Vec::new()
+            });
+        for device in found.iter_mut().flatten() {
+            if device.kind == DeviceKind::Coldcard {
+                device.firmware = versions
+                    .iter()
+                    .find(|(fingerprint, _)| *fingerprint == device.fingerprint)
+                    .map_or(Firmware::Unknown, |(_, version)| coldcard_firmware(version));
+            }
+        }
+    }
+    Ok(found)
+}
+
+/// Whether async-hwi can read the firmware of the device type, HWI doesn't report versions
+fn reports_firmware(device_type: &HWIDeviceType) -> bool {
+    matches!(
+        device_type,
+        HWIDeviceType::Ledger
+            | HWIDeviceType::BitBox02
+            | HWIDeviceType::Coldcard
+            | HWIDeviceType::Jade
+    )
+}
+
+/// Firmware and model of a Coldcard from its reply to `version`
+///
+/// The reply lists the build date, firmware version, bootloader version and hardware label,
+/// one per line. EDGE firmware has an "X" suffix and the Q a "Q" suffix, which async-hwi strips
+/// before the model can be told from it.
+fn coldcard_firmware(reply: &str) -> Firmware {
+    let mut lines = reply.lines().map(str::trim);
+    let version = lines
+        .by_ref()
+        .find_map(|line| parse_version(line.trim_end_matches(['Q', 'X'])).ok());
+    let model = lines.find_map(|line| match line {
+        "q1" => Some(Model::ColdcardQ),
+        "mk4" => Some(Model::ColdcardMk4),
+        line if line.starts_with("mk") => Some(Model::ColdcardMk3),
+        _ => None,
+    });
+    match version {
+        Some(version) => Firmware::Known { version, model },
+        None => Firmware::Unknown,
+    }
+}
+
+/// Firmware version of a device, or app version for Ledger
+///
+/// Only async-hwi reads versions, HWI doesn't report them. Devices which only HWI can talk to,
+/// like Trezor, are not held to minimum versions. A failed lookup leaves the version unknown,
+/// which refuses every feature with a minimum version.
+pub async fn firmware_version(
+    device_type: &HWIDeviceType,
+    fingerprint: Option<&str>,
+    network: Network,
+    app_handle: Option<tauri::AppHandle>,
+) -> Firmware {
+    if !reports_firmware(device_type) {
+        return Firmware::NotReported;
+    }
+    let Some(fingerprint) = fingerprint.and_then(|f| Fingerprint::from_str(f).ok()) else {
+        return Firmware::Unknown;
+    };
+
+    enumerate_async_hwi(network, app_handle)
+        .await
+        .map_err(|e| warn!("async-hwi failed to enumerate: {}", e))
+        .ok()
+        .and_then(|devices| {
+            devices
+                .into_iter()
+                .flatten()
+                .find(|device| device.fingerprint == fingerprint)
+        })
+        .map_or(Firmware::Unknown, |device| device.firmware)
+}
+
+/// Queries HWI and async-hwi concurrently, HWI errors are only returned if async-hwi found nothing
+pub async fn enumerate(
+    network: Option<Network>,
+    app_handle: Option<tauri::AppHandle>,
+) -> Result<Vec<Result<EnumeratedDevice, String>>, AppError> {
+    let hwi = async {
+        HWIAppClient::enumerate(network.map(HWIChain::from))
+            .await
+            .map(|devices| {
+                devices
+                    .into_iter()
+                    .map(|device| device.map_err(|e| e.to_string()))
+                    .collect()
+            })
+    };
+    let async_hwi = enumerate_async_hwi(network.unwrap_or(Network::Bitcoin), app_handle);
+    let (hwi, async_hwi) = tokio::join!(hwi, async_hwi);
+
+    let async_hwi = async_hwi.unwrap_or_else(|e| {
+        warn!("async-hwi failed to enumerate: {}", e);
+        Vec::new()
+    });
+    let hwi = match hwi {
+        Ok(devices) => devices,
+        Err(e) if async_hwi.is_empty() => return Err(e.into()),
+        Err(e) => {
+            warn!("HWI failed to enumerate: {}", e);
+            Vec::new()
+        }
+    };
+    Ok(merge(hwi, async_hwi))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hwi_fixtures::hwi_device;
+    use async_hwi::Version;
+    use tauri::async_runtime::block_on;
+
+    const FINGERPRINT: &str = "e2867bb6";
+
+    #[test]
+    fn merges_devices_seen_by_both_backends() {
+        let version = Version {
+            major: 2,
+            minor: 1,
+            patch: 0,
+            prerelease: None,
+        };
+        let devices = merge(
+            vec![
+                Ok(hwi_device(
+                    HWIDeviceType::Ledger,
+                    "ledger_nano_s_plus",
+                    "DevSrvsID:4294969604",
+                    Some(FINGERPRINT),
+                )),
+                Err("Trezor is locked".to_string()),
+            ],
+            vec![
+                Ok(AsyncDevice {
+                    kind: DeviceKind::Ledger,
+                    fingerprint: Fingerprint::from_str(FINGERPRINT).unwrap(),
+                    firmware: Firmware::Known {
+                        version: version.clone(),
+                        model: None,
+                    },
+                }),
+                Ok(AsyncDevice {
+                    kind: DeviceKind::BitBox02,
+                    fingerprint: Fingerprint::from_str("0f056943").unwrap(),
+                    firmware: Firmware::Unknown,
+                }),
+            ],
+        );
+
+        assert_eq!(devices.len(), 3);
+        let ledger = devices[0].as_ref().unwrap();
+        assert_eq!(ledger.device.model, "ledger_nano_s_plus");
+        assert_eq!(ledger.firmware_version.as_deref(), Some("2.1.0"));
+        assert_eq!(ledger.backends, vec![Backend::Hwi, Backend::AsyncHwi]);
+        assert!(ledger.capabilities.contains(&Feature::Miniscript));
+
+        assert!(devices[1].is_err());
+
+        let bitbox = devices[2].as_ref().unwrap();
+        assert_eq!(bitbox.device.device_type, HWIDeviceType::BitBox02);
+        assert_eq!(bitbox.backends, vec![Backend::AsyncHwi]);
+        assert!(!bitbox.capabilities.contains(&Feature::Hmac));
+    }
+
+    #[test]
+    fn reads_firmware_versions() {
+        let firmware = |device_type, fingerprint| {
+            block_on(firmware_version(
+                &device_type,
+                fingerprint,
+                Network::Testnet,
+                None,
+            ))
+        };
+        // Neither needs a device: HWI reports no versions, and a device without a fingerprint
+        // can't be matched with what async-hwi finds
+        assert_eq!(
+            firmware(HWIDeviceType::Trezor, Some(FINGERPRINT)),
+            Firmware::NotReported
+        );
+        assert_eq!(firmware(HWIDeviceType::Ledger, None), Firmware::Unknown);
+
+        let version = |major, minor, patch| Version {
+            major,
+            minor,
+            patch,
+            prerelease: None,
+        };
+        assert_eq!(
+            coldcard_firmware("2024-07-05T1348\n6.3.3QX\n3.1.5\nq1\n1\n"),
+            Firmware::Known {
+                version: version(6, 3, 3),
+                model: Some(Model::ColdcardQ),
+            }
+        );
+        assert_eq!(
+            coldcard_firmware("2023-12-21T1527\n6.2.2X\n3.1.5\nmk4\n1\n"),
+            Firmware::Known {
+                version: version(6, 2, 2),
+                model: Some(Model::ColdcardMk4),
+            }
+        );
+        assert_eq!(
+            coldcard_firmware("2021-09-02T1752\n4.1.3\n2.1.0\nmk3\n"),
+            Firmware::Known {
+                version: version(4, 1, 3),
+                model: Some(Model::ColdcardMk3),
+            }
+        );
+        assert_eq!(coldcard_firmware("unexpected"), Firmware::Unknown);
+    }
+}
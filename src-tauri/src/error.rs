@@ -0,0 +1,241 @@
+use serde::{Serialize, Serializer};
+use thiserror::Error;
+
+use crate::approval::ApprovalError;
+use crate::audit::AuditError;
+use crate::capabilities::CapabilityError;
+use crate::channel::ChannelError;
+use crate::descriptors::ExportError;
+use crate::hwi::error::{Error as HWIError, ErrorCode};
+use crate::lifecycle::LifecycleError;
+use crate::message::MessageError;
+use crate::pin::PinError;
+use crate::HWIClientState;
+
+/// Error returned by every Tauri command
+///
+/// Serializes to `{kind, code, message, device, retryable}` so that the webview and the phone
+/// can branch on the kind of failure instead of matching on the message.
+#[derive(Error, Debug)]
+pub enum AppError {
+    #[error("{}", hwi_message(.0))]
+    Hwi(#[from] HWIError),
+    #[error("{0}")]
+    Device(#[from] async_hwi::Error),
+    #[error(transparent)]
+    Channel(#[from] ChannelError),
+    #[error(transparent)]
+    Approval(#[from] ApprovalError),
+    #[error(transparent)]
+    Audit(#[from] AuditError),
+    #[error(transparent)]
+    Pin(#[from] PinError),
+    #[error(transparent)]
+    Lifecycle(#[from] LifecycleError),
+    #[error(transparent)]
+    Message(#[from] MessageError),
+    #[error(transparent)]
+    Export(#[from] ExportError),
+    #[error(transparent)]
+    Capability(#[from] CapabilityError),
+    #[error("Another operation is in progress")]
+    Busy(#[from] tokio::sync::TryLockError),
+    #[error("HWI client not initialized")]
+    NoDevice,
+    #[error("Address received from device does not match the expected address")]
+    AddressMismatch,
+    #[error("{0}")]
+    InvalidRequest(String),
+    #[error("{0}")]
+    Other(String),
+    #[error("{source}")]
+    OnDevice {
+        device: String,
+        source: Box<AppError>,
+    },
+}
+
+impl AppError {
+    /// Tags the error with the device the failed operation was talking to
+    pub fn on_device(self, hwi_state: &HWIClientState) -> Self {
+        match self {
+            AppError::OnDevice { .. } => self,
+            source => AppError::OnDevice {
+                device: hwi_state.device_type.to_string(),
+                source: Box::new(source),
+            },
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AppError::Hwi(_) => "HWI",
+            AppError::Device(_) => "DEVICE",
+            AppError::Channel(_) => "CHANNEL",
+            AppError::Approval(_) => "APPROVAL",
+            AppError::Audit(_) => "AUDIT",
+            AppError::Pin(_) => "PIN",
+            AppError::Lifecycle(_) => "LIFECYCLE",
+            AppError::Message(_) => "MESSAGE",
+            AppError::Export(_) => "EXPORT",
+            AppError::Capability(_) => "CAPABILITY",
+            AppError::Busy(_) => "BUSY",
+            AppError::NoDevice => "NO_DEVICE",
+            AppError::AddressMismatch => "ADDRESS_MISMATCH",
+            AppError::InvalidRequest(_) => "INVALID_REQUEST",
+            AppError::Other(_) => "OTHER",
+            AppError::OnDevice { source, .. } => source.kind(),
+        }
+    }
+
+    pub fn code(&self) -> Option<&'static str> {
+        match self {
+            AppError::Hwi(HWIError::UntrustedBinary(_)) => Some("UNTRUSTED_BINARY"),
+            AppError::Hwi(e) => e.code().map(|code| code.name()),
+            AppError::Device(e) => Some(device_error_code(e)),
+            AppError::Pin(e) => Some(pin_error_code(e)),
+            AppError::Lifecycle(e) => Some(lifecycle_error_code(e)),
+            AppError::Message(e) => Some(message_error_code(e)),
+            AppError::Capability(CapabilityError::Unsupported(..)) => Some("UNSUPPORTED_FEATURE"),
+            AppError::Capability(CapabilityError::FirmwareTooOld { .. }) => {
+                Some("FIRMWARE_TOO_OLD")
+            }
+            AppError::Capability(CapabilityError::VersionUnknown { .. }) => {
+                Some("FIRMWARE_UNKNOWN")
+            }
+            AppError::OnDevice { source, .. } => source.code(),
+            _ => None,
+        }
+    }
+
+    /// Whether repeating the same request may succeed, e.g. after reconnecting the device
+    pub fn retryable(&self) -> bool {
+        match self {
+            AppError::Hwi(e) => matches!(
+                e.code(),
+                Some(
+                    ErrorCode::DeviceConnError
+                        | ErrorCode::DeviceNotReady
+                        | ErrorCode::DeviceBusy
+                        | ErrorCode::ActionCanceled
+                )
+            ),
+            AppError::Device(e) => matches!(
+                e,
+                async_hwi::Error::DeviceDisconnected
+                    | async_hwi::Error::DeviceNotFound
+                    | async_hwi::Error::UserRefused
+            ),
+            AppError::Channel(e) => matches!(
+                e,
+                ChannelError::NoClient
+                    | ChannelError::ConnectionTimeout
+                    | ChannelError::SocketIoError(_)
+            ),
+            AppError::Pin(e) => matches!(e, PinError::NotPrompted | PinError::DeviceLost),
+            AppError::Busy(_) => true,
+            AppError::OnDevice { source, .. } => source.retryable(),
+            _ => false,
+        }
+    }
+
+    fn device(&self) -> Option<&str> {
+        match self {
+            AppError::OnDevice { device, .. } => Some(device),
+            _ => None,
+        }
+    }
+}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct SerializedError<'a> {
+            kind: &'static str,
+            code: Option<&'static str>,
+            message: String,
+            device: Option<&'a str>,
+            retryable: bool,
+        }
+
+        SerializedError {
+            kind: self.kind(),
+            code: self.code(),
+            message: self.to_string(),
+            device: self.device(),
+            retryable: self.retryable(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl From<String> for AppError {
+    fn from(message: String) -> Self {
+        AppError::Other(message)
+    }
+}
+
+impl From<&str> for AppError {
+    fn from(message: &str) -> Self {
+        AppError::Other(message.to_string())
+    }
+}
+
+/// HWI errors are shown as reported by the binary, without the debug formatting of the code
+fn hwi_message(e: &HWIError) -> String {
+    match e {
+        HWIError::Hwi(message, _) => message.trim().to_string(),
+        e => e.to_string(),
+    }
+}
+
+fn pin_error_code(e: &PinError) -> &'static str {
+    match e {
+        PinError::NotPrompted => "PIN_NOT_PROMPTED",
+        PinError::AlreadyUnlocked => "ALREADY_UNLOCKED",
+        PinError::LockedOut => "PIN_LOCKED_OUT",
+        PinError::DeviceLost => "DEVICE_LOST",
+    }
+}
+
+fn lifecycle_error_code(e: &LifecycleError) -> &'static str {
+    match e {
+        LifecycleError::NotConfirmed(_) => "NOT_CONFIRMED",
+        LifecycleError::OperationMismatch(_) | LifecycleError::DeviceMismatch => {
+            "CONFIRMATION_MISMATCH"
+        }
+        LifecycleError::Expired => "CONFIRMATION_EXPIRED",
+        LifecycleError::PhraseMismatch(_) => "PHRASE_MISMATCH",
+        LifecycleError::InvalidWordCount(_) => "INVALID_WORD_COUNT",
+    }
+}
+
+fn message_error_code(e: &MessageError) -> &'static str {
+    match e {
+        MessageError::UnsupportedAddress(_) | MessageError::UnsupportedPath(_) => {
+            "UNSUPPORTED_ADDRESS"
+        }
+        MessageError::InvalidSignature(_) => "INVALID_SIGNATURE",
+        MessageError::NotSigned => "NOT_SIGNED",
+        MessageError::WrongKey => "WRONG_KEY",
+    }
+}
+
+fn device_error_code(e: &async_hwi::Error) -> &'static str {
+    match e {
+        async_hwi::Error::ParsingPolicy(_) => "PARSING_POLICY",
+        async_hwi::Error::MissingPolicy => "MISSING_POLICY",
+        async_hwi::Error::UnsupportedVersion => "UNSUPPORTED_VERSION",
+        async_hwi::Error::UnsupportedInput => "UNSUPPORTED_INPUT",
+        async_hwi::Error::InvalidParameter(..) => "INVALID_PARAMETER",
+        async_hwi::Error::UnimplementedMethod => "UNIMPLEMENTED_METHOD",
+        async_hwi::Error::DeviceDisconnected => "DEVICE_DISCONNECTED",
+        async_hwi::Error::DeviceNotFound => "DEVICE_NOT_FOUND",
+        async_hwi::Error::DeviceDidNotSign => "DEVICE_DID_NOT_SIGN",
+        async_hwi::Error::Device(_) => "DEVICE_ERROR",
+        async_hwi::Error::Unexpected(_) => "UNEXPECTED",
+        async_hwi::Error::UserRefused => "USER_REFUSED",
+        async_hwi::Error::NetworkMismatch => "NETWORK_MISMATCH",
+    }
+}
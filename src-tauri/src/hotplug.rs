@@ -0,0 +1,301 @@
+use log::warn;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::diagnostics;
+use crate::hwi::types::HWIDevice;
+use crate::HWIAppClient;
+
+/// Interval of `PollingSource`, also used to wait while a command talks to a device
+pub const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Interval of `PollingSource` while a device is selected and the connect screen is closed,
+/// enough to notice the selected device being unplugged
+pub const SELECTED_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Set while the connect screen waits for a device, see [`set_polling`]
+static POLLING: AtomicBool = AtomicBool::new(false);
+
+/// Set while a device is selected, see [`set_device_selected`]
+static SELECTED: AtomicBool = AtomicBool::new(false);
+
+/// Devices show up in HWI a moment after the kernel announces them
+#[cfg(target_os = "linux")]
+const SETTLE_DELAY: Duration = Duration::from_millis(500);
+
+/// A device was plugged in or unplugged, emitted as `device-connected` / `device-disconnected`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeviceEvent {
+    Connected(HWIDevice),
+    Disconnected(HWIDevice),
+}
+
+impl DeviceEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DeviceEvent::Connected(_) => "device-connected",
+            DeviceEvent::Disconnected(_) => "device-disconnected",
+        }
+    }
+
+    pub fn device(&self) -> &HWIDevice {
+        match self {
+            DeviceEvent::Connected(device) | DeviceEvent::Disconnected(device) => device,
+        }
+    }
+}
+
+/// Tells when the set of connected devices may have changed
+pub trait DeviceEventSource {
+    /// Waits for the next list of connected devices, `None` stops the watcher
+    async fn next(&mut self) -> Option<Vec<HWIDevice>>;
+}
+
+/// Whether two enumerations found the same device
+///
+/// The fingerprint is unknown while a device is locked, so devices are told apart by their path.
+pub fn same_device(a: &HWIDevice, b: &HWIDevice) -> bool {
+    a.device_type == b.device_type && a.path == b.path
+}
+
+/// Turns consecutive device lists into connect and disconnect events
+#[derive(Debug, Default)]
+pub struct DeviceTracker {
+    devices: Vec<HWIDevice>,
+}
+
+impl DeviceTracker {
+    pub fn update(&mut self, devices: Vec<HWIDevice>) -> Vec<DeviceEvent> {
+        let mut events: Vec<DeviceEvent> = self
+            .devices
+            .iter()
+            .filter(|old| !devices.iter().any(|new| same_device(old, new)))
+            .cloned()
+            .map(DeviceEvent::Disconnected)
+            .collect();
+        events.extend(
+            devices
+                .iter()
+                .filter(|new| !self.devices.iter().any(|old| same_device(old, new)))
+                .cloned()
+                .map(DeviceEvent::Connected),
+        );
+        self.devices = devices;
+        events
+    }
+}
+
+/// Passes every change reported by `source` to `handle`, until the source is exhausted
+pub async fn watch<S: DeviceEventSource>(mut source: S, mut handle: impl FnMut(DeviceEvent)) {
+    let mut tracker = DeviceTracker::default();
+    while let Some(devices) = source.next().await {
+        tracker.update(devices).into_iter().for_each(&mut handle);
+    }
+}
+
+/// Keeps the watcher from talking to devices at the same time as a command
+pub trait DeviceAccess {
+    /// Runs `operation` while no command can use a device, `None` if one is using it already
+    async fn exclusive<F: Future>(&self, operation: F) -> Option<F::Output>;
+}
+
+/// Makes `PollingSource` enumerate every `POLL_INTERVAL`, while the connect screen waits for a
+/// device
+pub fn set_polling(enabled: bool) {
+    POLLING.store(enabled, Ordering::Relaxed);
+}
+
+/// Makes `PollingSource` enumerate every `SELECTED_POLL_INTERVAL`, while a device is selected
+pub fn set_device_selected(selected: bool) {
+    SELECTED.store(selected, Ordering::Relaxed);
+}
+
+/// Whether `PollingSource` enumerates now, given when it last did
+fn poll_due(polling: bool, selected: bool, last_poll: Option<Instant>) -> bool {
+    polling || (selected && last_poll.is_none_or(|at| at.elapsed() >= SELECTED_POLL_INTERVAL))
+}
+
+/// Enumerates once `access` allows it, without recording the command in the diagnostics
+async fn enumerate_exclusively(access: &impl DeviceAccess) -> Vec<HWIDevice> {
+    loop {
+        let enumerate = diagnostics::unrecorded(HWIAppClient::enumerate(None));
+        match access.exclusive(enumerate).await {
+            // Devices which failed to enumerate show up once they answer
+            Some(Ok(devices)) => return devices.into_iter().filter_map(Result::ok).collect(),
+            Some(Err(e)) => warn!("Device watcher failed to enumerate: {}", e),
+            None => {}
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Enumerates devices while polling is on or a device is selected, on every platform and for
+/// emulators too
+pub struct PollingSource<A> {
+    access: A,
+    started: bool,
+    last_poll: Option<Instant>,
+}
+
+impl<A: DeviceAccess> PollingSource<A> {
+    pub fn new(access: A) -> Self {
+        PollingSource {
+            access,
+            started: false,
+            last_poll: None,
+        }
+    }
+}
+
+impl<A: DeviceAccess> DeviceEventSource for PollingSource<A> {
+    async fn next(&mut self) -> Option<Vec<HWIDevice>> {
+        loop {
+            if self.started {
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+            self.started = true;
+            let polling = POLLING.load(Ordering::Relaxed);
+            let selected = SELECTED.load(Ordering::Relaxed);
+            if poll_due(polling, selected, self.last_poll) {
+                self.last_poll = Some(Instant::now());
+                return Some(enumerate_exclusively(&self.access).await);
+            }
+        }
+    }
+}
+
+/// Enumerates devices whenever udev reports a USB device being added or removed, whether or
+/// not polling is on
+#[cfg(target_os = "linux")]
+pub struct UdevSource<A> {
+    access: A,
+    started: bool,
+    changes: tokio::sync::mpsc::UnboundedReceiver<()>,
+}
+
+#[cfg(target_os = "linux")]
+impl<A: DeviceAccess> UdevSource<A> {
+    pub fn new(access: A) -> Result<Self, String> {
+        let (sender, changes) = tokio::sync::mpsc::unbounded_channel();
+        let (ready, started) = std::sync::mpsc::channel();
+
+        // The udev monitor can't leave the thread it was created on
+        std::thread::spawn(move || {
+            let socket = libudev::Context::new().and_then(|context| {
+                let mut monitor = libudev::Monitor::new(&context)?;
+                monitor.match_subsystem("usb")?;
+                monitor.listen()
+            });
+            let mut socket = match socket {
+                Ok(socket) => {
+                    let _ = ready.send(Ok(()));
+                    socket
+                }
+                Err(e) => {
+                    let _ = ready.send(Err(e.to_string()));
+                    return;
+                }
+            };
+
+            loop {
+                match socket.receive_event() {
+                    Some(event) => {
+                        if matches!(
+                            event.event_type(),
+                            libudev::EventType::Add | libudev::EventType::Remove
+                        ) && sender.send(()).is_err()
+                        {
+                            return;
+                        }
+                    }
+                    None => std::thread::sleep(Duration::from_millis(100)),
+                }
+            }
+        });
+
+        started
+            .recv()
+            .map_err(|e| e.to_string())?
+            .map(|_| UdevSource {
+                access,
+                started: false,
+                changes,
+            })
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl<A: DeviceAccess> DeviceEventSource for UdevSource<A> {
+    async fn next(&mut self) -> Option<Vec<HWIDevice>> {
+        if self.started {
+            self.changes.recv().await?;
+            // A device plugging in fires a burst of events
+            tokio::time::sleep(SETTLE_DELAY).await;
+            while self.changes.try_recv().is_ok() {}
+        }
+        self.started = true;
+        Some(enumerate_exclusively(&self.access).await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hwi::types::HWIDeviceType;
+    use crate::hwi_fixtures::hwi_device;
+    use std::collections::VecDeque;
+    use tauri::async_runtime::block_on;
+
+    struct FakeSource(VecDeque<Vec<HWIDevice>>);
+
+    impl DeviceEventSource for FakeSource {
+        async fn next(&mut self) -> Option<Vec<HWIDevice>> {
+            self.0.pop_front()
+        }
+    }
+
+    fn device(path: &str, fingerprint: Option<&str>) -> HWIDevice {
+        hwi_device(HWIDeviceType::Trezor, "trezor_t", path, fingerprint)
+    }
+
+    #[test]
+    fn reports_plugged_and_unplugged_devices() {
+        let locked = device("webusb:001:2", None);
+        let unlocked = device("webusb:001:2", Some("e2867bb6"));
+        let other = device("webusb:001:3", Some("0f056943"));
+        let source = FakeSource(VecDeque::from([
+            vec![locked.clone()],
+            // Unlocking the device is not a reconnect
+            vec![unlocked.clone()],
+            vec![unlocked.clone(), other.clone()],
+            vec![other.clone()],
+            vec![],
+        ]));
+
+        let mut events = Vec::new();
+        block_on(watch(source, |event| events.push(event)));
+        assert_eq!(
+            events,
+            vec![
+                DeviceEvent::Connected(locked),
+                DeviceEvent::Connected(other.clone()),
+                DeviceEvent::Disconnected(unlocked),
+                DeviceEvent::Disconnected(other),
+            ]
+        );
+    }
+
+    #[test]
+    fn polls_slowly_while_a_device_is_selected() {
+        let now = Instant::now();
+        let earlier = now - SELECTED_POLL_INTERVAL;
+
+        assert!(poll_due(true, false, Some(now)));
+        assert!(!poll_due(false, false, None));
+
+        assert!(poll_due(false, true, None));
+        assert!(!poll_due(false, true, Some(now)));
+        assert!(poll_due(false, true, Some(earlier)));
+    }
+}
@@ -1,3 +1,4 @@
+use serde::Deserialize;
 use std::convert::TryFrom;
 use std::{fmt, io, str};
 
@@ -32,6 +33,32 @@ impl ErrorCode {
     pub fn as_i8(&self) -> i8 {
         -(*self as i8)
     }
+
+    /// Stable name used when the code is reported to the frontend
+    pub fn name(&self) -> &'static str {
+        use ErrorCode::*;
+
+        match self {
+            NoDeviceType => "NO_DEVICE_TYPE",
+            MissingArguments => "MISSING_ARGUMENTS",
+            DeviceConnError => "DEVICE_CONN_ERROR",
+            UnknownDeviceType => "UNKNOWN_DEVICE_TYPE",
+            InvalidTx => "INVALID_TX",
+            NoPassword => "NO_PASSWORD",
+            BadArgument => "BAD_ARGUMENT",
+            NotImplemented => "NOT_IMPLEMENTED",
+            UnavailableAction => "UNAVAILABLE_ACTION",
+            DeviceAlreadyInit => "DEVICE_ALREADY_INIT",
+            DeviceAlreadyUnlocked => "DEVICE_ALREADY_UNLOCKED",
+            DeviceNotReady => "DEVICE_NOT_READY",
+            UnknownError => "UNKNOWN_ERROR",
+            ActionCanceled => "ACTION_CANCELED",
+            DeviceBusy => "DEVICE_BUSY",
+            NeedToBeRoot => "NEED_TO_BE_ROOT",
+            HelpText => "HELP_TEXT",
+            DeviceNotInitialized => "DEVICE_NOT_INITIALIZED",
+        }
+    }
 }
 
 impl TryFrom<i8> for ErrorCode {
@@ -77,9 +104,40 @@ pub enum Error {
     Utf8(std::str::Utf8Error),
     Io(std::io::Error),
     Hwi(String, Option<ErrorCode>),
+    UntrustedBinary(String),
     NotImplemented,
 }
 
+/// Error object printed by the HWI binary, e.g. `{"error": "...", "code": -13}`
+#[derive(Deserialize)]
+struct HWIErrorResponse {
+    error: String,
+    code: Option<i64>,
+}
+
+impl Error {
+    /// Parses the error object HWI prints instead of a result when a command fails
+    pub fn from_hwi_output(output: &str) -> Option<Error> {
+        let value: serde_json::Value = serde_json::from_str(output.trim()).ok()?;
+        if !value.is_object() {
+            return None;
+        }
+        let response: HWIErrorResponse = serde_json::from_value(value).ok()?;
+        let code = response
+            .code
+            .and_then(|code| i8::try_from(code).ok())
+            .and_then(|code| ErrorCode::try_from(code).ok());
+        Some(Error::Hwi(response.error, code))
+    }
+
+    pub fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Error::Hwi(_, code) => *code,
+            _ => None,
+        }
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use Error::*;
@@ -89,6 +147,7 @@ impl fmt::Display for Error {
             Utf8(_) => f.write_str("utf8 error"),
             Io(_) => f.write_str("I/O error"),
             Hwi(ref s, ref code) => write!(f, "HWI error: {}, ({:?})", s, code),
+            UntrustedBinary(ref s) => write!(f, "HWI binary failed verification: {}", s),
             NotImplemented => f.write_str("not implemented"),
         }
     }
@@ -103,6 +162,7 @@ impl std::error::Error for Error {
             Utf8(ref e) => Some(e),
             Io(ref e) => Some(e),
             Hwi(_, _) => None,
+            UntrustedBinary(_) => None,
             NotImplemented => None,
         }
     }
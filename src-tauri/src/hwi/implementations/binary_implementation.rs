@@ -1,5 +1,7 @@
-use serde_json::value::Value;
+use log::warn;
+use std::fmt;
 use std::str;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use crate::hwi::error::Error;
 use crate::hwi::types::{
@@ -8,44 +10,53 @@ use crate::hwi::types::{
 };
 use bitcoin::Psbt;
 
-macro_rules! deserialize_obj {
-    ( $e: expr ) => {{
-        let value: Value = serde_json::from_str($e)?;
-        let obj = value.clone();
-        serde_json::from_value(value)
-            .map_err(|e| Error::Hwi(format!("error {} while deserializing {}", e, obj), None))
-    }};
-}
+/// Whether HWI runs with `--debug`, shared by every client like the HWI log level is
+static DEBUG: AtomicBool = AtomicBool::new(false);
 
 #[derive(Debug)]
 pub struct BinaryHWIImplementation<T: HWIBinaryExecutor> {
     device: Option<HWIDevice>,
     expert: bool,
     chain: HWIChain,
+    /// BIP39 passphrase the device was found with, sent along with every command
+    password: Option<Password>,
     _phantom: std::marker::PhantomData<T>,
 }
 
+/// Keeps the passphrase out of `Debug` output
+#[derive(Clone)]
+struct Password(String);
+
+impl fmt::Debug for Password {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Password(<redacted>)")
+    }
+}
+
 impl<T: HWIBinaryExecutor> HWIImplementation for BinaryHWIImplementation<T> {
-    fn enumerate(chain: Option<HWIChain>) -> Result<String, Error> {
+    async fn enumerate(chain: Option<HWIChain>) -> Result<String, Error> {
         let output = BinaryHWIImplementation::<T>::run_hwi_command(
             None,
             false,
             chain.as_ref(),
+            None,
             vec!["enumerate"],
-        )?;
+        )
+        .await?;
         Ok(output.to_string())
     }
 
-    fn get_client(device: &HWIDevice, expert: bool, chain: HWIChain) -> Result<Self, Error> {
+    async fn get_client(device: &HWIDevice, expert: bool, chain: HWIChain) -> Result<Self, Error> {
         Ok(Self {
             device: Some(device.clone()),
             expert,
             chain,
+            password: None,
             _phantom: std::marker::PhantomData,
         })
     }
 
-    fn find_device(
+    async fn find_device(
         password: Option<&str>,
         device_type: Option<HWIDeviceType>,
         fingerprint: Option<&str>,
@@ -56,21 +67,25 @@ impl<T: HWIBinaryExecutor> HWIImplementation for BinaryHWIImplementation<T> {
             device: None,
             expert,
             chain,
+            password: password.map(|password| Password(password.to_string())),
             _phantom: std::marker::PhantomData,
         };
 
-        let mut args = vec!["enumerate"];
-
-        if let Some(pw) = password {
-            args.extend_from_slice(&["--password", pw]);
-        }
-
-        let output =
-            BinaryHWIImplementation::<T>::run_hwi_command(None, expert, Some(&client.chain), args)?;
-        let devices: Vec<HWIDevice> = deserialize_obj!(&output)?;
-
-        let device = devices
+        let output = BinaryHWIImplementation::<T>::run_hwi_command(
+            None,
+            expert,
+            Some(&client.chain),
+            password,
+            vec!["enumerate"],
+        )
+        .await?;
+        let device = HWIDevice::parse_enumerate(&output)?
             .into_iter()
+            .filter_map(|device| {
+                device
+                    .map_err(|e| warn!("Skipping device which failed to enumerate: {}", e))
+                    .ok()
+            })
             .find(|d| {
                 device_type.as_ref().map_or(true, |t| &d.device_type == t)
                     && fingerprint.map_or(true, |f| {
@@ -87,7 +102,15 @@ impl<T: HWIBinaryExecutor> HWIImplementation for BinaryHWIImplementation<T> {
         Ok(client)
     }
 
-    fn get_master_xpub(&self, addrtype: HWIAddressType, account: u32) -> Result<String, Error> {
+    fn device(&self) -> Option<&HWIDevice> {
+        self.device.as_ref()
+    }
+
+    async fn get_master_xpub(
+        &self,
+        addrtype: HWIAddressType,
+        account: u32,
+    ) -> Result<String, Error> {
         let mut args = vec!["getmasterxpub"];
         let addrtype_str = addrtype.to_string();
         let account_str = account.to_string();
@@ -98,46 +121,54 @@ impl<T: HWIBinaryExecutor> HWIImplementation for BinaryHWIImplementation<T> {
             self.device.as_ref(),
             self.expert,
             Some(&self.chain),
+            self.password(),
             args,
         )
+        .await
     }
 
-    fn sign_tx(&self, psbt: &Psbt) -> Result<String, Error> {
+    async fn sign_tx(&self, psbt: &Psbt) -> Result<String, Error> {
         let psbt_str = psbt.to_string();
-        let args = vec!["signtx", &psbt_str];
 
-        let output = BinaryHWIImplementation::<T>::run_hwi_command(
+        let output = BinaryHWIImplementation::<T>::run_hwi_command_with_stdin(
             self.device.as_ref(),
             self.expert,
             Some(&self.chain),
-            args,
-        )?;
+            self.password(),
+            vec!["signtx"],
+            vec![&psbt_str],
+        )
+        .await?;
         Ok(output)
     }
 
-    fn get_xpub(&self, path: &str, expert: bool) -> Result<String, Error> {
+    async fn get_xpub(&self, path: &str, expert: bool) -> Result<String, Error> {
         let args = vec!["getxpub", &path];
 
         BinaryHWIImplementation::<T>::run_hwi_command(
             self.device.as_ref(),
             expert,
             Some(&self.chain),
+            self.password(),
             args,
         )
+        .await
     }
 
-    fn sign_message(&self, message: &str, path: &str) -> Result<String, Error> {
+    async fn sign_message(&self, message: &str, path: &str) -> Result<String, Error> {
         let args = vec!["signmessage", message, path];
 
         BinaryHWIImplementation::<T>::run_hwi_command(
             self.device.as_ref(),
             self.expert,
             Some(&self.chain),
+            self.password(),
             args,
         )
+        .await
     }
 
-    fn get_keypool(
+    async fn get_keypool(
         &self,
         keypool: bool,
         internal: bool,
@@ -175,11 +206,13 @@ impl<T: HWIBinaryExecutor> HWIImplementation for BinaryHWIImplementation<T> {
             self.device.as_ref(),
             self.expert,
             Some(&self.chain),
+            self.password(),
             args,
         )
+        .await
     }
 
-    fn get_descriptors(&self, account: u32) -> Result<String, Error> {
+    async fn get_descriptors(&self, account: u32) -> Result<String, Error> {
         let mut args = vec!["getdescriptors"];
         let account_str = account.to_string();
         args.extend_from_slice(&["--account", &account_str]);
@@ -188,11 +221,13 @@ impl<T: HWIBinaryExecutor> HWIImplementation for BinaryHWIImplementation<T> {
             self.device.as_ref(),
             self.expert,
             Some(&self.chain),
+            self.password(),
             args,
         )
+        .await
     }
 
-    fn display_address_with_desc(&self, descriptor: &str) -> Result<String, Error> {
+    async fn display_address_with_desc(&self, descriptor: &str) -> Result<String, Error> {
         let mut args = vec!["displayaddress"];
         args.push("--desc");
         args.push(descriptor);
@@ -201,11 +236,13 @@ impl<T: HWIBinaryExecutor> HWIImplementation for BinaryHWIImplementation<T> {
             self.device.as_ref(),
             self.expert,
             Some(&self.chain),
+            self.password(),
             args,
         )
+        .await
     }
 
-    fn display_address_with_path(
+    async fn display_address_with_path(
         &self,
         path: &str,
         address_type: HWIAddressType,
@@ -219,45 +256,52 @@ impl<T: HWIBinaryExecutor> HWIImplementation for BinaryHWIImplementation<T> {
             self.device.as_ref(),
             self.expert,
             Some(&self.chain),
+            self.password(),
             args,
         )
+        .await
     }
 
-    fn install_udev_rules(_: &str, location: &str) -> Result<String, Error> {
+    async fn install_udev_rules(_: &str, location: &str) -> Result<String, Error> {
         let mut args = vec!["installudevrules"];
         args.extend_from_slice(&["--location", location]);
 
-        BinaryHWIImplementation::<T>::run_hwi_command(None, false, None, args)
+        BinaryHWIImplementation::<T>::run_hwi_command(None, false, None, None, args).await
     }
 
-    fn set_log_level(_: LogLevel) -> Result<(), Error> {
-        Err(Error::NotImplemented)
+    async fn set_log_level(level: LogLevel) -> Result<(), Error> {
+        // The CLI has no log levels, only `--debug` on top of its default output
+        DEBUG.store(matches!(level, LogLevel::DEBUG), Ordering::Relaxed);
+        Ok(())
     }
 
-    fn toggle_passphrase(&self) -> Result<String, Error> {
+    async fn toggle_passphrase(&self) -> Result<String, Error> {
         let args = vec!["togglepassphrase"];
         BinaryHWIImplementation::<T>::run_hwi_command(
             self.device.as_ref(),
             self.expert,
             Some(&self.chain),
+            self.password(),
             args,
         )
+        .await
     }
 
-    fn setup_device(&self, label: &str, passphrase: &str) -> Result<String, Error> {
-        let mut args = vec!["setup"];
-        args.extend_from_slice(&["--label", label]);
-        args.extend_from_slice(&["--backup_passphrase", passphrase]);
+    async fn setup_device(&self, label: &str, passphrase: &str) -> Result<String, Error> {
+        let args = vec!["setup", "--label", label];
 
-        BinaryHWIImplementation::<T>::run_hwi_command(
+        BinaryHWIImplementation::<T>::run_hwi_command_with_stdin(
             self.device.as_ref(),
             self.expert,
             Some(&self.chain),
+            self.password(),
             args,
+            vec!["--backup_passphrase", passphrase],
         )
+        .await
     }
 
-    fn restore_device(&self, label: &str, word_count: u8) -> Result<String, Error> {
+    async fn restore_device(&self, label: &str, word_count: u8) -> Result<String, Error> {
         let mut args = vec!["restore"];
         let word_count_str = word_count.to_string();
         args.extend_from_slice(&["--word_count", &word_count_str]);
@@ -267,72 +311,122 @@ impl<T: HWIBinaryExecutor> HWIImplementation for BinaryHWIImplementation<T> {
             self.device.as_ref(),
             self.expert,
             Some(&self.chain),
+            self.password(),
             args,
         )
+        .await
     }
-    fn backup_device(&self, label: &str, backup_passphrase: &str) -> Result<String, Error> {
-        let mut args = vec!["backup"];
-        args.extend_from_slice(&["--label", label]);
-        args.extend_from_slice(&["--backup_passphrase", backup_passphrase]);
+    async fn backup_device(&self, label: &str, backup_passphrase: &str) -> Result<String, Error> {
+        let args = vec!["backup", "--label", label];
 
-        BinaryHWIImplementation::<T>::run_hwi_command(
+        BinaryHWIImplementation::<T>::run_hwi_command_with_stdin(
             self.device.as_ref(),
             self.expert,
             Some(&self.chain),
+            self.password(),
             args,
+            vec!["--backup_passphrase", backup_passphrase],
         )
+        .await
     }
 
-    fn wipe_device(&self) -> Result<String, Error> {
+    async fn wipe_device(&self) -> Result<String, Error> {
         let args = vec!["wipe"];
         BinaryHWIImplementation::<T>::run_hwi_command(
             self.device.as_ref(),
             self.expert,
             Some(&self.chain),
+            self.password(),
             args,
         )
+        .await
     }
 
-    fn prompt_pin(&self) -> Result<String, Error> {
+    async fn prompt_pin(&self) -> Result<String, Error> {
         let args = vec!["promptpin"];
         BinaryHWIImplementation::<T>::run_hwi_command(
             self.device.as_ref(),
             self.expert,
             Some(&self.chain),
+            self.password(),
             args,
         )
+        .await
     }
 
-    fn send_pin(&self, pin: &str) -> Result<String, Error> {
-        let args = vec!["sendpin", pin];
-        BinaryHWIImplementation::<T>::run_hwi_command(
+    async fn send_pin(&self, pin: &str) -> Result<String, Error> {
+        BinaryHWIImplementation::<T>::run_hwi_command_with_stdin(
             self.device.as_ref(),
             self.expert,
             Some(&self.chain),
-            args,
+            self.password(),
+            vec!["sendpin"],
+            vec![pin],
         )
+        .await
     }
 
-    fn get_version() -> Result<String, Error> {
+    async fn get_version() -> Result<String, Error> {
         let args = vec!["--version"];
-        BinaryHWIImplementation::<T>::run_hwi_command(None, false, None, args)
+        BinaryHWIImplementation::<T>::run_hwi_command(None, false, None, None, args).await
     }
 
-    fn install_hwilib(_: String) -> Result<(), Error> {
+    async fn install_hwilib(_: String) -> Result<(), Error> {
         Err(Error::NotImplemented)
     }
 }
 
 impl<T: HWIBinaryExecutor> BinaryHWIImplementation<T> {
-    fn run_hwi_command(
+    fn password(&self) -> Option<&str> {
+        self.password.as_ref().map(|password| password.0.as_str())
+    }
+
+    async fn run_hwi_command(
+        device: Option<&HWIDevice>,
+        expert: bool,
+        chain: Option<&HWIChain>,
+        password: Option<&str>,
+        args: Vec<&str>,
+    ) -> Result<String, Error> {
+        BinaryHWIImplementation::<T>::run_hwi_command_with_stdin(
+            device,
+            expert,
+            chain,
+            password,
+            args,
+            vec![],
+        )
+        .await
+    }
+
+    /// Runs a command whose `stdin_args` are passed through standard input rather than argv,
+    /// where they would be visible to other local users. HWI appends them after `args`.
+    async fn run_hwi_command_with_stdin(
         device: Option<&HWIDevice>,
         expert: bool,
         chain: Option<&HWIChain>,
+        password: Option<&str>,
         args: Vec<&str>,
+        stdin_args: Vec<&str>,
     ) -> Result<String, Error> {
         let mut command_args = Vec::new();
 
-        if !args.contains(&"enumerate") && !args.contains(&"--version") {
+        // The password is a global option and has to come before the subcommand, so with one
+        // set the whole command moves to stdin after it
+        let (args, stdin_args) = match password {
+            Some(password) => (
+                vec![],
+                [vec!["--password", password], args, stdin_args].concat(),
+            ),
+            None => (args, stdin_args),
+        };
+
+        if DEBUG.load(Ordering::Relaxed) {
+            command_args.push("--debug".to_string());
+        }
+
+        let is_enumerate = args.contains(&"enumerate") || stdin_args.contains(&"enumerate");
+        if !is_enumerate && !args.contains(&"--version") {
             let device = device.ok_or(Error::Hwi("Device not set".to_string(), None))?;
             if let Some(fingerprint) = device.fingerprint {
                 command_args.push("--fingerprint".to_string());
@@ -355,8 +449,22 @@ impl<T: HWIBinaryExecutor> BinaryHWIImplementation<T> {
             command_args.push(c.to_string());
         }
 
-        command_args.extend(args.iter().map(|s| s.to_string()));
+        if !stdin_args.is_empty() {
+            command_args.push("--stdin".to_string());
+        }
 
-        T::execute_command(command_args)
+        command_args.extend(args.iter().map(|s| s.to_string()));
+        let stdin_args = stdin_args.iter().map(|s| s.to_string()).collect();
+
+        match T::execute_command(command_args, stdin_args).await {
+            Ok(output) => match Error::from_hwi_output(&output) {
+                Some(e) => Err(e),
+                None => Ok(output),
+            },
+            Err(Error::Hwi(output, None)) => {
+                Err(Error::from_hwi_output(&output).unwrap_or(Error::Hwi(output, None)))
+            }
+            Err(e) => Err(e),
+        }
     }
 }
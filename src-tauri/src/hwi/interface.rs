@@ -8,9 +8,9 @@ use serde_json::value::Value;
 
 use crate::hwi::error::Error;
 use crate::hwi::types::{
-    HWIAddress, HWIAddressType, HWIChain, HWIDescriptor, HWIDevice, HWIDeviceInternal,
-    HWIDeviceType, HWIExtendedPubKey, HWIImplementation, HWIKeyPoolElement,
-    HWIPartiallySignedTransaction, HWISignature, HWIStatus, HWIWordCount, LogLevel, ToDescriptor,
+    HWIAddress, HWIAddressType, HWIChain, HWIDescriptor, HWIDevice, HWIDeviceType,
+    HWIExtendedPubKey, HWIImplementation, HWIKeyPoolElement, HWIPartiallySignedTransaction,
+    HWISignature, HWIStatus, HWIWordCount, LogLevel, ToDescriptor,
 };
 
 macro_rules! deserialize_obj {
@@ -43,10 +43,11 @@ impl<T: HWIImplementation> HWIClient<T> {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn enumerate(chain: Option<HWIChain>) -> Result<Vec<Result<HWIDevice, Error>>, Error> {
-        let output = T::enumerate(chain)?;
-        let devices_internal: Vec<HWIDeviceInternal> = deserialize_obj!(&output)?;
-        Ok(devices_internal.into_iter().map(|d| d.try_into()).collect())
+    pub async fn enumerate(
+        chain: Option<HWIChain>,
+    ) -> Result<Vec<Result<HWIDevice, Error>>, Error> {
+        let output = T::enumerate(chain).await?;
+        HWIDevice::parse_enumerate(&output)
     }
 
     /// Returns the HWIClient for a certain device. You can list all the available devices using
@@ -77,8 +78,12 @@ impl<T: HWIImplementation> HWIClient<T> {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn get_client(device: &HWIDevice, expert: bool, chain: HWIChain) -> Result<Self, Error> {
-        let implementation = T::get_client(device, expert, chain)?;
+    pub async fn get_client(
+        device: &HWIDevice,
+        expert: bool,
+        chain: HWIChain,
+    ) -> Result<Self, Error> {
+        let implementation = T::get_client(device, expert, chain).await?;
 
         Ok(Self { implementation })
     }
@@ -105,7 +110,7 @@ impl<T: HWIImplementation> HWIClient<T> {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn find_device(
+    pub async fn find_device(
         password: Option<&str>,
         device_type: Option<HWIDeviceType>,
         fingerprint: Option<&str>,
@@ -118,46 +123,58 @@ impl<T: HWIImplementation> HWIClient<T> {
             fingerprint,
             expert,
             HWIChain::from(chain),
-        )?;
+        )
+        .await?;
 
         Ok(Self { implementation })
     }
 
+    /// Returns the device this client talks to, as listed by `enumerate`.
+    pub fn device(&self) -> Option<&HWIDevice> {
+        self.implementation.device()
+    }
+
     /// Returns the master xpub of a device, given the address type and the account number.
-    pub fn get_master_xpub(
+    pub async fn get_master_xpub(
         &self,
         addrtype: HWIAddressType,
         account: u32,
     ) -> Result<HWIExtendedPubKey, Error> {
-        let output = self.implementation.get_master_xpub(addrtype, account)?;
+        let output = self
+            .implementation
+            .get_master_xpub(addrtype, account)
+            .await?;
         deserialize_obj!(&output)
     }
 
     /// Signs a PSBT.
-    pub fn sign_tx(&self, psbt: &Psbt) -> Result<HWIPartiallySignedTransaction, Error> {
-        let output = self.implementation.sign_tx(psbt)?;
+    pub async fn sign_tx(&self, psbt: &Psbt) -> Result<HWIPartiallySignedTransaction, Error> {
+        let output = self.implementation.sign_tx(psbt).await?;
         deserialize_obj!(&output)
     }
 
     /// Returns the xpub of a device. If `expert` is set, additional output is returned.
-    pub fn get_xpub(
+    pub async fn get_xpub(
         &self,
         path: &DerivationPath,
         expert: bool,
     ) -> Result<HWIExtendedPubKey, Error> {
         let prefixed_path = format!("m/{}", path);
-        let output = self.implementation.get_xpub(&prefixed_path, expert)?;
+        let output = self.implementation.get_xpub(&prefixed_path, expert).await?;
         deserialize_obj!(&output)
     }
 
     /// Signs a message.
-    pub fn sign_message(
+    pub async fn sign_message(
         &self,
         message: &str,
         path: &DerivationPath,
     ) -> Result<HWISignature, Error> {
         let prefixed_path = format!("m/{}", path);
-        let output = self.implementation.sign_message(message, &prefixed_path)?;
+        let output = self
+            .implementation
+            .sign_message(message, &prefixed_path)
+            .await?;
         deserialize_obj!(&output)
     }
 
@@ -172,7 +189,7 @@ impl<T: HWIImplementation> HWIClient<T> {
     /// * `start` - Keypool start
     /// * `end` - Keypool end
     #[allow(clippy::too_many_arguments)]
-    pub fn get_keypool(
+    pub async fn get_keypool(
         &self,
         keypool: bool,
         internal: bool,
@@ -184,40 +201,49 @@ impl<T: HWIImplementation> HWIClient<T> {
         end: u32,
     ) -> Result<Vec<HWIKeyPoolElement>, Error> {
         let path_str = path.map(|p| format!("m/{}/*", p));
-        let output = self.implementation.get_keypool(
-            keypool,
-            internal,
-            addr_type,
-            addr_all,
-            account.unwrap_or(0),
-            path_str,
-            start,
-            end,
-        )?;
+        let output = self
+            .implementation
+            .get_keypool(
+                keypool,
+                internal,
+                addr_type,
+                addr_all,
+                account.unwrap_or(0),
+                path_str,
+                start,
+                end,
+            )
+            .await?;
         deserialize_obj!(&output)
     }
 
     /// Returns device descriptors. You can optionally specify a BIP43 account to use.
-    pub fn get_descriptors<U>(&self, account: Option<u32>) -> Result<HWIDescriptor<U>, Error>
+    pub async fn get_descriptors<U>(&self, account: Option<u32>) -> Result<HWIDescriptor<U>, Error>
     where
         U: ToDescriptor + DeserializeOwned,
     {
-        let output = self.implementation.get_descriptors(account.unwrap_or(0))?;
+        let output = self
+            .implementation
+            .get_descriptors(account.unwrap_or(0))
+            .await?;
         deserialize_obj!(&output)
     }
 
     /// Returns an address given a descriptor.
-    pub fn display_address_with_desc<U>(&self, descriptor: &U) -> Result<HWIAddress, Error>
+    pub async fn display_address_with_desc<U>(&self, descriptor: &U) -> Result<HWIAddress, Error>
     where
         U: ToDescriptor + ToString,
     {
         let descriptor = descriptor.to_string().split('#').collect::<Vec<_>>()[0].to_string();
-        let output = self.implementation.display_address_with_desc(&descriptor)?;
+        let output = self
+            .implementation
+            .display_address_with_desc(&descriptor)
+            .await?;
         deserialize_obj!(&output)
     }
 
     /// Returns an address given path and address type.
-    pub fn display_address_with_path(
+    pub async fn display_address_with_path(
         &self,
         path: &DerivationPath,
         address_type: HWIAddressType,
@@ -225,7 +251,8 @@ impl<T: HWIImplementation> HWIClient<T> {
         let prefixed_path = format!("m/{}", path);
         let output = self
             .implementation
-            .display_address_with_path(&prefixed_path, address_type)?;
+            .display_address_with_path(&prefixed_path, address_type)
+            .await?;
         deserialize_obj!(&output)
     }
 
@@ -233,11 +260,15 @@ impl<T: HWIImplementation> HWIClient<T> {
     ///
     /// The rules will be copied from the source to the location; the default source location is
     /// `./udev`, the default destination location is `/lib/udev/rules.d`
-    pub fn install_udev_rules(source: Option<&str>, location: Option<&str>) -> Result<(), Error> {
+    pub async fn install_udev_rules(
+        source: Option<&str>,
+        location: Option<&str>,
+    ) -> Result<(), Error> {
         let output = T::install_udev_rules(
             source.unwrap_or("./udev"),
             location.unwrap_or("/lib/udev/rules.d/"),
-        )?;
+        )
+        .await?;
         let status: HWIStatus = deserialize_obj!(&output)?;
         status.into()
     }
@@ -245,29 +276,34 @@ impl<T: HWIImplementation> HWIClient<T> {
     /// Set logging level
     /// # Arguments
     /// * `level` - Log level.
-    pub fn set_log_level(level: LogLevel) -> Result<(), Error> {
-        T::set_log_level(level)?;
+    pub async fn set_log_level(level: LogLevel) -> Result<(), Error> {
+        T::set_log_level(level).await?;
         Ok(())
     }
 
     /// Toggle whether the device is using a BIP 39 passphrase.
-    pub fn toggle_passphrase(&self) -> Result<(), Error> {
-        let output = self.implementation.toggle_passphrase()?;
+    pub async fn toggle_passphrase(&self) -> Result<(), Error> {
+        let output = self.implementation.toggle_passphrase().await?;
         let status: HWIStatus = deserialize_obj!(&output)?;
         status.into()
     }
 
     /// Set up the device
-    pub fn setup_device(&self, label: Option<&str>, passphrase: Option<&str>) -> Result<(), Error> {
+    pub async fn setup_device(
+        &self,
+        label: Option<&str>,
+        passphrase: Option<&str>,
+    ) -> Result<(), Error> {
         let output = self
             .implementation
-            .setup_device(label.unwrap_or(""), passphrase.unwrap_or(""))?;
+            .setup_device(label.unwrap_or(""), passphrase.unwrap_or(""))
+            .await?;
         let status: HWIStatus = deserialize_obj!(&output)?;
         status.into()
     }
 
     /// Restore a device
-    pub fn restore_device(
+    pub async fn restore_device(
         &self,
         label: Option<&str>,
         word_count: Option<HWIWordCount>,
@@ -275,58 +311,62 @@ impl<T: HWIImplementation> HWIClient<T> {
         let word_count: u8 = word_count.map_or_else(|| 24, |w| w as u8);
         let output = self
             .implementation
-            .restore_device(label.unwrap_or(""), word_count)?;
+            .restore_device(label.unwrap_or(""), word_count)
+            .await?;
         let status: HWIStatus = deserialize_obj!(&output)?;
         status.into()
     }
 
     /// Create a backup of the device
-    pub fn backup_device(
+    pub async fn backup_device(
         &self,
         label: Option<&str>,
         backup_passphrase: Option<&str>,
     ) -> Result<(), Error> {
-        let output = self.implementation.backup_device(
-            label.unwrap_or_default(),
-            backup_passphrase.unwrap_or_default(),
-        )?;
+        let output = self
+            .implementation
+            .backup_device(
+                label.unwrap_or_default(),
+                backup_passphrase.unwrap_or_default(),
+            )
+            .await?;
         let status: HWIStatus = deserialize_obj!(&output)?;
         status.into()
     }
 
     /// Wipe a device
-    pub fn wipe_device(&self) -> Result<(), Error> {
-        let output = self.implementation.wipe_device()?;
+    pub async fn wipe_device(&self) -> Result<(), Error> {
+        let output = self.implementation.wipe_device().await?;
         let status: HWIStatus = deserialize_obj!(&output)?;
         status.into()
     }
 
     /// Prompt PIN to a device
-    pub fn prompt_pin(&self) -> Result<(), Error> {
-        let output = self.implementation.prompt_pin()?;
+    pub async fn prompt_pin(&self) -> Result<(), Error> {
+        let output = self.implementation.prompt_pin().await?;
         let status: HWIStatus = deserialize_obj!(&output)?;
         status.into()
     }
 
     /// Send PIN to a device
-    pub fn send_pin(&self, pin: &str) -> Result<(), Error> {
-        let output = self.implementation.send_pin(pin)?;
+    pub async fn send_pin(&self, pin: &str) -> Result<(), Error> {
+        let output = self.implementation.send_pin(pin).await?;
         let status: HWIStatus = deserialize_obj!(&output)?;
         status.into()
     }
 
     /// Get the installed version of hwilib. Returns None if hwi is not installed.
-    pub fn get_version() -> Result<String, Error> {
-        T::get_version()
+    pub async fn get_version() -> Result<String, Error> {
+        T::get_version().await
     }
 
     /// Install hwi for the current user via pip. If no version is specified, the default version from pip will be installed.
-    pub fn install_hwilib(version: Option<&str>) -> Result<(), Error> {
+    pub async fn install_hwilib(version: Option<&str>) -> Result<(), Error> {
         let hwi_with_version = match version {
             Some(ver) => "hwi==".to_owned() + ver,
             None => "hwi".to_owned(),
         };
-        T::install_hwilib(hwi_with_version)?;
+        T::install_hwilib(hwi_with_version).await?;
         Ok(())
     }
 }
@@ -169,28 +169,46 @@ pub struct HWIDevice {
 impl TryFrom<HWIDeviceInternal> for HWIDevice {
     type Error = Error;
     fn try_from(h: HWIDeviceInternal) -> Result<HWIDevice, Error> {
-        match h.error {
-            Some(e) => {
-                let code = h.code.and_then(|c| ErrorCode::try_from(c).ok());
-                Err(Error::Hwi(e, code))
-            }
-            // When HWIDeviceInternal contains errors, some fields might be missing
-            // (depending on the error, hwi might not be able to know all of them).
-            // When there's no error though, all the fields must be present, and
-            // for this reason we expect here.
-            None => Ok(HWIDevice {
-                device_type: HWIDeviceType::from(
-                    h.device_type.expect("Device type should be here"),
-                ),
-                model: h.model.expect("Model should be here"),
-                path: h.path.expect("Path should be here"),
-                needs_pin_sent: h.needs_pin_sent.expect("needs_pin_sent should be here"),
-                needs_passphrase_sent: h
-                    .needs_passphrase_sent
-                    .expect("needs_passphrase_sent should be here"),
-                fingerprint: h.fingerprint,
-            }),
+        // A device waiting for its PIN or passphrase can't report its fingerprint yet, but is
+        // still listed so that it can be unlocked
+        let locked = h.needs_pin_sent == Some(true) || h.needs_passphrase_sent == Some(true);
+        if let Some(e) = h.error.filter(|_| !locked || h.path.is_none()) {
+            let code = h.code.and_then(|c| ErrorCode::try_from(c).ok());
+            return Err(Error::Hwi(e, code));
         }
+
+        // Without an error all the fields should be present, but a newer HWI or an unusual
+        // device may leave some out. Only the type and path are needed to talk to the device.
+        let missing =
+            |field: &str| Error::Hwi(format!("HWI enumerate entry is missing `{}`", field), None);
+        let device_type = h.device_type.ok_or_else(|| missing("type"))?;
+        Ok(HWIDevice {
+            model: h.model.unwrap_or_else(|| device_type.clone()),
+            device_type: HWIDeviceType::from(device_type),
+            path: h.path.ok_or_else(|| missing("path"))?,
+            needs_pin_sent: h.needs_pin_sent.unwrap_or(false),
+            needs_passphrase_sent: h.needs_passphrase_sent.unwrap_or(false),
+            fingerprint: h.fingerprint,
+        })
+    }
+}
+
+impl HWIDevice {
+    /// Parses the output of `hwi enumerate`, with one result per listed device
+    ///
+    /// An entry which can't be parsed only fails that device, the others stay usable.
+    pub(crate) fn parse_enumerate(output: &str) -> Result<Vec<Result<HWIDevice, Error>>, Error> {
+        let entries: Vec<serde_json::Value> = serde_json::from_str(output)?;
+        Ok(entries
+            .into_iter()
+            .map(|entry| {
+                serde_json::from_value::<HWIDeviceInternal>(entry.clone())
+                    .map_err(|e| {
+                        Error::Hwi(format!("error {} while deserializing {}", e, entry), None)
+                    })?
+                    .try_into()
+            })
+            .collect())
     }
 }
 
@@ -259,6 +277,7 @@ impl Display for HWIDeviceType {
     }
 }
 
+#[derive(Clone, Copy, Debug, Deserialize)]
 pub enum LogLevel {
     DEBUG,
     INFO,
@@ -277,11 +296,11 @@ pub enum HWIWordCount {
 }
 
 pub trait HWIImplementation {
-    fn enumerate(chain: Option<HWIChain>) -> Result<String, Error>;
-    fn get_client(device: &HWIDevice, expert: bool, chain: HWIChain) -> Result<Self, Error>
+    async fn enumerate(chain: Option<HWIChain>) -> Result<String, Error>;
+    async fn get_client(device: &HWIDevice, expert: bool, chain: HWIChain) -> Result<Self, Error>
     where
         Self: Sized;
-    fn find_device(
+    async fn find_device(
         password: Option<&str>,
         device_type: Option<HWIDeviceType>,
         fingerprint: Option<&str>,
@@ -290,26 +309,31 @@ pub trait HWIImplementation {
     ) -> Result<Self, Error>
     where
         Self: Sized;
-    fn get_xpub(&self, path: &str, expert: bool) -> Result<String, Error>;
-    fn sign_tx(&self, psbt: &Psbt) -> Result<String, Error>;
-    fn get_master_xpub(&self, addrtype: HWIAddressType, account: u32) -> Result<String, Error>;
-    fn sign_message(&self, message: &str, path: &str) -> Result<String, Error>;
-    fn display_address_with_desc(&self, descriptor: &str) -> Result<String, Error>;
-    fn display_address_with_path(
+    fn device(&self) -> Option<&HWIDevice>;
+    async fn get_xpub(&self, path: &str, expert: bool) -> Result<String, Error>;
+    async fn sign_tx(&self, psbt: &Psbt) -> Result<String, Error>;
+    async fn get_master_xpub(
+        &self,
+        addrtype: HWIAddressType,
+        account: u32,
+    ) -> Result<String, Error>;
+    async fn sign_message(&self, message: &str, path: &str) -> Result<String, Error>;
+    async fn display_address_with_desc(&self, descriptor: &str) -> Result<String, Error>;
+    async fn display_address_with_path(
         &self,
         path: &str,
         address_type: HWIAddressType,
     ) -> Result<String, Error>;
-    fn toggle_passphrase(&self) -> Result<String, Error>;
-    fn setup_device(&self, label: &str, passphrase: &str) -> Result<String, Error>;
-    fn restore_device(&self, label: &str, word_count: u8) -> Result<String, Error>;
-    fn backup_device(&self, label: &str, backup_passphrase: &str) -> Result<String, Error>;
-    fn wipe_device(&self) -> Result<String, Error>;
-    fn prompt_pin(&self) -> Result<String, Error>;
-    fn send_pin(&self, pin: &str) -> Result<String, Error>;
-    fn get_descriptors(&self, account: u32) -> Result<String, Error>;
+    async fn toggle_passphrase(&self) -> Result<String, Error>;
+    async fn setup_device(&self, label: &str, passphrase: &str) -> Result<String, Error>;
+    async fn restore_device(&self, label: &str, word_count: u8) -> Result<String, Error>;
+    async fn backup_device(&self, label: &str, backup_passphrase: &str) -> Result<String, Error>;
+    async fn wipe_device(&self) -> Result<String, Error>;
+    async fn prompt_pin(&self) -> Result<String, Error>;
+    async fn send_pin(&self, pin: &str) -> Result<String, Error>;
+    async fn get_descriptors(&self, account: u32) -> Result<String, Error>;
     #[allow(clippy::too_many_arguments)]
-    fn get_keypool(
+    async fn get_keypool(
         &self,
         keypool: bool,
         internal: bool,
@@ -320,12 +344,51 @@ pub trait HWIImplementation {
         start: u32,
         end: u32,
     ) -> Result<String, Error>;
-    fn get_version() -> Result<String, Error>;
-    fn install_udev_rules(source: &str, location: &str) -> Result<String, Error>;
-    fn set_log_level(level: LogLevel) -> Result<(), Error>;
-    fn install_hwilib(version: String) -> Result<(), Error>;
+    async fn get_version() -> Result<String, Error>;
+    async fn install_udev_rules(source: &str, location: &str) -> Result<String, Error>;
+    async fn set_log_level(level: LogLevel) -> Result<(), Error>;
+    async fn install_hwilib(version: String) -> Result<(), Error>;
 }
 
 pub trait HWIBinaryExecutor {
-    fn execute_command(args: Vec<String>) -> Result<String, Error>;
+    /// Runs HWI with `args`, writing `stdin_args` to its standard input when `--stdin` is set
+    async fn execute_command(args: Vec<String>, stdin_args: Vec<String>) -> Result<String, Error>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_enumerate_entries_independently() {
+        let output = r#"[
+            {"type": "trezor", "model": "trezor_t", "path": "webusb:001:1", "needs_pin_sent": false, "needs_passphrase_sent": false, "fingerprint": "e2867bb6"},
+            {"type": "keepkey", "path": "hid:0001", "error": "Could not open client or get fingerprint information", "code": -13},
+            {"type": "coldcard", "model": "coldcard"},
+            {"type": "jade", "path": "/dev/ttyUSB0", "fingerprint": "not a fingerprint"},
+            {"type": "ledger", "path": "hid:0002"},
+            {"type": "trezor", "model": "trezor_1", "path": "hid:0003", "needs_pin_sent": false, "needs_passphrase_sent": true, "error": "Passphrase needs to be specified before the fingerprint information can be retrieved", "code": -12}
+        ]"#;
+
+        let devices = HWIDevice::parse_enumerate(output).unwrap();
+        assert_eq!(devices.len(), 6);
+        assert_eq!(
+            devices[0].as_ref().unwrap().device_type,
+            HWIDeviceType::Trezor
+        );
+        assert_eq!(
+            devices[1].as_ref().unwrap_err().code(),
+            Some(ErrorCode::UnknownError)
+        );
+        assert!(devices[2].is_err());
+        assert!(devices[3].is_err());
+
+        let ledger = devices[4].as_ref().unwrap();
+        assert_eq!(ledger.model, "ledger");
+        assert!(!ledger.needs_pin_sent);
+
+        let locked = devices[5].as_ref().unwrap();
+        assert!(locked.needs_passphrase_sent);
+        assert_eq!(locked.fingerprint, None);
+    }
 }
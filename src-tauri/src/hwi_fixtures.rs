@@ -0,0 +1,246 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::diagnostics::subcommand;
+use crate::hwi::error::Error;
+use crate::hwi::types::HWIBinaryExecutor;
+
+/// Overrides the directory fixtures are read from and recorded to
+const FIXTURES_DIR_ENV: &str = "KEEPER_HWI_FIXTURES";
+
+/// A single recorded HWI invocation
+///
+/// Fixtures are grouped by subcommand, e.g. every `getxpub` call lives in "getxpub.json".
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Fixture {
+    pub args: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stdin_args: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdout: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+pub fn fixtures_dir() -> PathBuf {
+    std::env::var_os(FIXTURES_DIR_ENV)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| {
+            Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("fixtures")
+                .join("hwi")
+        })
+}
+
+/// Serves HWI output from the fixture files instead of running the sidecar
+#[cfg(test)]
+pub struct MockExecutor;
+
+#[cfg(test)]
+impl HWIBinaryExecutor for MockExecutor {
+    async fn execute_command(args: Vec<String>, stdin_args: Vec<String>) -> Result<String, Error> {
+        let fixture = load(&fixture_path(&args, &stdin_args))?
+            .into_iter()
+            .find(|fixture| fixture.args == args && fixture.stdin_args == stdin_args)
+            .ok_or_else(|| Error::Hwi(format!("No fixture for hwi {}", args.join(" ")), None))?;
+
+        match fixture.error {
+            Some(error) => Err(Error::Hwi(error, None)),
+            None => Ok(fixture.stdout.unwrap_or_default()),
+        }
+    }
+}
+
+/// Runs commands with `E` and saves each invocation as a fixture
+///
+/// Recordings contain PSBTs and xpubs, so this is only available with the `record-hwi` feature.
+/// Commands carrying a PIN, password or passphrase are never recorded.
+#[cfg(feature = "record-hwi")]
+#[cfg_attr(test, allow(dead_code))]
+pub struct RecordingExecutor<E>(std::marker::PhantomData<E>);
+
+#[cfg(feature = "record-hwi")]
+impl<E: HWIBinaryExecutor> HWIBinaryExecutor for RecordingExecutor<E> {
+    async fn execute_command(args: Vec<String>, stdin_args: Vec<String>) -> Result<String, Error> {
+        let result = E::execute_command(args.clone(), stdin_args.clone()).await;
+        if carries_secret(&args, &stdin_args) {
+            return result;
+        }
+
+        let fixture = Fixture {
+            args,
+            stdin_args,
+            stdout: result.as_ref().ok().cloned(),
+            error: match &result {
+                Ok(_) => None,
+                Err(Error::Hwi(error, _)) => Some(error.clone()),
+                Err(e) => Some(e.to_string()),
+            },
+        };
+        if let Err(e) = record(fixture) {
+            log::warn!("Failed to record HWI fixture: {}", e);
+        }
+        result
+    }
+}
+
+#[cfg(feature = "record-hwi")]
+fn carries_secret(args: &[String], stdin_args: &[String]) -> bool {
+    matches!(subcommand(args, stdin_args), "sendpin" | "setup" | "backup")
+        || stdin_args.iter().any(|arg| arg == "--password")
+}
+
+#[cfg(feature = "record-hwi")]
+fn record(fixture: Fixture) -> Result<(), Error> {
+    let path = fixture_path(&fixture.args, &fixture.stdin_args);
+    let mut fixtures = load(&path)?;
+    fixtures.retain(|f| f.args != fixture.args || f.stdin_args != fixture.stdin_args);
+    fixtures.push(fixture);
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, serde_json::to_string_pretty(&fixtures)?)?;
+    Ok(())
+}
+
+fn load(path: &Path) -> Result<Vec<Fixture>, Error> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+}
+
+fn fixture_path(args: &[String], stdin_args: &[String]) -> PathBuf {
+    fixtures_dir().join(format!("{}.json", subcommand(args, stdin_args)))
+}
+
+#[cfg(test)]
+pub const FIXTURE_FINGERPRINT: &str = "e2867bb6";
+
+/// Client for the Trezor recorded in the fixtures
+#[cfg(test)]
+pub fn fixture_client() -> crate::HWIAppClient {
+    tauri::async_runtime::block_on(crate::HWIAppClient::find_device(
+        None,
+        Some(crate::hwi::types::HWIDeviceType::Trezor),
+        Some(FIXTURE_FINGERPRINT),
+        false,
+        bitcoin::Network::Testnet,
+    ))
+    .unwrap()
+}
+
+/// Device as HWI enumerates it, locked until it has a fingerprint
+#[cfg(test)]
+pub fn hwi_device(
+    device_type: crate::hwi::types::HWIDeviceType,
+    model: &str,
+    path: &str,
+    fingerprint: Option<&str>,
+) -> crate::hwi::types::HWIDevice {
+    crate::hwi::types::HWIDevice {
+        device_type,
+        model: model.to_string(),
+        path: path.to_string(),
+        needs_pin_sent: fingerprint.is_none(),
+        needs_passphrase_sent: false,
+        fingerprint: fingerprint.map(|f| f.parse().unwrap()),
+    }
+}
+
+/// Client for a locked Trezor One of the REGTEST fixtures, see src/pin.rs
+#[cfg(test)]
+pub fn locked_client(path: &str) -> crate::HWIAppClient {
+    let device = hwi_device(
+        crate::hwi::types::HWIDeviceType::Trezor,
+        "trezor_1",
+        path,
+        None,
+    );
+    tauri::async_runtime::block_on(crate::HWIAppClient::get_client(
+        &device,
+        false,
+        crate::hwi::types::HWIChain::from(bitcoin::Network::Regtest),
+    ))
+    .unwrap()
+}
+
+/// State of the Trezor recorded in the fixtures, as left by `set_hwi_client`
+#[cfg(test)]
+pub fn fixture_state() -> crate::HWIClientState {
+    crate::HWIClientState {
+        hwi: fixture_client(),
+        device_type: crate::hwi::types::HWIDeviceType::Trezor,
+        fingerprint: Some(FIXTURE_FINGERPRINT.to_string()),
+        network: bitcoin::Network::Testnet,
+        pin: crate::pin::PinUnlock::new(None),
+        firmware: crate::capabilities::Firmware::NotReported,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hwi::error::ErrorCode;
+    use crate::hwi::types::{HWIDeviceType, TESTNET};
+    use crate::HWIAppClient;
+    use bitcoin::Psbt;
+    use std::str::FromStr;
+    use tauri::async_runtime::block_on;
+
+    const UNSIGNED_PSBT: &str = "cHNidP8BAFICAAAAAQEAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAD9////AZBfAQAAAAAAFgAU8ozs5duvGfsgWWby4j22jvYSbQMAAAAAAAAA";
+
+    #[test]
+    fn finds_subcommand_after_global_options() {
+        let args: Vec<String> = ["--fingerprint", "e2867bb6", "--chain", "TEST", "getxpub"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(subcommand(&args, &[]), "getxpub");
+        assert_eq!(subcommand(&["--version".to_string()], &[]), "version");
+        assert_eq!(
+            subcommand(&["--stdin".to_string()], &["sendpin".to_string()]),
+            "sendpin"
+        );
+    }
+
+    #[test]
+    fn enumerates_recorded_devices() {
+        let devices = block_on(HWIAppClient::enumerate(Some(TESTNET))).unwrap();
+        assert_eq!(devices.len(), 1);
+
+        let device = devices[0].as_ref().unwrap();
+        assert_eq!(device.device_type, HWIDeviceType::Trezor);
+        assert_eq!(
+            device.fingerprint.map(|f| f.to_string()).as_deref(),
+            Some(FIXTURE_FINGERPRINT)
+        );
+    }
+
+    #[test]
+    fn signs_recorded_psbt() {
+        let client = fixture_client();
+        let psbt = Psbt::from_str(UNSIGNED_PSBT).unwrap();
+
+        let signed = block_on(client.sign_tx(&psbt)).unwrap().psbt;
+        assert_eq!(signed.unsigned_tx, psbt.unsigned_tx);
+        assert_eq!(signed.inputs[0].partial_sigs.len(), 1);
+    }
+
+    #[test]
+    fn parses_recorded_error_codes() {
+        let client = fixture_client();
+        let descriptor = "wpkh([e2867bb6/84'/1'/0']tpubDDPRy5xWxJTuVmsh7YRzK8o2EdMWgn4t41fTLxXRgyRN7EKvN2L8BKCFC1gUfPu8Xp6rr667Yc26zrXsiBZsgBc8dQiYnhPNk2Q7CsBrer5/0/1)";
+
+        let error =
+            block_on(client.display_address_with_desc(&descriptor.to_string())).unwrap_err();
+        assert_eq!(error.code(), Some(ErrorCode::ActionCanceled));
+    }
+
+    #[test]
+    fn fails_without_fixture() {
+        let client = fixture_client();
+        assert!(block_on(client.wipe_device()).is_err());
+    }
+}
@@ -0,0 +1,202 @@
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::OsRng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use thiserror::Error;
+
+use crate::approval::unix_time;
+use crate::hwi::types::HWIWordCount;
+
+/// Time the user has to go through with a confirmed operation
+pub const CONFIRMATION_TIMEOUT_SECS: u64 = 60;
+
+#[derive(Error, Debug)]
+pub enum LifecycleError {
+    #[error("{0} has to be confirmed first")]
+    NotConfirmed(LifecycleOperation),
+    #[error("The confirmation was given for {0}")]
+    OperationMismatch(LifecycleOperation),
+    #[error("The confirmation was given for another device")]
+    DeviceMismatch,
+    #[error("The confirmation has expired")]
+    Expired,
+    #[error("Type {0} to confirm")]
+    PhraseMismatch(String),
+    #[error("Unsupported word count {0}, expected 12, 18 or 24")]
+    InvalidWordCount(u8),
+}
+
+/// Operations which change what is stored on the device
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum LifecycleOperation {
+    SetupDevice,
+    RestoreDevice,
+    BackupDevice,
+    WipeDevice,
+    TogglePassphrase,
+}
+
+impl LifecycleOperation {
+    /// Name of the channel action and audit entries
+    pub fn action(&self) -> &'static str {
+        match self {
+            LifecycleOperation::SetupDevice => "SETUP_DEVICE",
+            LifecycleOperation::RestoreDevice => "RESTORE_DEVICE",
+            LifecycleOperation::BackupDevice => "BACKUP_DEVICE",
+            LifecycleOperation::WipeDevice => "WIPE_DEVICE",
+            LifecycleOperation::TogglePassphrase => "TOGGLE_PASSPHRASE",
+        }
+    }
+
+    /// Whether the operation destroys keys, and thus needs a confirmation token
+    pub fn is_destructive(&self) -> bool {
+        matches!(self, LifecycleOperation::WipeDevice)
+    }
+}
+
+impl std::fmt::Display for LifecycleOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.action())
+    }
+}
+
+/// What the user has to type before a destructive operation, e.g. the device fingerprint
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfirmationChallenge {
+    pub operation: LifecycleOperation,
+    pub phrase: String,
+}
+
+impl ConfirmationChallenge {
+    pub fn check(&self, typed: &str) -> Result<(), LifecycleError> {
+        if typed.trim().eq_ignore_ascii_case(&self.phrase) {
+            Ok(())
+        } else {
+            Err(LifecycleError::PhraseMismatch(self.phrase.clone()))
+        }
+    }
+}
+
+/// Proof that the user confirmed an operation on a specific device, usable once
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfirmationToken {
+    pub token: String,
+    pub operation: LifecycleOperation,
+    pub device: String,
+    pub expires_at: u64,
+}
+
+#[derive(Default)]
+pub struct Confirmations {
+    pending: HashMap<String, ConfirmationToken>,
+}
+
+impl Confirmations {
+    pub fn issue(&mut self, operation: LifecycleOperation, device: String) -> ConfirmationToken {
+        let mut token_bytes = [0u8; 16];
+        OsRng.fill_bytes(&mut token_bytes);
+
+        let now = unix_time();
+        self.pending.retain(|_, token| token.expires_at > now);
+
+        let token = ConfirmationToken {
+            token: hex::encode(token_bytes),
+            operation,
+            device,
+            expires_at: now + CONFIRMATION_TIMEOUT_SECS,
+        };
+        self.pending.insert(token.token.clone(), token.clone());
+        token
+    }
+
+    /// Checks and uses up a token, which is gone even when it doesn't match
+    pub fn consume(
+        &mut self,
+        token: &str,
+        operation: LifecycleOperation,
+        device: &str,
+    ) -> Result<(), LifecycleError> {
+        let confirmation = self
+            .pending
+            .remove(token)
+            .ok_or(LifecycleError::NotConfirmed(operation))?;
+
+        if confirmation.expires_at <= unix_time() {
+            Err(LifecycleError::Expired)
+        } else if confirmation.operation != operation {
+            Err(LifecycleError::OperationMismatch(confirmation.operation))
+        } else if confirmation.device != device {
+            Err(LifecycleError::DeviceMismatch)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+pub fn word_count(words: u8) -> Result<HWIWordCount, LifecycleError> {
+    match words {
+        12 => Ok(HWIWordCount::W12),
+        18 => Ok(HWIWordCount::W18),
+        24 => Ok(HWIWordCount::W24),
+        words => Err(LifecycleError::InvalidWordCount(words)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn challenges_need_the_exact_phrase() {
+        let challenge = ConfirmationChallenge {
+            operation: LifecycleOperation::WipeDevice,
+            phrase: "e2867bb6".to_string(),
+        };
+        assert!(challenge.check("e2867bb6").is_ok());
+        assert!(challenge.check(" E2867BB6\n").is_ok());
+        assert!(matches!(
+            challenge.check("e2867bb"),
+            Err(LifecycleError::PhraseMismatch(phrase)) if phrase == "e2867bb6"
+        ));
+        assert!(challenge.check("").is_err());
+    }
+
+    #[test]
+    fn tokens_are_bound_to_operation_and_device() {
+        let mut confirmations = Confirmations::default();
+
+        let token = confirmations.issue(LifecycleOperation::WipeDevice, "trezor e2867bb6".into());
+        assert!(matches!(
+            confirmations.consume(&token.token, LifecycleOperation::WipeDevice, "trezor other"),
+            Err(LifecycleError::DeviceMismatch)
+        ));
+        // A rejected token can't be retried
+        assert!(matches!(
+            confirmations.consume(
+                &token.token,
+                LifecycleOperation::WipeDevice,
+                "trezor e2867bb6"
+            ),
+            Err(LifecycleError::NotConfirmed(_))
+        ));
+
+        let token = confirmations.issue(LifecycleOperation::WipeDevice, "trezor e2867bb6".into());
+        assert!(confirmations
+            .consume(
+                &token.token,
+                LifecycleOperation::WipeDevice,
+                "trezor e2867bb6"
+            )
+            .is_ok());
+        assert!(confirmations
+            .consume(
+                &token.token,
+                LifecycleOperation::WipeDevice,
+                "trezor e2867bb6"
+            )
+            .is_err());
+    }
+}
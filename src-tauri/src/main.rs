@@ -1,44 +1,88 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod approval;
+mod audit;
+mod capabilities;
 mod channel;
+mod descriptors;
 mod device;
+mod diagnostics;
+mod emulators;
+mod enumeration;
+mod error;
+mod hotplug;
 mod hwi;
+#[cfg(any(test, feature = "record-hwi"))]
+mod hwi_fixtures;
+mod lifecycle;
+mod message;
 mod miniscript_hwi;
+mod pin;
+mod progress;
+mod sidecar;
+use approval::{ApprovalGate, ApprovalRule, PendingApproval};
 use async_hwi::AddressScript;
+use audit::{AuditEvent, AuditFilter, AuditLog, AuditQueryResult, AuditSource};
 use bitcoin::base64::{engine::general_purpose, Engine as _};
 use bitcoin::Address;
-use channel::Channel;
-use device::get_xpubs;
-use hwi::error::Error;
+use capabilities::Feature;
+use channel::{Channel, ChannelError};
+use descriptors::{AccountDescriptors, ExportFormat};
+use device::{get_xpubs, verify_address_at_path};
+use emulators::{Emulator, EmulatorSetting};
+use enumeration::EnumeratedDevice;
+use error::AppError;
+use hotplug::DeviceEvent;
 use hwi::implementations::binary_implementation::BinaryHWIImplementation;
 use hwi::interface::HWIClient;
-use hwi::types::{HWIBinaryExecutor, HWIChain, HWIDevice, HWIDeviceType};
+use hwi::types::{HWIAddressType, HWIChain, HWIDevice, HWIDeviceType, LogLevel};
+use lifecycle::{ConfirmationChallenge, ConfirmationToken, Confirmations, LifecycleOperation};
 #[cfg(target_os = "linux")]
 use log::warn;
-use miniscript_hwi::{get_miniscript_device_by_fingerprint, list_devices, Wallet};
+use message::{MessageError, MessageFormat, MessageVerification, SignedMessage};
+use miniscript_hwi::{get_device_by_fingerprint, get_miniscript_device_by_fingerprint};
+use pin::{PinStatus, PinUnlock};
+use progress::{count_newly_signed_inputs, ProgressReporter, ProgressStage};
 use serde_json::{json, Value};
+#[cfg(not(test))]
+use sidecar::HWIBinaryExecutorImpl;
 #[cfg(target_os = "linux")]
 use std::path::Path;
 use std::str::FromStr;
-use tauri::api::process::Command;
-use tauri::{Manager, State};
+use tauri::{Manager, Runtime, State};
 use tokio::sync::Mutex;
 
 pub type AppState = Mutex<AppStateInner>;
-type HWIAppClient = HWIClient<BinaryHWIImplementation<HWIBinaryExecutorImpl>>;
+
+#[cfg(test)]
+type AppExecutor = hwi_fixtures::MockExecutor;
+#[cfg(all(not(test), feature = "record-hwi"))]
+type AppExecutor = hwi_fixtures::RecordingExecutor<HWIBinaryExecutorImpl>;
+#[cfg(all(not(test), not(feature = "record-hwi")))]
+type AppExecutor = HWIBinaryExecutorImpl;
+
+type HWIAppClient = HWIClient<BinaryHWIImplementation<AppExecutor>>;
 
 pub struct HWIClientState {
     hwi: HWIAppClient,
-    #[allow(dead_code)]
     device_type: HWIDeviceType,
     fingerprint: Option<String>,
     network: bitcoin::Network,
+    pin: PinUnlock,
+    /// Checked against the minimum versions of the capability matrix
+    firmware: capabilities::Firmware,
 }
 
 pub struct AppStateInner {
     channel: Channel,
     hwi: Option<HWIClientState>,
+    approvals: ApprovalGate,
+    audit: AuditLog,
+    /// Pending confirmations of destructive device operations
+    confirmations: Confirmations,
+    /// Id of the channel request being handled, used to tag progress events
+    active_request: Option<String>,
 }
 
 #[cfg(not(feature = "release"))]
@@ -53,7 +97,7 @@ static ENV: &str = "prod";
 async fn connect_channel(
     app_handle: tauri::AppHandle,
     state: State<'_, AppState>,
-) -> Result<bool, String> {
+) -> Result<bool, AppError> {
     let mut state = state.lock().await;
     if state.channel.client.is_none() {
         let new_channel = Channel::new(app_handle, 30).await;
@@ -61,7 +105,7 @@ async fn connect_channel(
             state.channel = new_channel;
             Ok(true)
         } else {
-            Err("Failed to connect channel".to_string())
+            Err(AppError::Channel(ChannelError::NoClient))
         }
     } else {
         Ok(true)
@@ -69,30 +113,30 @@ async fn connect_channel(
 }
 
 #[tauri::command]
-fn disconnect_channel(state: State<'_, AppState>) -> Result<(), String> {
-    let state = state.try_lock().map_err(|e| e.to_string())?;
-    state.channel.disconnect().map_err(|e| e.to_string())
+fn disconnect_channel(state: State<'_, AppState>) -> Result<(), AppError> {
+    let state = state.try_lock()?;
+    state.channel.disconnect().map_err(AppError::from)
 }
 
 #[tauri::command]
-fn get_channel_secret(state: State<'_, AppState>) -> Result<String, String> {
-    let state = state.try_lock().map_err(|e| e.to_string())?;
+fn get_channel_secret(state: State<'_, AppState>) -> Result<String, AppError> {
+    let state = state.try_lock()?;
     Ok(state.channel.encryption_key.clone().unwrap_or_default())
 }
 
 #[tauri::command]
-fn generate_encryption_key(state: State<'_, AppState>) -> Result<String, String> {
-    let mut state = state.try_lock().map_err(|e| e.to_string())?;
+fn generate_encryption_key(state: State<'_, AppState>) -> Result<String, AppError> {
+    let mut state = state.try_lock()?;
     state
         .channel
         .generate_encryption_key()
-        .map_err(|e| e.to_string())
+        .map_err(AppError::from)
 }
 
 #[tauri::command]
-fn emit_to_channel(state: State<'_, AppState>, event_data: Value) -> Result<(), String> {
-    let state = state.try_lock().map_err(|e| e.to_string())?;
-    let hwi_state = state.hwi.as_ref().ok_or("HWI client not initialized")?;
+fn emit_to_channel(state: State<'_, AppState>, event_data: Value) -> Result<(), AppError> {
+    let state = state.try_lock()?;
+    let hwi_state = state.hwi.as_ref().ok_or(AppError::NoDevice)?;
     state
         .channel
         .emit(
@@ -101,93 +145,202 @@ fn emit_to_channel(state: State<'_, AppState>, event_data: Value) -> Result<(),
             false,
             Some(&hwi_state.network.to_string()),
         )
-        .map_err(|e| e.to_string())
+        .map_err(AppError::from)
 }
 
-// ==================== HWI Commands ====================
+// ==================== Approval Commands ====================
 
 #[tauri::command]
-async fn hwi_enumerate(
-    _: State<'_, AppState>,
-    network: Option<bitcoin::Network>,
-) -> Result<Vec<Result<HWIDevice, String>>, String> {
-    HWIAppClient::enumerate(network.map(HWIChain::from))
-        .map(|devices| {
-            devices
-                .into_iter()
-                .map(|device| device.map_err(|e| e.to_string()))
-                .collect()
-        })
-        .map_err(|e| e.to_string())
+fn get_pending_approvals(state: State<'_, AppState>) -> Result<Vec<PendingApproval>, AppError> {
+    let state = state.try_lock()?;
+    Ok(state.approvals.pending())
 }
 
 #[tauri::command]
-async fn async_hwi_enumerate(
+async fn approve_request(
     app_handle: tauri::AppHandle,
-    _: State<'_, AppState>,
-    network: Option<bitcoin::Network>,
-) -> Result<Vec<Result<HWIDevice, String>>, String> {
-    let devices = list_devices(
-        network.unwrap_or(bitcoin::Network::Bitcoin),
-        Some(Wallet {
-            name: Some(&"none".to_string()), // Needed in case other devices which aren't the BB02 and require wallet name are connected
-            policy: Some(&"none".to_string()), // Needed in case other devices which aren't the BB02 and require wallet policy are connected
-            hmac: None,
-        }),
-        Some(app_handle),
-    )
-    .await
-    .map_err(|e| e.to_string())?;
+    state: State<'_, AppState>,
+    id: String,
+    remember: bool,
+) -> Result<(), AppError> {
+    let mut state = state.lock().await;
+    let message = state.approvals.approve(&id, remember)?;
+    state.audit.record(AuditEvent::new(
+        AuditSource::Channel,
+        message["data"]["action"].as_str().unwrap_or_default(),
+        "approved",
+    ));
+    approval::forward_message(&app_handle, &mut state, message)
+        .map_err(|e| AppError::Other(e.to_string()))
+}
 
-    let mut hwi_devices = Vec::new();
-    for device in devices {
-        let fingerprint = device
-            .get_master_fingerprint()
-            .await
-            .map_err(|e| e.to_string())?;
-        hwi_devices.push(Ok(HWIDevice {
-            device_type: HWIDeviceType::from(device.device_kind().to_string().as_str()),
-            model: "".to_string(),
-            path: "".to_string(),
-            needs_pin_sent: false,
-            needs_passphrase_sent: false,
-            fingerprint: Some(fingerprint),
-        }));
+#[tauri::command]
+async fn reject_request(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<(), AppError> {
+    let mut state = state.lock().await;
+    let rejected = state.approvals.reject(&id)?;
+    approval::notify_denied(&app_handle, &state, &rejected, "rejected");
+    Ok(())
+}
+
+#[tauri::command]
+fn get_approval_rules(state: State<'_, AppState>) -> Result<Vec<ApprovalRule>, AppError> {
+    let state = state.try_lock()?;
+    Ok(state.approvals.rules().to_vec())
+}
+
+#[tauri::command]
+fn remove_approval_rule(state: State<'_, AppState>, rule: ApprovalRule) -> Result<(), AppError> {
+    let mut state = state.try_lock()?;
+    state.approvals.remove_rule(&rule).map_err(AppError::from)
+}
+
+// ==================== Audit Commands ====================
+
+#[tauri::command]
+fn get_audit_log(
+    state: State<'_, AppState>,
+    filter: Option<AuditFilter>,
+) -> Result<AuditQueryResult, AppError> {
+    let state = state.try_lock()?;
+    state
+        .audit
+        .query(&filter.unwrap_or_default())
+        .map_err(AppError::from)
+}
+
+#[tauri::command]
+fn export_audit_log(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<ExportedFile, AppError> {
+    let state = state.try_lock()?;
+    let path = export_path(&app_handle, "audit", "jsonl")?;
+    let count = state.audit.export(&path)?;
+    Ok(ExportedFile::new(path, count))
+}
+
+/// A file written by one of the export commands
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportedFile {
+    path: String,
+    count: usize,
+}
+
+impl ExportedFile {
+    fn new(path: std::path::PathBuf, count: usize) -> Self {
+        ExportedFile {
+            path: path.display().to_string(),
+            count,
+        }
     }
+}
+
+/// Exports only go to the exports directory of the app, the webview never picks the path
+fn export_path(
+    app_handle: &tauri::AppHandle,
+    prefix: &str,
+    extension: &str,
+) -> Result<std::path::PathBuf, AppError> {
+    let dir = app_handle
+        .path_resolver()
+        .app_data_dir()
+        .ok_or_else(|| AppError::Other("Failed to locate the app data directory".to_string()))?
+        .join("exports");
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| AppError::Other(format!("Failed to create {}: {}", dir.display(), e)))?;
+    Ok(dir.join(format!(
+        "{}-{}.{}",
+        prefix,
+        approval::unix_time(),
+        extension
+    )))
+}
+
+// ==================== HWI Commands ====================
 
-    Ok(hwi_devices)
+/// Lists the connected devices of every backend, see [`enumeration::enumerate`]
+#[tauri::command]
+async fn enumerate_devices(
+    app_handle: tauri::AppHandle,
+    _: State<'_, AppState>,
+    network: Option<bitcoin::Network>,
+) -> Result<Vec<Result<EnumeratedDevice, String>>, AppError> {
+    enumeration::enumerate(network, Some(app_handle)).await
 }
 
+/// Selects the device used by the HWI commands
+///
+/// A `passphrase` is required for devices listed with `needs_passphrase_sent`. It unlocks the
+/// passphrase wallet for the whole session, so the returned device carries the fingerprint of
+/// that wallet rather than the one reported by `enumerate_devices`.
 #[tauri::command]
-fn set_hwi_client(
+async fn set_hwi_client(
+    app_handle: tauri::AppHandle,
     state: State<'_, AppState>,
     fingerprint: Option<String>,
     device_type: HWIDeviceType,
     network: bitcoin::Network,
-) -> Result<(), String> {
-    let mut state = state.try_lock().map_err(|e| e.to_string())?;
+    passphrase: Option<String>,
+) -> Result<HWIDevice, AppError> {
+    let mut state = state.lock().await;
     let client = HWIAppClient::find_device(
-        None,
+        passphrase.as_deref(),
         Some(device_type.clone()),
         fingerprint.as_deref(),
         false,
         network,
     )
-    .map_err(|e| e.to_string())?;
+    .await
+    .map_err(AppError::from);
+    state.audit.record(
+        AuditEvent::command("SELECT_DEVICE", &client).with_detail(format!(
+            "{} {}{}",
+            device_type,
+            fingerprint.as_deref().unwrap_or("-"),
+            if passphrase.is_some() {
+                " (passphrase)"
+            } else {
+                ""
+            }
+        )),
+    );
+    let client = client?;
+    let device = client.device().cloned().ok_or(AppError::NoDevice)?;
+    let fingerprint = device.fingerprint.map(|f| f.to_string()).or(fingerprint);
+    let firmware = enumeration::firmware_version(
+        &device_type,
+        fingerprint.as_deref(),
+        network,
+        Some(app_handle),
+    )
+    .await;
     state.hwi = Some(HWIClientState {
         hwi: client,
         device_type,
         fingerprint,
         network,
+        pin: PinUnlock::new(Some(&device)),
+        firmware,
     });
-    Ok(())
+    hotplug::set_device_selected(true);
+    Ok(device)
 }
 
 #[tauri::command]
-async fn hwi_get_xpubs(state: State<'_, AppState>, account: usize) -> Result<Value, String> {
+async fn hwi_get_xpubs(state: State<'_, AppState>, account: usize) -> Result<Value, AppError> {
     let state = state.lock().await;
-    let hwi_state = state.hwi.as_ref().ok_or("HWI client not initialized")?;
-    let xpub_data = get_xpubs(hwi_state, account).map_err(|e| e.to_string())?;
+    let hwi_state = state.hwi.as_ref().ok_or(AppError::NoDevice)?;
+    let xpub_data = get_xpubs(hwi_state, account).await.map_err(AppError::from);
+    state
+        .audit
+        .record(AuditEvent::command("ADD_DEVICE", &xpub_data).with_device(Some(hwi_state)));
+    let mut xpub_data = xpub_data.map_err(|e| e.on_device(hwi_state))?;
+    xpub_data["capabilities"] = json!(capabilities::device_matrix(hwi_state));
 
     Ok(json!({
         "event": "CHANNEL_MESSAGE",
@@ -201,10 +354,15 @@ async fn hwi_get_xpubs(state: State<'_, AppState>, account: usize) -> Result<Val
 }
 
 #[tauri::command]
-async fn hwi_healthcheck(state: State<'_, AppState>, account: usize) -> Result<Value, String> {
+async fn hwi_healthcheck(state: State<'_, AppState>, account: usize) -> Result<Value, AppError> {
     let state = state.lock().await;
-    let hwi_state = state.hwi.as_ref().ok_or("HWI client not initialized")?;
-    let xpub_data = get_xpubs(hwi_state, account).map_err(|e| e.to_string())?;
+    let hwi_state = state.hwi.as_ref().ok_or(AppError::NoDevice)?;
+    let xpub_data = get_xpubs(hwi_state, account).await.map_err(AppError::from);
+    state
+        .audit
+        .record(AuditEvent::command("HEALTH_CHECK", &xpub_data).with_device(Some(hwi_state)));
+    let mut xpub_data = xpub_data.map_err(|e| e.on_device(hwi_state))?;
+    xpub_data["capabilities"] = json!(capabilities::device_matrix(hwi_state));
 
     Ok(json!({
         "event": "CHANNEL_MESSAGE",
@@ -217,277 +375,842 @@ async fn hwi_healthcheck(state: State<'_, AppState>, account: usize) -> Result<V
     }))
 }
 
+/// Checks the device can register and use the wallet `policy`
+fn require_policy(hwi_state: &HWIClientState, policy: &str) -> Result<(), AppError> {
+    capabilities::require(hwi_state, Feature::WalletRegistration)?;
+    for feature in capabilities::policy_features(policy) {
+        capabilities::require(hwi_state, feature)?;
+    }
+    Ok(())
+}
+
 #[tauri::command]
-async fn hwi_sign_tx(
+async fn hwi_sign_tx<R: Runtime>(
+    app_handle: tauri::AppHandle<R>,
     state: State<'_, AppState>,
     psbt: String,
     policy: Option<String>,
     wallet_name: Option<String>,
     hmac: Option<String>,
-) -> Result<Value, String> {
-    let state = state.lock().await;
-    let hwi_state = state.hwi.as_ref().ok_or("HWI client not initialized")?;
-
-    let mut res_hmac = hmac.clone();
-
-    let signed_psbt = if let Some(policy) = policy {
-        // Miniscript policy path
-        let mut device = get_miniscript_device_by_fingerprint(
-            hwi_state.network,
-            hwi_state.fingerprint.as_deref(),
-            &policy,
-            wallet_name.as_ref(),
-            hmac.as_ref(),
-        )
-        .await?;
+) -> Result<Value, AppError> {
+    let mut state = state.lock().await;
+    let hwi_state = state.hwi.as_ref().ok_or(AppError::NoDevice)?;
+    let txid = bitcoin::Psbt::from_str(&psbt)
+        .ok()
+        .map(|psbt| psbt.unsigned_tx.compute_txid().to_string());
+    let progress = ProgressReporter::new(
+        &app_handle,
+        &state.channel,
+        state.active_request.clone(),
+        "SIGN_TX",
+        hwi_state.network,
+    );
 
-        let mut psbt_obj = bitcoin::Psbt::from_str(&psbt).map_err(|e| e.to_string())?;
+    let result = async {
+        let mut res_hmac = hmac.clone();
 
-        if hwi_state.device_type != HWIDeviceType::Coldcard {
-            let is_registered = device
-                .is_wallet_registered(&wallet_name.clone().unwrap_or_default(), &policy)
-                .await
-                .map_err(|e| e.to_string())?;
+        let signed_psbt = if let Some(policy) = policy {
+            // Miniscript policy path
+            require_policy(hwi_state, &policy)?;
+            let mut device = get_miniscript_device_by_fingerprint(
+                hwi_state.network,
+                hwi_state.fingerprint.as_deref(),
+                &policy,
+                wallet_name.as_ref(),
+                hmac.as_ref(),
+            )
+            .await?;
+            progress.report(ProgressStage::DeviceFound);
 
-            if !is_registered {
-                res_hmac = Some(hex::encode(
-                    device
-                        .register_wallet(
-                            &wallet_name.clone().ok_or("Wallet name not provided")?,
+            let mut psbt_obj = bitcoin::Psbt::from_str(&psbt)
+                .map_err(|e| AppError::InvalidRequest(e.to_string()))?;
+            let unsigned_psbt = psbt_obj.clone();
+
+            if capabilities::device_supports(hwi_state, Feature::RegistrationCheck) {
+                let is_registered = device
+                    .is_wallet_registered(&wallet_name.clone().unwrap_or_default(), &policy)
+                    .await?;
+
+                if !is_registered {
+                    progress.report(ProgressStage::RegisteringWallet);
+                    res_hmac = Some(hex::encode(
+                        device
+                            .register_wallet(
+                                &wallet_name.clone().ok_or("Wallet name not provided")?,
+                                &policy,
+                            )
+                            .await?
+                            .unwrap_or_default(),
+                    ));
+
+                    if res_hmac.is_some() && !res_hmac.clone().unwrap().is_empty() {
+                        // Drop current device to free up the connection
+                        drop(device);
+
+                        // re-fetch the device with the new HMAC
+                        device = get_miniscript_device_by_fingerprint(
+                            hwi_state.network,
+                            hwi_state.fingerprint.as_deref(),
                             &policy,
+                            wallet_name.as_ref(),
+                            res_hmac.as_ref(),
                         )
-                        .await
-                        .map_err(|e| e.to_string())?
-                        .unwrap_or_default(),
-                ));
-
-                if res_hmac.is_some() && !res_hmac.clone().unwrap().is_empty() {
-                    // Drop current device to free up the connection
-                    drop(device);
-
-                    // re-fetch the device with the new HMAC
-                    device = get_miniscript_device_by_fingerprint(
-                        hwi_state.network,
-                        hwi_state.fingerprint.as_deref(),
-                        &policy,
-                        wallet_name.as_ref(),
-                        res_hmac.as_ref(),
-                    )
-                    .await?;
+                        .await?;
+                    }
                 }
             }
-        } else {
-            // Coldcard change verification gives an error when xpubs are included for Miniscript scheme
-            psbt_obj.xpub.clear();
-        }
+            if !capabilities::device_supports(hwi_state, Feature::PsbtGlobalXpubs) {
+                psbt_obj.xpub.clear();
+            }
 
-        device
-            .sign_tx(&mut psbt_obj)
-            .await
-            .map_err(|e| e.to_string())?;
-        psbt_obj.to_string()
-    } else {
-        general_purpose::STANDARD.encode(
-            hwi_state
-                .hwi
-                .sign_tx(&bitcoin::Psbt::from_str(&psbt).map_err(|e| e.to_string())?)
-                .map_err(|e| e.to_string())?
-                .serialize(),
-        )
-    };
+            progress.report(ProgressStage::WaitingForConfirmation);
+            device.sign_tx(&mut psbt_obj).await?;
+            progress.report(ProgressStage::SignedInputs {
+                signed: count_newly_signed_inputs(&unsigned_psbt, &psbt_obj),
+                total: psbt_obj.inputs.len(),
+            });
+            psbt_obj.to_string()
+        } else {
+            let psbt_obj = bitcoin::Psbt::from_str(&psbt)
+                .map_err(|e| AppError::InvalidRequest(e.to_string()))?;
+            if capabilities::spends_taproot(&psbt_obj) {
+                capabilities::require(hwi_state, Feature::Taproot)?;
+            }
+            progress.report(ProgressStage::DeviceFound);
+            progress.report(ProgressStage::WaitingForConfirmation);
+            let signed = hwi_state.hwi.sign_tx(&psbt_obj).await?;
+            progress.report(ProgressStage::SignedInputs {
+                signed: count_newly_signed_inputs(&psbt_obj, &signed),
+                total: signed.inputs.len(),
+            });
+            general_purpose::STANDARD.encode(signed.serialize())
+        };
 
-    Ok(json!({
-        "event": "CHANNEL_MESSAGE",
-        "data": {
-            "responseData": {
-                "action": "SIGN_TX",
-                "data": {
-                    "signedSerializedPSBT": signed_psbt,
-                    "hmac": res_hmac
+        Ok::<Value, AppError>(json!({
+            "event": "CHANNEL_MESSAGE",
+            "data": {
+                "responseData": {
+                    "action": "SIGN_TX",
+                    "data": {
+                        "signedSerializedPSBT": signed_psbt,
+                        "hmac": res_hmac
+                    }
                 }
             }
-        }
-    }))
+        }))
+    }
+    .await;
+
+    state.audit.record(
+        AuditEvent::command("SIGN_TX", &result)
+            .with_device(Some(hwi_state))
+            .with_txid(txid),
+    );
+    let result = result.map_err(|e| e.on_device(hwi_state));
+    // The channel request is answered with the result, later progress belongs to a new one
+    state.active_request = None;
+    result
 }
 
 #[tauri::command]
-async fn hwi_register_multisig(
+async fn hwi_register_multisig<R: Runtime>(
+    app_handle: tauri::AppHandle<R>,
     state: State<'_, AppState>,
     descriptor: Option<String>,
     policy: Option<String>,
     wallet_name: Option<String>,
     expected_address: String,
-) -> Result<Value, String> {
-    let state = state.lock().await;
-    let hwi_state = state.hwi.as_ref().ok_or("HWI client not initialized")?;
+) -> Result<Value, AppError> {
+    let mut state = state.lock().await;
+    let hwi_state = state.hwi.as_ref().ok_or(AppError::NoDevice)?;
+    let progress = ProgressReporter::new(
+        &app_handle,
+        &state.channel,
+        state.active_request.clone(),
+        "REGISTER_MULTISIG",
+        hwi_state.network,
+    );
 
-    let mut final_address = expected_address.clone();
+    let result = async {
+        let mut final_address = expected_address.clone();
 
-    let mut res_hmac: Option<String> = None;
+        let mut res_hmac: Option<String> = None;
 
-    if let Some(descriptor) = descriptor {
-        // Descriptor path
-        let address = hwi_state
-            .hwi
-            .display_address_with_desc(&descriptor)
-            .map_err(|e| e.to_string())?;
-        if address.address != Address::from_str(&expected_address).map_err(|e| e.to_string())? {
-            return Err(
-                "Address received from device does not match the expected address".to_string(),
-            );
-        }
-        final_address = address.address.assume_checked().to_string().clone();
-    } else if let Some(policy) = policy {
-        // Miniscript policy path
-        let device = get_miniscript_device_by_fingerprint(
-            hwi_state.network,
-            hwi_state.fingerprint.as_deref(),
-            &policy,
-            wallet_name.as_ref(),
-            None,
-        )
-        .await?;
+        if let Some(descriptor) = descriptor {
+            // Descriptor path
+            capabilities::require(hwi_state, Feature::DisplayAddress)?;
+            progress.report(ProgressStage::WaitingForConfirmation);
+            let address = hwi_state.hwi.display_address_with_desc(&descriptor).await?;
+            if address.address
+                != Address::from_str(&expected_address)
+                    .map_err(|e| AppError::InvalidRequest(e.to_string()))?
+            {
+                return Err(AppError::AddressMismatch);
+            }
+            final_address = address.address.assume_checked().to_string().clone();
+        } else if let Some(policy) = policy {
+            // Miniscript policy path
+            require_policy(hwi_state, &policy)?;
+            let device = get_miniscript_device_by_fingerprint(
+                hwi_state.network,
+                hwi_state.fingerprint.as_deref(),
+                &policy,
+                wallet_name.as_ref(),
+                None,
+            )
+            .await?;
+            progress.report(ProgressStage::DeviceFound);
+            progress.report(ProgressStage::RegisteringWallet);
 
-        res_hmac = Some(hex::encode(
-            device
-                .register_wallet(&wallet_name.ok_or("Wallet name not provided")?, &policy)
-                .await
-                .map_err(|e| e.to_string())?
-                .unwrap_or_default(),
-        ));
-    } else {
-        return Err("Either descriptor or policy must be provided".to_string());
-    }
+            res_hmac = Some(hex::encode(
+                device
+                    .register_wallet(&wallet_name.ok_or("Wallet name not provided")?, &policy)
+                    .await?
+                    .unwrap_or_default(),
+            ));
+        } else {
+            return Err(AppError::InvalidRequest(
+                "Either descriptor or policy must be provided".to_string(),
+            ));
+        }
 
-    Ok(json!({
-        "event": "CHANNEL_MESSAGE",
-        "data": {
-            "responseData": {
-                "action": "REGISTER_MULTISIG",
-                "data": {
-                    "address": final_address,
-                    "hmac": res_hmac
+        Ok::<Value, AppError>(json!({
+            "event": "CHANNEL_MESSAGE",
+            "data": {
+                "responseData": {
+                    "action": "REGISTER_MULTISIG",
+                    "data": {
+                        "address": final_address,
+                        "hmac": res_hmac
+                    }
                 }
             }
-        }
-    }))
+        }))
+    }
+    .await;
+
+    state
+        .audit
+        .record(AuditEvent::command("REGISTER_MULTISIG", &result).with_device(Some(hwi_state)));
+    let result = result.map_err(|e| e.on_device(hwi_state));
+    // The channel request is answered with the result, later progress belongs to a new one
+    state.active_request = None;
+    result
 }
 
 #[tauri::command]
-async fn hwi_verify_address(
+#[allow(clippy::too_many_arguments)]
+async fn hwi_verify_address<R: Runtime>(
+    app_handle: tauri::AppHandle<R>,
     state: State<'_, AppState>,
     descriptor: Option<String>,
     policy: Option<String>,
     index: Option<usize>,
     wallet_name: Option<String>,
     hmac: Option<String>,
+    path: Option<String>,
+    address_type: Option<HWIAddressType>,
     expected_address: String,
-) -> Result<Value, String> {
-    let state = state.lock().await;
-    let hwi_state = state.hwi.as_ref().ok_or("HWI client not initialized")?;
+) -> Result<Value, AppError> {
+    let mut state = state.lock().await;
+    let hwi_state = state.hwi.as_ref().ok_or(AppError::NoDevice)?;
+    let progress = ProgressReporter::new(
+        &app_handle,
+        &state.channel,
+        state.active_request.clone(),
+        "VERIFY_ADDRESS",
+        hwi_state.network,
+    );
 
-    let mut final_address = expected_address.clone();
+    let result = async {
+        let mut final_address = expected_address.clone();
 
-    let mut res_hmac = hmac.clone();
+        let mut res_hmac = hmac.clone();
 
-    if let Some(descriptor) = descriptor {
-        // Descriptor path
-        let address = hwi_state
-            .hwi
-            .display_address_with_desc(&descriptor)
-            .map_err(|e| e.to_string())?;
-        if address.address != Address::from_str(&expected_address).map_err(|e| e.to_string())? {
-            return Err(
-                "Address received from device does not match the expected address".to_string(),
-            );
-        }
-        final_address = address.address.assume_checked().to_string().clone();
-    } else if let Some(policy) = policy {
-        // Miniscript policy path
-        let mut device = get_miniscript_device_by_fingerprint(
-            hwi_state.network,
-            hwi_state.fingerprint.as_deref(),
-            &policy,
-            wallet_name.as_ref(),
-            hmac.as_ref(),
-        )
-        .await?;
-
-        if hwi_state.device_type != HWIDeviceType::Coldcard {
-            let is_registered = device
-                .is_wallet_registered(&wallet_name.clone().unwrap_or_default(), &policy)
-                .await
-                .map_err(|e| e.to_string())?;
-
-            if !is_registered {
-                res_hmac = Some(hex::encode(
-                    device
-                        .register_wallet(
-                            &wallet_name.clone().ok_or("Wallet name not provided")?,
+        if let Some(descriptor) = descriptor {
+            // Descriptor path
+            capabilities::require(hwi_state, Feature::DisplayAddress)?;
+            progress.report(ProgressStage::WaitingForConfirmation);
+            let address = hwi_state.hwi.display_address_with_desc(&descriptor).await?;
+            if address.address
+                != Address::from_str(&expected_address)
+                    .map_err(|e| AppError::InvalidRequest(e.to_string()))?
+            {
+                return Err(AppError::AddressMismatch);
+            }
+            final_address = address.address.assume_checked().to_string().clone();
+        } else if let Some(policy) = policy {
+            // Miniscript policy path
+            require_policy(hwi_state, &policy)?;
+            capabilities::require(hwi_state, Feature::DisplayAddress)?;
+            let mut device = get_miniscript_device_by_fingerprint(
+                hwi_state.network,
+                hwi_state.fingerprint.as_deref(),
+                &policy,
+                wallet_name.as_ref(),
+                hmac.as_ref(),
+            )
+            .await?;
+            progress.report(ProgressStage::DeviceFound);
+
+            if capabilities::device_supports(hwi_state, Feature::RegistrationCheck) {
+                let is_registered = device
+                    .is_wallet_registered(&wallet_name.clone().unwrap_or_default(), &policy)
+                    .await?;
+
+                if !is_registered {
+                    progress.report(ProgressStage::RegisteringWallet);
+                    res_hmac = Some(hex::encode(
+                        device
+                            .register_wallet(
+                                &wallet_name.clone().ok_or("Wallet name not provided")?,
+                                &policy,
+                            )
+                            .await?
+                            .unwrap_or_default(),
+                    ));
+
+                    if res_hmac.is_some() && !res_hmac.clone().unwrap().is_empty() {
+                        // Drop current device to free up the connection
+                        drop(device);
+
+                        // re-fetch the device with the new HMAC
+                        device = get_miniscript_device_by_fingerprint(
+                            hwi_state.network,
+                            hwi_state.fingerprint.as_deref(),
                             &policy,
+                            wallet_name.as_ref(),
+                            res_hmac.as_ref(),
                         )
-                        .await
-                        .map_err(|e| e.to_string())?
-                        .unwrap_or_default(),
-                ));
-
-                if res_hmac.is_some() && !res_hmac.clone().unwrap().is_empty() {
-                    // Drop current device to free up the connection
-                    drop(device);
-
-                    // re-fetch the device with the new HMAC
-                    device = get_miniscript_device_by_fingerprint(
-                        hwi_state.network,
-                        hwi_state.fingerprint.as_deref(),
-                        &policy,
-                        wallet_name.as_ref(),
-                        res_hmac.as_ref(),
-                    )
-                    .await?;
+                        .await?;
+                    }
                 }
             }
+
+            progress.report(ProgressStage::WaitingForConfirmation);
+            device
+                .display_address(&AddressScript::Miniscript {
+                    index: index
+                        .ok_or("Index must be provided")?
+                        .try_into()
+                        .map_err(|_| "Index conversion failed".to_string())?,
+                    change: false,
+                })
+                .await?;
+        } else if let Some(path) = path {
+            // Single-sig path
+            let path = bitcoin::bip32::DerivationPath::from_str(&path)
+                .map_err(|e| AppError::InvalidRequest(e.to_string()))?;
+            let expected_address = Address::from_str(&expected_address)
+                .map_err(|e| AppError::InvalidRequest(e.to_string()))?;
+            progress.report(ProgressStage::WaitingForConfirmation);
+            final_address =
+                verify_address_at_path(hwi_state, &path, address_type, &expected_address)
+                    .await?
+                    .to_string();
+        } else {
+            return Err(AppError::InvalidRequest(
+                "Either descriptor, policy or path must be provided".to_string(),
+            ));
         }
 
-        device
-            .display_address(&AddressScript::Miniscript {
-                index: index
-                    .ok_or("Index must be provided")?
-                    .try_into()
-                    .map_err(|_| "Index conversion failed".to_string())?,
-                change: false,
-            })
-            .await
-            .map_err(|e| e.to_string())?;
-    } else {
-        return Err("Either descriptor or policy must be provided".to_string());
+        Ok::<Value, AppError>(json!({
+            "event": "CHANNEL_MESSAGE",
+            "data": {
+                "responseData": {
+                    "action": "VERIFY_ADDRESS",
+                    "data": {
+                        "address": final_address,
+                        "hmac": res_hmac
+                    }
+                }
+            }
+        }))
     }
+    .await;
 
-    Ok(json!({
+    state
+        .audit
+        .record(AuditEvent::command("VERIFY_ADDRESS", &result).with_device(Some(hwi_state)));
+    let result = result.map_err(|e| e.on_device(hwi_state));
+    // The channel request is answered with the result, later progress belongs to a new one
+    state.active_request = None;
+    result
+}
+
+/// Devices whose BIP322 signatures are made through async-hwi, like their miniscript PSBTs
+///
+/// async-hwi has no message signing, so legacy signatures of every device go through HWI.
+fn signs_bip322_with_async_hwi(device_type: &HWIDeviceType) -> bool {
+    matches!(
+        device_type,
+        HWIDeviceType::Ledger | HWIDeviceType::Jade | HWIDeviceType::BitBox02
+    )
+}
+
+/// Signs a message with the key at `path`, proving ownership of its single-key address
+#[tauri::command]
+async fn hwi_sign_message<R: Runtime>(
+    app_handle: tauri::AppHandle<R>,
+    state: State<'_, AppState>,
+    message: String,
+    path: String,
+    format: MessageFormat,
+) -> Result<Value, AppError> {
+    let mut state = state.lock().await;
+    let Some(hwi_state) = state.hwi.as_ref() else {
+        state.active_request = None;
+        return Err(AppError::NoDevice);
+    };
+    let progress = ProgressReporter::new(
+        &app_handle,
+        &state.channel,
+        state.active_request.clone(),
+        "SIGN_MESSAGE",
+        hwi_state.network,
+    );
+
+    let result = async {
+        let path = bitcoin::bip32::DerivationPath::from_str(&path)
+            .map_err(|e| AppError::InvalidRequest(e.to_string()))?;
+        let mut device = match format {
+            MessageFormat::Bip322Simple if signs_bip322_with_async_hwi(&hwi_state.device_type) => {
+                Some(
+                    get_device_by_fingerprint(hwi_state.network, hwi_state.fingerprint.as_deref())
+                        .await?,
+                )
+            }
+            _ => None,
+        };
+        if device.is_some() {
+            progress.report(ProgressStage::DeviceFound);
+        }
+        let pubkey = match &device {
+            Some(device) => device.get_extended_pubkey(&path).await?.public_key,
+            None => hwi_state.hwi.get_xpub(&path, false).await?.public_key,
+        };
+        let address = message::address_at(&path, pubkey, hwi_state.network)?;
+        if !message::supports(&address, format) {
+            return Err(MessageError::UnsupportedAddress(format).into());
+        }
+        match format {
+            MessageFormat::Legacy => capabilities::require(hwi_state, Feature::MessageSigning)?,
+            MessageFormat::Bip322Simple => {
+                if address.address_type() == Some(bitcoin::AddressType::P2tr) {
+                    capabilities::require(hwi_state, Feature::Taproot)?;
+                }
+            }
+        }
+
+        progress.report(ProgressStage::WaitingForConfirmation);
+        let signature = match format {
+            MessageFormat::Legacy => general_purpose::STANDARD
+                .encode(&hwi_state.hwi.sign_message(&message, &path).await?.signature),
+            MessageFormat::Bip322Simple => {
+                let fingerprint = hwi_state
+                    .fingerprint
+                    .as_deref()
+                    .ok_or("Device fingerprint is missing")?;
+                let fingerprint = bitcoin::bip32::Fingerprint::from_str(fingerprint)
+                    .map_err(|e| AppError::Other(e.to_string()))?;
+                let mut psbt =
+                    message::bip322_psbt(&address, &message, pubkey, (fingerprint, path))?;
+                match device.as_mut() {
+                    Some(device) => device.sign_tx(&mut psbt).await?,
+                    None => psbt = hwi_state.hwi.sign_tx(&psbt).await?.psbt,
+                }
+                message::bip322_signature(&psbt)?
+            }
+        };
+
+        // Never hand out a signature which doesn't prove what was asked for
+        if !message::verify(&address, &message, &signature)?.valid {
+            return Err(MessageError::WrongKey.into());
+        }
+
+        Ok::<Value, AppError>(json!({
+            "event": "CHANNEL_MESSAGE",
+            "data": {
+                "responseData": {
+                    "action": "SIGN_MESSAGE",
+                    "data": SignedMessage {
+                        address: address.to_string(),
+                        signature,
+                        format,
+                    }
+                }
+            }
+        }))
+    }
+    .await;
+
+    state.audit.record(
+        AuditEvent::command("SIGN_MESSAGE", &result)
+            .with_device(Some(hwi_state))
+            .with_detail(format.to_string()),
+    );
+    let result = result.map_err(|e| e.on_device(hwi_state));
+    // The channel request is answered with the result, later progress belongs to a new one
+    state.active_request = None;
+    result
+}
+
+/// Checks a legacy or BIP322 simple signature, no device is needed
+#[tauri::command]
+fn verify_message(
+    address: String,
+    message: String,
+    signature: String,
+) -> Result<MessageVerification, AppError> {
+    // Only the script of the address is compared, so its network doesn't matter
+    let address = Address::from_str(&address)
+        .map_err(|e| AppError::InvalidRequest(e.to_string()))?
+        .assume_checked();
+    Ok(message::verify(&address, &message, &signature)?)
+}
+
+/// Exports watch-only descriptors of an account into the exports directory
+#[tauri::command]
+async fn export_descriptors(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    account: u32,
+    address_type: HWIAddressType,
+    format: ExportFormat,
+) -> Result<ExportedFile, AppError> {
+    let state = state.lock().await;
+    let hwi_state = state.hwi.as_ref().ok_or(AppError::NoDevice)?;
+
+    let result = async {
+        let descriptors = match format {
+            // getkeypool is how HWI exports keys for Bitcoin Core
+            ExportFormat::Core => {
+                let mut keypool = Vec::new();
+                for internal in [false, true] {
+                    keypool.extend(
+                        hwi_state
+                            .hwi
+                            .get_keypool(
+                                true,
+                                internal,
+                                address_type.clone(),
+                                false,
+                                Some(account),
+                                None,
+                                0,
+                                descriptors::CORE_RANGE_END,
+                            )
+                            .await?,
+                    );
+                }
+                AccountDescriptors::from_keypool(keypool, address_type)?
+            }
+            _ => AccountDescriptors::from_hwi(
+                hwi_state.hwi.get_descriptors(Some(account)).await?,
+                address_type,
+            )?,
+        };
+        let contents = descriptors.export(format, hwi_state.network)?;
+        let path = export_path(&app_handle, "descriptors", format.extension())?;
+        descriptors::write(&contents, &path)?;
+        // Receive and change
+        Ok::<ExportedFile, AppError>(ExportedFile::new(path, 2))
+    }
+    .await;
+
+    state.audit.record(
+        AuditEvent::command("EXPORT_DESCRIPTORS", &result)
+            .with_device(Some(hwi_state))
+            .with_detail(format!("{:?} account {}", format, account)),
+    );
+    result.map_err(|e| e.on_device(hwi_state))
+}
+
+fn emit_pin_state(app_handle: &tauri::AppHandle, status: &PinStatus) {
+    if let Err(e) = app_handle.emit_all("pin-state", status) {
+        log::error!("Failed to emit pin-state event: {:?}", e);
+    }
+}
+
+#[tauri::command]
+async fn hwi_pin_status(state: State<'_, AppState>) -> Result<PinStatus, AppError> {
+    let state = state.lock().await;
+    let hwi_state = state.hwi.as_ref().ok_or(AppError::NoDevice)?;
+    Ok(hwi_state.pin.status())
+}
+
+#[tauri::command]
+async fn hwi_prompt_pin(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<PinStatus, AppError> {
+    let mut state = state.lock().await;
+    let hwi_state = state.hwi.as_mut().ok_or(AppError::NoDevice)?;
+    let result = hwi_state.pin.prompt(&hwi_state.hwi).await;
+    emit_pin_state(&app_handle, &hwi_state.pin.status());
+    result.map_err(|e| e.on_device(hwi_state))
+}
+
+#[tauri::command]
+async fn hwi_send_pin(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    pin: String,
+) -> Result<PinStatus, AppError> {
+    let mut state = state.lock().await;
+    let hwi_state = state.hwi.as_mut().ok_or(AppError::NoDevice)?;
+    let chain = HWIChain::from(hwi_state.network);
+    let result = match hwi_state
+        .pin
+        .send(&hwi_state.hwi, &pin, chain.clone())
+        .await
+    {
+        // Talk to the unlocked device by its fingerprint from now on
+        Ok((status, Some(device))) => HWIAppClient::get_client(&device, false, chain)
+            .await
+            .map(|client| {
+                hwi_state.hwi = client;
+                hwi_state.fingerprint = device.fingerprint.map(|f| f.to_string());
+                status
+            })
+            .map_err(AppError::from),
+        Ok((status, None)) => Ok(status),
+        Err(e) => Err(e),
+    };
+    let status = hwi_state.pin.status();
+    emit_pin_state(&app_handle, &status);
+    let result = result.map_err(|e| e.on_device(hwi_state));
+    state.audit.record(
+        AuditEvent::command("SEND_PIN", &result)
+            .with_device(state.hwi.as_ref())
+            .with_detail(format!("{:?}", status.state)),
+    );
+    result
+}
+
+/// Identifies the selected device in confirmation tokens
+fn confirmation_device(hwi_state: &HWIClientState) -> String {
+    let id = match (&hwi_state.fingerprint, hwi_state.hwi.device()) {
+        (Some(fingerprint), _) => fingerprint.clone(),
+        (None, Some(device)) => device.path.clone(),
+        (None, None) => String::new(),
+    };
+    format!("{} {}", hwi_state.device_type, id)
+}
+
+fn lifecycle_response(operation: LifecycleOperation) -> Value {
+    json!({
         "event": "CHANNEL_MESSAGE",
         "data": {
             "responseData": {
-                "action": "VERIFY_ADDRESS",
+                "action": operation.action(),
                 "data": {
-                    "address": final_address,
-                    "hmac": res_hmac
+                    "success": true
                 }
             }
         }
-    }))
+    })
+}
+
+/// The fingerprint of the selected device, or its model while the fingerprint is unknown
+fn confirmation_challenge(
+    hwi_state: &HWIClientState,
+    operation: LifecycleOperation,
+) -> Result<ConfirmationChallenge, AppError> {
+    if !operation.is_destructive() {
+        return Err(AppError::InvalidRequest(format!(
+            "{} does not need a confirmation",
+            operation
+        )));
+    }
+    let phrase = match (&hwi_state.fingerprint, hwi_state.hwi.device()) {
+        (Some(fingerprint), _) => fingerprint.clone(),
+        (None, Some(device)) if !device.model.is_empty() => device.model.clone(),
+        _ => hwi_state.device_type.to_string(),
+    };
+    Ok(ConfirmationChallenge { operation, phrase })
+}
+
+/// What the user has to type to confirm a destructive operation on the selected device
+#[tauri::command]
+async fn hwi_confirmation_challenge(
+    state: State<'_, AppState>,
+    operation: LifecycleOperation,
+) -> Result<ConfirmationChallenge, AppError> {
+    let state = state.lock().await;
+    let hwi_state = state.hwi.as_ref().ok_or(AppError::NoDevice)?;
+    confirmation_challenge(hwi_state, operation)
+}
+
+/// Issues a single-use token for a destructive operation on the selected device, once the user
+/// typed the phrase of `hwi_confirmation_challenge`
+#[tauri::command]
+async fn hwi_request_confirmation(
+    state: State<'_, AppState>,
+    operation: LifecycleOperation,
+    typed: String,
+) -> Result<ConfirmationToken, AppError> {
+    let mut state = state.lock().await;
+    let hwi_state = state.hwi.as_ref().ok_or(AppError::NoDevice)?;
+    confirmation_challenge(hwi_state, operation)?.check(&typed)?;
+    let device = confirmation_device(hwi_state);
+    let token = state.confirmations.issue(operation, device);
+    state.audit.record(
+        AuditEvent::new(AuditSource::Command, "REQUEST_CONFIRMATION", "success")
+            .with_device(state.hwi.as_ref())
+            .with_detail(operation.to_string()),
+    );
+    Ok(token)
 }
 
 #[tauri::command]
-async fn hwi_prompt_pin(state: State<'_, AppState>) -> Result<(), String> {
+async fn hwi_setup_device(
+    state: State<'_, AppState>,
+    label: Option<String>,
+    passphrase: Option<String>,
+) -> Result<Value, AppError> {
+    let operation = LifecycleOperation::SetupDevice;
     let state = state.lock().await;
-    let hwi_state = state.hwi.as_ref().ok_or("HWI client not initialized")?;
-    hwi_state.hwi.prompt_pin().map_err(|e| e.to_string())
+    let hwi_state = state.hwi.as_ref().ok_or(AppError::NoDevice)?;
+    let result = hwi_state
+        .hwi
+        .setup_device(label.as_deref(), passphrase.as_deref())
+        .await
+        .map(|_| lifecycle_response(operation))
+        .map_err(AppError::from);
+    state
+        .audit
+        .record(AuditEvent::command(operation.action(), &result).with_device(Some(hwi_state)));
+    result.map_err(|e| e.on_device(hwi_state))
 }
 
 #[tauri::command]
-async fn hwi_send_pin(state: State<'_, AppState>, pin: String) -> Result<(), String> {
-    let state: tokio::sync::MutexGuard<'_, AppStateInner> = state.lock().await;
-    let hwi_state = state.hwi.as_ref().ok_or("HWI client not initialized")?;
-    hwi_state.hwi.send_pin(&pin).map_err(|e| e.to_string())
+async fn hwi_restore_device(
+    state: State<'_, AppState>,
+    label: Option<String>,
+    word_count: Option<u8>,
+) -> Result<Value, AppError> {
+    let operation = LifecycleOperation::RestoreDevice;
+    let state = state.lock().await;
+    let hwi_state = state.hwi.as_ref().ok_or(AppError::NoDevice)?;
+    let result = async {
+        let word_count = word_count.map(lifecycle::word_count).transpose()?;
+        hwi_state
+            .hwi
+            .restore_device(label.as_deref(), word_count)
+            .await?;
+        Ok::<Value, AppError>(lifecycle_response(operation))
+    }
+    .await;
+    state
+        .audit
+        .record(AuditEvent::command(operation.action(), &result).with_device(Some(hwi_state)));
+    result.map_err(|e| e.on_device(hwi_state))
+}
+
+#[tauri::command]
+async fn hwi_backup_device(
+    state: State<'_, AppState>,
+    label: Option<String>,
+    backup_passphrase: Option<String>,
+) -> Result<Value, AppError> {
+    let operation = LifecycleOperation::BackupDevice;
+    let state = state.lock().await;
+    let hwi_state = state.hwi.as_ref().ok_or(AppError::NoDevice)?;
+    let result = hwi_state
+        .hwi
+        .backup_device(label.as_deref(), backup_passphrase.as_deref())
+        .await
+        .map(|_| lifecycle_response(operation))
+        .map_err(AppError::from);
+    state
+        .audit
+        .record(AuditEvent::command(operation.action(), &result).with_device(Some(hwi_state)));
+    result.map_err(|e| e.on_device(hwi_state))
+}
+
+#[tauri::command]
+async fn hwi_toggle_passphrase(state: State<'_, AppState>) -> Result<Value, AppError> {
+    let operation = LifecycleOperation::TogglePassphrase;
+    let state = state.lock().await;
+    let hwi_state = state.hwi.as_ref().ok_or(AppError::NoDevice)?;
+    let result = hwi_state
+        .hwi
+        .toggle_passphrase()
+        .await
+        .map(|_| lifecycle_response(operation))
+        .map_err(AppError::from);
+    state
+        .audit
+        .record(AuditEvent::command(operation.action(), &result).with_device(Some(hwi_state)));
+    result.map_err(|e| e.on_device(hwi_state))
+}
+
+/// Wipes the selected device, with a token from `hwi_request_confirmation`
+///
+/// The device has to be selected again afterwards, as its keys are gone.
+#[tauri::command]
+async fn hwi_wipe_device(
+    state: State<'_, AppState>,
+    confirmation: String,
+) -> Result<Value, AppError> {
+    let operation = LifecycleOperation::WipeDevice;
+    let mut state = state.lock().await;
+    let inner = &mut *state;
+    let hwi_state = inner.hwi.as_ref().ok_or(AppError::NoDevice)?;
+    let result = async {
+        inner
+            .confirmations
+            .consume(&confirmation, operation, &confirmation_device(hwi_state))?;
+        hwi_state.hwi.wipe_device().await?;
+        Ok::<Value, AppError>(lifecycle_response(operation))
+    }
+    .await;
+    inner
+        .audit
+        .record(AuditEvent::command(operation.action(), &result).with_device(Some(hwi_state)));
+    let result = result.map_err(|e| e.on_device(hwi_state));
+    if result.is_ok() {
+        inner.hwi = None;
+        hotplug::set_device_selected(false);
+    }
+    result
+}
+
+#[tauri::command]
+fn hwi_cancel() -> usize {
+    sidecar::cancel_all()
+}
+
+#[tauri::command]
+async fn hwi_set_log_level(level: LogLevel) -> Result<(), AppError> {
+    HWIAppClient::set_log_level(level)
+        .await
+        .map_err(AppError::from)
+}
+
+/// Writes the last `count` HWI transcripts (all kept ones by default) for a bug report
+#[tauri::command]
+fn export_diagnostics(
+    app_handle: tauri::AppHandle,
+    count: Option<usize>,
+) -> Result<ExportedFile, AppError> {
+    let bundle = diagnostics::bundle(count.unwrap_or(usize::MAX), sidecar::verification());
+    let path = export_path(&app_handle, "diagnostics", "json")?;
+    let count = diagnostics::export(&bundle, &path)
+        .map_err(|e| AppError::Other(format!("Failed to export diagnostics: {}", e)))?;
+    Ok(ExportedFile::new(path, count))
+}
+
+#[tauri::command]
+fn get_emulators() -> Vec<EmulatorSetting> {
+    emulators::settings()
+}
+
+#[tauri::command]
+fn set_emulators(enabled: Vec<Emulator>) -> Vec<EmulatorSetting> {
+    emulators::set_enabled(enabled);
+    emulators::settings()
 }
 
 #[cfg(target_os = "linux")]
@@ -496,6 +1219,69 @@ fn check_udev_rules() -> Result<bool, String> {
     Ok(udev_file.exists())
 }
 
+/// Commands lock the state while they talk to a device, so the watcher holds the lock for the
+/// whole enumeration and skips it while a command runs
+impl<R: Runtime> hotplug::DeviceAccess for tauri::AppHandle<R> {
+    async fn exclusive<F: std::future::Future>(&self, operation: F) -> Option<F::Output> {
+        let state = self.state::<AppState>();
+        let _state = state.try_lock().ok()?;
+        Some(operation.await)
+    }
+}
+
+/// Polls for devices while the connect screen waits for one, udev reports them anyway on Linux
+#[tauri::command]
+fn set_device_polling(enabled: bool) {
+    hotplug::set_polling(enabled);
+}
+
+/// Emits `device-connected` / `device-disconnected` and forgets the selected device once unplugged
+fn spawn_device_watcher(app_handle: tauri::AppHandle) {
+    let access = app_handle.clone();
+    let handle = move |event: DeviceEvent| {
+        if let Err(e) = app_handle.emit_all(event.name(), event.device()) {
+            log::error!("Failed to emit {}: {}", event.name(), e);
+        }
+        if let DeviceEvent::Disconnected(device) = event {
+            pin::forget_failed_attempts(&device.path);
+            let app_handle = app_handle.clone();
+            tauri::async_runtime::spawn(async move {
+                let state = app_handle.state::<AppState>();
+                let mut state = state.lock().await;
+                let unplugged =
+                    state
+                        .hwi
+                        .as_ref()
+                        .is_some_and(|hwi_state| match hwi_state.hwi.device() {
+                            Some(selected) => hotplug::same_device(selected, &device),
+                            None => {
+                                hwi_state.device_type == device.device_type
+                                    && device.fingerprint.is_some()
+                                    && hwi_state.fingerprint
+                                        == device.fingerprint.map(|f| f.to_string())
+                            }
+                        });
+                if unplugged {
+                    state.hwi = None;
+                    hotplug::set_device_selected(false);
+                }
+            });
+        }
+    };
+
+    tauri::async_runtime::spawn(async move {
+        // Emulators don't show up in udev, so they are polled for
+        #[cfg(target_os = "linux")]
+        if emulators::enabled().is_empty() {
+            match hotplug::UdevSource::new(access.clone()) {
+                Ok(source) => return hotplug::watch(source, handle).await,
+                Err(e) => log::warn!("Falling back to polling for devices: {}", e),
+            }
+        }
+        hotplug::watch(hotplug::PollingSource::new(access), handle).await
+    });
+}
+
 #[tauri::command]
 fn get_environment() -> String {
     ENV.into()
@@ -509,17 +1295,33 @@ fn main() {
             {
                 if !check_udev_rules().unwrap_or(false) {
                     warn!("udev rules are not installed. Trying to install them.");
-                    let result = HWIAppClient::install_udev_rules(None, None);
-                    if result.is_err() {
-                        warn!("Failed to install udev rules: {}", result.err().unwrap());
-                    }
+                    tauri::async_runtime::spawn(async {
+                        let result = HWIAppClient::install_udev_rules(None, None).await;
+                        if result.is_err() {
+                            warn!("Failed to install udev rules: {}", result.err().unwrap());
+                        }
+                    });
                 }
             }
+            // Verify the sidecar early so that a failure shows up in the logs before first use
+            tauri::async_runtime::spawn(async {
+                let _ = sidecar::verify().await;
+                // e.g. RUST_LOG=hwi=debug
+                if log::log_enabled!(target: "hwi", log::Level::Debug) {
+                    let _ = HWIAppClient::set_log_level(LogLevel::DEBUG).await;
+                }
+            });
+            let data_dir = app.path_resolver().app_data_dir();
             let app_state = AppStateInner {
                 channel: Channel::new_empty(),
                 hwi: None,
+                approvals: ApprovalGate::new(data_dir.clone()),
+                audit: AuditLog::new(data_dir),
+                confirmations: Confirmations::default(),
+                active_request: None,
             };
             app.manage(Mutex::new(app_state));
+            spawn_device_watcher(app.handle());
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -527,41 +1329,211 @@ fn main() {
             disconnect_channel,
             get_channel_secret,
             generate_encryption_key,
-            hwi_enumerate,
+            enumerate_devices,
             set_hwi_client,
             hwi_get_xpubs,
             hwi_healthcheck,
             hwi_sign_tx,
             hwi_register_multisig,
             hwi_verify_address,
+            hwi_sign_message,
+            verify_message,
             emit_to_channel,
+            get_pending_approvals,
+            approve_request,
+            reject_request,
+            get_approval_rules,
+            remove_approval_rule,
+            get_audit_log,
+            export_audit_log,
             hwi_send_pin,
             hwi_prompt_pin,
-            async_hwi_enumerate,
+            hwi_pin_status,
+            hwi_confirmation_challenge,
+            hwi_request_confirmation,
+            hwi_setup_device,
+            hwi_restore_device,
+            hwi_backup_device,
+            hwi_toggle_passphrase,
+            hwi_wipe_device,
+            hwi_cancel,
+            hwi_set_log_level,
+            export_diagnostics,
+            export_descriptors,
+            get_emulators,
+            set_emulators,
+            set_device_polling,
             get_environment,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
 
-pub struct HWIBinaryExecutorImpl;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hwi_fixtures::fixture_state;
+    use crate::lifecycle::LifecycleError;
+    use tauri::async_runtime::block_on;
+    use tauri::test::{mock_app, MockRuntime};
+    use tauri::App;
 
-impl HWIBinaryExecutor for HWIBinaryExecutorImpl {
-    fn execute_command(args: Vec<String>) -> Result<String, Error> {
-        let mut args = args;
+    const UNSIGNED_PSBT: &str = "cHNidP8BAFICAAAAAQEAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAD9////AZBfAQAAAAAAFgAU8ozs5duvGfsgWWby4j22jvYSbQMAAAAAAAAA";
 
-        args.insert(0, "--emulators".to_string());
+    fn app(hwi: Option<HWIClientState>) -> App<MockRuntime> {
+        let app = mock_app();
+        app.manage(Mutex::new(AppStateInner {
+            channel: Channel::new_empty(),
+            hwi,
+            approvals: ApprovalGate::new(None),
+            audit: AuditLog::new(None),
+            confirmations: Confirmations::default(),
+            active_request: None,
+        }));
+        app
+    }
+
+    #[test]
+    fn needs_a_selected_device() {
+        let app = app(None);
+        assert!(matches!(
+            block_on(hwi_get_xpubs(app.state(), 0)),
+            Err(AppError::NoDevice)
+        ));
+        assert!(matches!(
+            block_on(hwi_pin_status(app.state())),
+            Err(AppError::NoDevice)
+        ));
+    }
 
-        let output = Command::new_sidecar("hwi")
-            .map_err(|e| Error::Hwi(format!("Failed to create sidecar command: {}", e), None))?
-            .args(args)
-            .output()
-            .map_err(|e| Error::Hwi(format!("Failed to execute command: {}", e), None))?;
+    #[test]
+    fn shares_xpubs_with_capabilities() {
+        let app = app(Some(fixture_state()));
 
-        if output.status.success() {
-            Ok(output.stdout)
-        } else {
-            Err(Error::Hwi(output.stderr, None))
-        }
+        let response = block_on(hwi_get_xpubs(app.state(), 0)).unwrap();
+        let response = &response["data"]["responseData"];
+        assert_eq!(response["action"], "ADD_DEVICE");
+        assert_eq!(response["data"]["mfp"], "E2867BB6");
+        assert_eq!(response["data"]["capabilities"]["TAPROOT"], true);
+        assert_eq!(response["data"]["capabilities"]["MINISCRIPT"], false);
+    }
+
+    #[test]
+    fn signs_psbt_and_ends_the_request() {
+        let app = app(Some(fixture_state()));
+        block_on(app.state::<AppState>().lock()).active_request = Some("ab12".to_string());
+
+        let response = block_on(hwi_sign_tx(
+            app.handle(),
+            app.state(),
+            UNSIGNED_PSBT.to_string(),
+            None,
+            None,
+            None,
+        ))
+        .unwrap();
+        let signed = response["data"]["responseData"]["data"]["signedSerializedPSBT"]
+            .as_str()
+            .unwrap();
+        let signed = bitcoin::Psbt::from_str(signed).unwrap();
+        assert_eq!(signed.inputs[0].partial_sigs.len(), 1);
+        assert!(block_on(app.state::<AppState>().lock())
+            .active_request
+            .is_none());
+
+        assert!(matches!(
+            block_on(hwi_sign_tx(
+                app.handle(),
+                app.state(),
+                "not a psbt".to_string(),
+                None,
+                None,
+                None,
+            )),
+            Err(AppError::InvalidRequest(_))
+        ));
+    }
+
+    #[test]
+    fn ends_the_request_when_signing_a_message_fails() {
+        let sign = |app: &App<MockRuntime>| {
+            block_on(app.state::<AppState>().lock()).active_request = Some("ab12".to_string());
+            let result = block_on(hwi_sign_message(
+                app.handle(),
+                app.state(),
+                "Hello".to_string(),
+                "m/84'/1'/0'/0/0".to_string(),
+                MessageFormat::Legacy,
+            ));
+            assert!(block_on(app.state::<AppState>().lock())
+                .active_request
+                .is_none());
+            result
+        };
+
+        assert!(matches!(sign(&app(None)), Err(AppError::NoDevice)));
+        // The fixtures have no key at this path
+        assert!(sign(&app(Some(fixture_state()))).is_err());
+    }
+
+    #[test]
+    fn verifies_address_by_path() {
+        let app = app(Some(fixture_state()));
+        let verify = |expected: &str| {
+            block_on(hwi_verify_address(
+                app.handle(),
+                app.state(),
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some("m/84'/1'/0'/0/0".to_string()),
+                None,
+                expected.to_string(),
+            ))
+        };
+
+        let response = verify("tb1q72xweewm4uvlkgzevmewy0dk3mmpymgr3n58qx").unwrap();
+        assert_eq!(response["data"]["responseData"]["action"], "VERIFY_ADDRESS");
+        assert!(matches!(
+            verify("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx"),
+            Err(AppError::AddressMismatch)
+        ));
+    }
+
+    #[test]
+    fn refuses_to_wipe_without_confirmation() {
+        let app = app(Some(fixture_state()));
+        assert!(block_on(hwi_wipe_device(app.state(), "forged".to_string())).is_err());
+        assert!(block_on(app.state::<AppState>().lock()).hwi.is_some());
+    }
+
+    #[test]
+    fn confirms_with_the_typed_fingerprint() {
+        let app = app(Some(fixture_state()));
+        let operation = LifecycleOperation::WipeDevice;
+
+        let challenge = block_on(hwi_confirmation_challenge(app.state(), operation)).unwrap();
+        assert_eq!(challenge.phrase, hwi_fixtures::FIXTURE_FINGERPRINT);
+        assert!(matches!(
+            block_on(hwi_request_confirmation(
+                app.state(),
+                operation,
+                "trezor".to_string()
+            )),
+            Err(AppError::Lifecycle(LifecycleError::PhraseMismatch(_)))
+        ));
+        assert!(block_on(hwi_request_confirmation(
+            app.state(),
+            operation,
+            challenge.phrase
+        ))
+        .is_ok());
+        assert!(block_on(hwi_confirmation_challenge(
+            app.state(),
+            LifecycleOperation::BackupDevice
+        ))
+        .is_err());
     }
 }
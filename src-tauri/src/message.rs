@@ -0,0 +1,396 @@
+use bitcoin::absolute::LockTime;
+use bitcoin::base64::{engine::general_purpose, Engine as _};
+use bitcoin::bip32::{DerivationPath, Fingerprint};
+use bitcoin::consensus::{deserialize, serialize};
+use bitcoin::ecdsa;
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::opcodes::all::OP_RETURN;
+use bitcoin::script::Builder;
+use bitcoin::secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+use bitcoin::secp256k1::{self, Message, Secp256k1, XOnlyPublicKey};
+use bitcoin::sighash::{EcdsaSighashType, Prevouts, SighashCache, TapSighashType};
+use bitcoin::sign_message::{signed_msg_hash, MessageSignature};
+use bitcoin::transaction::Version;
+use bitcoin::{
+    taproot, Address, AddressType, Amount, CompressedPublicKey, Network, OutPoint, Psbt, PublicKey,
+    Script, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::device::address_type_for_path;
+use crate::hwi::types::HWIAddressType;
+
+/// Tag of the BIP322 message hash
+const BIP322_TAG: &[u8] = b"BIP0322-signed-message";
+
+#[derive(Error, Debug)]
+pub enum MessageError {
+    #[error("{0} signatures are not supported for this address type")]
+    UnsupportedAddress(MessageFormat),
+    #[error("No single-key address type for path m/{0}")]
+    UnsupportedPath(DerivationPath),
+    #[error("Invalid signature: {0}")]
+    InvalidSignature(String),
+    #[error("The device did not sign the message")]
+    NotSigned,
+    #[error("The device signed with a different key than the one at the requested path")]
+    WrongKey,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MessageFormat {
+    /// "Bitcoin Signed Message" signatures, with BIP137 headers for SegWit addresses
+    Legacy,
+    /// BIP322 simple signatures, the witness of the virtual `to_sign` transaction
+    Bip322Simple,
+}
+
+impl std::fmt::Display for MessageFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MessageFormat::Legacy => f.write_str("Legacy"),
+            MessageFormat::Bip322Simple => f.write_str("BIP322"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedMessage {
+    pub address: String,
+    pub signature: String,
+    pub format: MessageFormat,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageVerification {
+    pub valid: bool,
+    pub format: MessageFormat,
+}
+
+/// Single-key address of `pubkey`, with the script type given by the purpose of `path`, see
+/// [`address_type_for_path`]
+pub fn address_at(
+    path: &DerivationPath,
+    pubkey: secp256k1::PublicKey,
+    network: Network,
+) -> Result<Address, MessageError> {
+    let compressed = CompressedPublicKey(pubkey);
+    match address_type_for_path(path) {
+        Some(HWIAddressType::Legacy) => Ok(Address::p2pkh(compressed, network)),
+        Some(HWIAddressType::Sh_Wit) => Ok(Address::p2shwpkh(&compressed, network)),
+        Some(HWIAddressType::Wit) => Ok(Address::p2wpkh(&compressed, network)),
+        Some(HWIAddressType::Tap) => Ok(Address::p2tr(
+            &Secp256k1::verification_only(),
+            XOnlyPublicKey::from(pubkey),
+            None,
+            network,
+        )),
+        None => Err(MessageError::UnsupportedPath(path.clone())),
+    }
+}
+
+/// Legacy signatures predate Taproot, BIP322 simple signatures need a SegWit address
+pub fn supports(address: &Address, format: MessageFormat) -> bool {
+    match format {
+        MessageFormat::Legacy => address.address_type() != Some(AddressType::P2tr),
+        MessageFormat::Bip322Simple => matches!(
+            address.address_type(),
+            Some(AddressType::P2wpkh | AddressType::P2tr)
+        ),
+    }
+}
+
+fn bip322_message_hash(message: &str) -> sha256::Hash {
+    let tag = sha256::Hash::hash(BIP322_TAG);
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_ref());
+    engine.input(tag.as_ref());
+    engine.input(message.as_bytes());
+    sha256::Hash::from_engine(engine)
+}
+
+/// The virtual transaction committing to the message, spent by `to_sign`
+fn to_spend(script_pubkey: &Script, message: &str) -> Transaction {
+    Transaction {
+        version: Version(0),
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint {
+                txid: Txid::all_zeros(),
+                vout: 0xFFFFFFFF,
+            },
+            script_sig: Builder::new()
+                .push_int(0)
+                .push_slice(bip322_message_hash(message).to_byte_array())
+                .into_script(),
+            sequence: Sequence::ZERO,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: Amount::ZERO,
+            script_pubkey: script_pubkey.to_owned(),
+        }],
+    }
+}
+
+fn to_sign(to_spend: &Transaction, witness: Witness) -> Transaction {
+    Transaction {
+        version: Version(0),
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint {
+                txid: to_spend.compute_txid(),
+                vout: 0,
+            },
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ZERO,
+            witness,
+        }],
+        output: vec![TxOut {
+            value: Amount::ZERO,
+            script_pubkey: Builder::new().push_opcode(OP_RETURN).into_script(),
+        }],
+    }
+}
+
+/// PSBT of `to_sign` for the device to sign, BIP322 simple signatures are its input witness
+pub fn bip322_psbt(
+    address: &Address,
+    message: &str,
+    pubkey: secp256k1::PublicKey,
+    origin: (Fingerprint, DerivationPath),
+) -> Result<Psbt, MessageError> {
+    let to_spend = to_spend(&address.script_pubkey(), message);
+    let mut psbt = Psbt::from_unsigned_tx(to_sign(&to_spend, Witness::new()))
+        .map_err(|e| MessageError::InvalidSignature(e.to_string()))?;
+
+    let input = &mut psbt.inputs[0];
+    input.witness_utxo = Some(to_spend.output[0].clone());
+    match address.address_type() {
+        Some(AddressType::P2wpkh) => {
+            // Some devices want the full previous transaction even for SegWit inputs
+            input.non_witness_utxo = Some(to_spend);
+            input.bip32_derivation.insert(pubkey, origin);
+        }
+        Some(AddressType::P2tr) => {
+            let internal_key = XOnlyPublicKey::from(pubkey);
+            input.tap_internal_key = Some(internal_key);
+            input.tap_key_origins.insert(internal_key, (vec![], origin));
+        }
+        _ => {
+            return Err(MessageError::UnsupportedAddress(
+                MessageFormat::Bip322Simple,
+            ))
+        }
+    }
+    Ok(psbt)
+}
+
+/// Encodes the witness of a signed `bip322_psbt` as BIP322 simple signature
+pub fn bip322_signature(psbt: &Psbt) -> Result<String, MessageError> {
+    let input = psbt.inputs.first().ok_or(MessageError::NotSigned)?;
+    let witness = if let Some(witness) = &input.final_script_witness {
+        witness.clone()
+    } else if let Some(signature) = &input.tap_key_sig {
+        Witness::p2tr_key_spend(signature)
+    } else if let Some((pubkey, signature)) = input.partial_sigs.iter().next() {
+        Witness::p2wpkh(signature, &pubkey.inner)
+    } else {
+        return Err(MessageError::NotSigned);
+    };
+    Ok(general_purpose::STANDARD.encode(serialize(&witness)))
+}
+
+/// Checks a base64 signature of `message` by `address`, telling the format apart by its encoding
+///
+/// Fails only when the signature can't be decoded, a well-formed signature by another key is
+/// reported as invalid.
+pub fn verify(
+    address: &Address,
+    message: &str,
+    signature: &str,
+) -> Result<MessageVerification, MessageError> {
+    let bytes = general_purpose::STANDARD
+        .decode(signature.trim())
+        .map_err(|e| MessageError::InvalidSignature(e.to_string()))?;
+
+    if bytes.len() == 65 && (27..=42).contains(&bytes[0]) {
+        Ok(MessageVerification {
+            valid: verify_legacy(address, message, &bytes)?,
+            format: MessageFormat::Legacy,
+        })
+    } else {
+        let witness: Witness =
+            deserialize(&bytes).map_err(|e| MessageError::InvalidSignature(e.to_string()))?;
+        Ok(MessageVerification {
+            valid: verify_bip322_simple(address, message, witness)?,
+            format: MessageFormat::Bip322Simple,
+        })
+    }
+}
+
+fn verify_legacy(address: &Address, message: &str, bytes: &[u8]) -> Result<bool, MessageError> {
+    let invalid = |e: secp256k1::Error| MessageError::InvalidSignature(e.to_string());
+
+    // Headers 27-30 are for uncompressed keys, 31-34 for compressed P2PKH and 35-42 for
+    // P2SH-P2WPKH and P2WPKH, which are always compressed
+    let header = bytes[0] - 27;
+    let recovery_id = RecoveryId::from_i32((header & 0x03) as i32).map_err(invalid)?;
+    let signature = MessageSignature::new(
+        RecoverableSignature::from_compact(&bytes[1..], recovery_id).map_err(invalid)?,
+        header >= 4,
+    );
+
+    match signature.recover_pubkey(&Secp256k1::verification_only(), signed_msg_hash(message)) {
+        Ok(pubkey) => Ok(address.address_type() != Some(AddressType::P2tr)
+            && address.is_related_to_pubkey(&pubkey)),
+        Err(_) => Ok(false),
+    }
+}
+
+fn verify_bip322_simple(
+    address: &Address,
+    message: &str,
+    witness: Witness,
+) -> Result<bool, MessageError> {
+    let invalid = |e: &dyn std::fmt::Display| MessageError::InvalidSignature(e.to_string());
+    let secp = Secp256k1::verification_only();
+    let script_pubkey = address.script_pubkey();
+    let to_spend = to_spend(&script_pubkey, message);
+    let to_sign = to_sign(&to_spend, witness.clone());
+    let mut cache = SighashCache::new(&to_sign);
+
+    match address.address_type() {
+        Some(AddressType::P2wpkh) => {
+            let (Some(signature), Some(pubkey), 2) =
+                (witness.nth(0), witness.nth(1), witness.len())
+            else {
+                return Ok(false);
+            };
+            let signature = ecdsa::Signature::from_slice(signature).map_err(|e| invalid(&e))?;
+            let pubkey = PublicKey::from_slice(pubkey).map_err(|e| invalid(&e))?;
+            if signature.sighash_type != EcdsaSighashType::All
+                || !address.is_related_to_pubkey(&pubkey)
+            {
+                return Ok(false);
+            }
+
+            let sighash = cache
+                .p2wpkh_signature_hash(0, &script_pubkey, Amount::ZERO, signature.sighash_type)
+                .map_err(|e| invalid(&e))?;
+            Ok(secp
+                .verify_ecdsa(
+                    &Message::from_digest(sighash.to_byte_array()),
+                    &signature.signature,
+                    &pubkey.inner,
+                )
+                .is_ok())
+        }
+        Some(AddressType::P2tr) => {
+            let (Some(signature), 1) = (witness.nth(0), witness.len()) else {
+                return Ok(false);
+            };
+            let signature = taproot::Signature::from_slice(signature).map_err(|e| invalid(&e))?;
+            if !matches!(
+                signature.sighash_type,
+                TapSighashType::Default | TapSighashType::All
+            ) {
+                return Ok(false);
+            }
+
+            let output_key = XOnlyPublicKey::from_slice(&script_pubkey.as_bytes()[2..])
+                .map_err(|e| invalid(&e))?;
+            let sighash = cache
+                .taproot_key_spend_signature_hash(
+                    0,
+                    &Prevouts::All(&to_spend.output),
+                    signature.sighash_type,
+                )
+                .map_err(|e| invalid(&e))?;
+            Ok(secp
+                .verify_schnorr(
+                    &signature.signature,
+                    &Message::from_digest(sighash.to_byte_array()),
+                    &output_key,
+                )
+                .is_ok())
+        }
+        _ => Err(MessageError::UnsupportedAddress(
+            MessageFormat::Bip322Simple,
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::secp256k1::SecretKey;
+    use std::str::FromStr;
+
+    /// Test vectors from BIP322
+    const BIP322_ADDRESS: &str = "bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l";
+    const BIP322_EMPTY: &str = "AkcwRAIgM2gBAQqvZX15ZiysmKmQpDrG83avLIT492QBzLnQIxYCIBaTpOaD20qRlEylyxFSeEA2ba9YOixpX8z46TSDtS40ASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=";
+    const BIP322_HELLO_WORLD: &str = "AkcwRAIgZRfIY3p7/DoVTty6YZbWS71bc5Vct9p9Fia83eRmw2QCICK/ENGfwLtptFluMGs2KsqoNSk89pO7F29zJLUx9a/sASECx/EgAxlkQpQ9hYjgGu6EBCPMVPwVIVJqO4XCsMvViHI=";
+
+    fn address(address: &str) -> Address {
+        Address::from_str(address).unwrap().assume_checked()
+    }
+
+    #[test]
+    fn verifies_bip322_simple_signatures() {
+        assert_eq!(
+            bip322_message_hash("Hello World").to_string(),
+            "f0eb03b1a75ac6d9847f55c624a99169b5dccba2a31f5b23bea77ba270de0a7a"
+        );
+
+        let address = address(BIP322_ADDRESS);
+        let verification = verify(&address, "", BIP322_EMPTY).unwrap();
+        assert_eq!(verification.format, MessageFormat::Bip322Simple);
+        assert!(verification.valid);
+        assert!(
+            verify(&address, "Hello World", BIP322_HELLO_WORLD)
+                .unwrap()
+                .valid
+        );
+        assert!(!verify(&address, "Hello World", BIP322_EMPTY).unwrap().valid);
+    }
+
+    #[test]
+    fn verifies_legacy_signatures() {
+        let secp = Secp256k1::new();
+        let secret_key = SecretKey::from_slice(&[7; 32]).unwrap();
+        let pubkey = CompressedPublicKey(secret_key.public_key(&secp));
+        let p2pkh = Address::p2pkh(pubkey, Network::Bitcoin);
+        let p2wpkh = Address::p2wpkh(&pubkey, Network::Bitcoin);
+
+        let sign = |message: &str, header: u8| {
+            let hash = signed_msg_hash(message);
+            let (recovery_id, compact) = secp
+                .sign_ecdsa_recoverable(&Message::from_digest(hash.to_byte_array()), &secret_key)
+                .serialize_compact();
+            let mut bytes = vec![header + recovery_id.to_i32() as u8];
+            bytes.extend_from_slice(&compact);
+            general_purpose::STANDARD.encode(bytes)
+        };
+
+        let verification = verify(&p2pkh, "Hello World", &sign("Hello World", 31)).unwrap();
+        assert_eq!(verification.format, MessageFormat::Legacy);
+        assert!(verification.valid);
+        // BIP137 header of a P2WPKH address
+        assert!(
+            verify(&p2wpkh, "Hello World", &sign("Hello World", 39))
+                .unwrap()
+                .valid
+        );
+        assert!(
+            !verify(&p2pkh, "Hello", &sign("Hello World", 31))
+                .unwrap()
+                .valid
+        );
+        assert!(verify(&p2pkh, "Hello World", "not a signature").is_err());
+    }
+}
@@ -6,13 +6,21 @@ use async_hwi::{
     specter::{Specter, SpecterSimulator},
     HWI,
 };
-use bitcoin::{base64, hashes::hex::FromHex, Network};
+use bitcoin::{
+    base64,
+    bip32::{Fingerprint, Xpub},
+    hashes::hex::FromHex,
+    Network,
+};
 use std::{
     error::Error,
     path::{Path, PathBuf},
+    str::FromStr,
 };
 
 use base64::Engine;
+
+use crate::emulators::{self, Emulator};
 use serde::{Deserialize, Serialize};
 use std::io::{Read, Write};
 use tauri::Manager;
@@ -185,8 +193,10 @@ pub async fn list_devices(
 ) -> Result<Vec<Box<dyn HWI + Send>>, Box<dyn Error>> {
     let mut hws = Vec::new();
 
-    if let Ok(device) = SpecterSimulator::try_connect().await {
-        hws.push(device.into());
+    if emulators::is_enabled(Emulator::Specter) {
+        if let Ok(device) = SpecterSimulator::try_connect().await {
+            hws.push(device.into());
+        }
     }
 
     if let Ok(devices) = Specter::enumerate().await {
@@ -219,13 +229,16 @@ pub async fn list_devices(
                                 continue; // Skip incompatible network configurations
                             }
 
-                            device = device.with_wallet(
-                                wallet
-                                    .as_ref()
-                                    .and_then(|w| w.name.as_ref())
-                                    .ok_or::<Box<dyn Error>>("jade requires a wallet name".into())?
-                                    .to_string(),
-                            );
+                            if let Some(ref wallet) = wallet {
+                                device = device.with_wallet(
+                                    wallet
+                                        .name
+                                        .ok_or::<Box<dyn Error>>(
+                                            "jade requires a wallet name".into(),
+                                        )?
+                                        .to_string(),
+                                );
+                            }
 
                             hws.push(device.into());
                         }
@@ -237,8 +250,10 @@ pub async fn list_devices(
         Err(e) => eprintln!("Error enumerating Jade devices: {:?}", e),
     }
 
-    if let Ok(device) = LedgerSimulator::try_connect().await {
-        hws.push(device.into());
+    if emulators::is_enabled(Emulator::Ledger) {
+        if let Ok(device) = LedgerSimulator::try_connect().await {
+            hws.push(device.into());
+        }
     }
 
     let api = Box::new(HidApi::new().unwrap());
@@ -326,6 +341,43 @@ pub async fn list_devices(
     Ok(hws)
 }
 
+/// Replies of the connected Coldcards to `version`, by master fingerprint
+///
+/// async-hwi only keeps the version number, the rest of the reply tells the models apart.
+pub fn coldcard_versions() -> Vec<(Fingerprint, String)> {
+    let api = match HidApi::new() {
+        Ok(api) => Box::new(api),
+        Err(e) => {
+            eprintln!("Failed to open HID API: {:?}", e);
+            return Vec::new();
+        }
+    };
+
+    let mut versions = Vec::new();
+    for device_info in api.device_list() {
+        if device_info.vendor_id() != coldcard::api::COINKITE_VID
+            || device_info.product_id() != coldcard::api::CKCC_PID
+        {
+            continue;
+        }
+        let Some(sn) = device_info.serial_number() else {
+            continue;
+        };
+        if let Ok((mut cc, xpub)) = coldcard::api::Coldcard::open(&api, sn, None) {
+            // Opening the device already returns its master xpub, none if it has no secret yet
+            let fingerprint = xpub
+                .and_then(|xpub| Xpub::from_str(&xpub.xpub).ok())
+                .map(|xpub| xpub.fingerprint());
+            match (fingerprint, cc.version()) {
+                (Some(fingerprint), Ok(version)) => versions.push((fingerprint, version)),
+                (_, Err(e)) => eprintln!("Failed to read the Coldcard version: {:?}", e),
+                (None, _) => eprintln!("Failed to read the Coldcard fingerprint"),
+            }
+        }
+    }
+    versions
+}
+
 pub async fn get_miniscript_device_by_fingerprint(
     network: bitcoin::Network,
     fingerprint: Option<&str>,
@@ -344,7 +396,24 @@ pub async fn get_miniscript_device_by_fingerprint(
     )
     .await
     .map_err(|e| e.to_string())?;
+    find_by_fingerprint(devices, fingerprint).await
+}
+
+/// Device for single-key operations, which need no wallet policy
+pub async fn get_device_by_fingerprint(
+    network: bitcoin::Network,
+    fingerprint: Option<&str>,
+) -> Result<Box<dyn async_hwi::HWI + Send>, String> {
+    let devices = list_devices(network, None, None)
+        .await
+        .map_err(|e| e.to_string())?;
+    find_by_fingerprint(devices, fingerprint).await
+}
 
+async fn find_by_fingerprint(
+    devices: Vec<Box<dyn HWI + Send>>,
+    fingerprint: Option<&str>,
+) -> Result<Box<dyn async_hwi::HWI + Send>, String> {
     for device in devices {
         if let Some(fg) = fingerprint {
             if fg.to_uppercase()
@@ -0,0 +1,258 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use thiserror::Error;
+
+use crate::error::AppError;
+use crate::hwi::error::Error as HWIError;
+use crate::hwi::interface::HWIClient;
+use crate::hwi::types::{HWIChain, HWIDevice, HWIImplementation};
+
+/// Consecutive wrong PINs after which no more PINs are sent to a device
+///
+/// Trezor and KeepKey delay every attempt after a wrong PIN and wipe themselves after 16, so the
+/// app stops well before that. Wrong PINs are counted per device path, selecting the device
+/// again keeps the count and only unplugging it starts over.
+pub const MAX_PIN_ATTEMPTS: u32 = 5;
+
+/// Wrong PINs per device path, kept across selections of the device
+static FAILED_ATTEMPTS: Mutex<BTreeMap<String, u32>> = Mutex::new(BTreeMap::new());
+
+fn failed_attempts() -> MutexGuard<'static, BTreeMap<String, u32>> {
+    FAILED_ATTEMPTS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Starts counting wrong PINs over, for a device which was unplugged
+pub fn forget_failed_attempts(path: &str) {
+    failed_attempts().remove(path);
+}
+
+#[derive(Error, Debug)]
+pub enum PinError {
+    #[error("Ask the device to show the PIN matrix first")]
+    NotPrompted,
+    #[error("The device is already unlocked")]
+    AlreadyUnlocked,
+    #[error("Too many wrong PINs were entered, reconnect the device to try again")]
+    LockedOut,
+    #[error("The device was disconnected while it was being unlocked")]
+    DeviceLost,
+}
+
+/// Steps of unlocking a device with the host-side PIN matrix
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PinState {
+    /// The device needs a PIN and isn't showing the matrix
+    Locked,
+    /// The device shows the matrix and waits for the scrambled PIN
+    Prompted,
+    Unlocked,
+    /// Too many wrong PINs, see `MAX_PIN_ATTEMPTS`
+    LockedOut,
+}
+
+/// Reported to the UI after every step, also as a `pin-state` event
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PinStatus {
+    pub state: PinState,
+    pub failed_attempts: u32,
+    pub remaining_attempts: u32,
+    /// Whether the unlocked device still waits for its BIP39 passphrase
+    pub needs_passphrase: bool,
+}
+
+/// PIN unlock flow of the selected device
+#[derive(Debug)]
+pub struct PinUnlock {
+    state: PinState,
+    /// Key of the device in `FAILED_ATTEMPTS`
+    path: Option<String>,
+    failed_attempts: u32,
+    needs_passphrase: bool,
+}
+
+impl PinUnlock {
+    pub fn new(device: Option<&HWIDevice>) -> Self {
+        let path = device.map(|device| device.path.clone());
+        let failed_attempts = path
+            .as_ref()
+            .and_then(|path| failed_attempts().get(path).copied())
+            .unwrap_or(0);
+        PinUnlock {
+            state: match device {
+                Some(device) if device.needs_pin_sent => match failed_attempts {
+                    attempts if attempts >= MAX_PIN_ATTEMPTS => PinState::LockedOut,
+                    _ => PinState::Locked,
+                },
+                _ => PinState::Unlocked,
+            },
+            path,
+            failed_attempts,
+            needs_passphrase: device.is_some_and(|device| device.needs_passphrase_sent),
+        }
+    }
+
+    fn set_failed_attempts(&mut self, attempts: u32) {
+        self.failed_attempts = attempts;
+        if let Some(path) = &self.path {
+            match attempts {
+                0 => failed_attempts().remove(path),
+                _ => failed_attempts().insert(path.clone(), attempts),
+            };
+        }
+    }
+
+    pub fn status(&self) -> PinStatus {
+        PinStatus {
+            state: self.state,
+            failed_attempts: self.failed_attempts,
+            remaining_attempts: MAX_PIN_ATTEMPTS.saturating_sub(self.failed_attempts),
+            needs_passphrase: self.needs_passphrase,
+        }
+    }
+
+    /// Makes the device show the PIN matrix, again if it already does
+    pub async fn prompt<T: HWIImplementation>(
+        &mut self,
+        client: &HWIClient<T>,
+    ) -> Result<PinStatus, AppError> {
+        match self.state {
+            PinState::LockedOut => Err(PinError::LockedOut.into()),
+            PinState::Unlocked => Err(PinError::AlreadyUnlocked.into()),
+            PinState::Locked | PinState::Prompted => {
+                client.prompt_pin().await?;
+                self.state = PinState::Prompted;
+                Ok(self.status())
+            }
+        }
+    }
+
+    /// Sends the scrambled PIN and checks whether it unlocked the device
+    ///
+    /// Whether the PIN was right is decided by enumerating again, as the device only reports
+    /// failure. After a wrong PIN the matrix is shown again for the next attempt. Returns the
+    /// enumerated device once it is unlocked, since its fingerprint is only known from then on.
+    pub async fn send<T: HWIImplementation>(
+        &mut self,
+        client: &HWIClient<T>,
+        pin: &str,
+        chain: HWIChain,
+    ) -> Result<(PinStatus, Option<HWIDevice>), AppError> {
+        match self.state {
+            PinState::LockedOut => return Err(PinError::LockedOut.into()),
+            PinState::Unlocked => return Err(PinError::AlreadyUnlocked.into()),
+            PinState::Locked => return Err(PinError::NotPrompted.into()),
+            PinState::Prompted => {}
+        }
+
+        // HWI reports a wrong PIN as `{"success": false}`, errors with a code are about
+        // something else, e.g. the connection
+        if let Err(e) = client.send_pin(pin).await {
+            if e.code().is_some() || !matches!(e, HWIError::Hwi(..)) {
+                self.state = PinState::Locked;
+                return Err(e.into());
+            }
+        }
+
+        let device = refresh_device(client, chain).await?;
+        if !device.needs_pin_sent {
+            self.state = PinState::Unlocked;
+            self.set_failed_attempts(0);
+            self.needs_passphrase = device.needs_passphrase_sent;
+            return Ok((self.status(), Some(device)));
+        }
+
+        self.set_failed_attempts(self.failed_attempts + 1);
+        if self.failed_attempts >= MAX_PIN_ATTEMPTS {
+            self.state = PinState::LockedOut;
+        } else {
+            self.state = PinState::Locked;
+            client.prompt_pin().await?;
+            self.state = PinState::Prompted;
+        }
+        Ok((self.status(), None))
+    }
+}
+
+/// Finds the client's device in a new enumeration, by path as it has no fingerprint while locked
+async fn refresh_device<T: HWIImplementation>(
+    client: &HWIClient<T>,
+    chain: HWIChain,
+) -> Result<HWIDevice, AppError> {
+    let current = client.device().ok_or(AppError::NoDevice)?;
+    HWIClient::<T>::enumerate(Some(chain))
+        .await?
+        .into_iter()
+        .filter_map(Result::ok)
+        .find(|device| device.path == current.path && device.device_type == current.device_type)
+        .ok_or(PinError::DeviceLost.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hwi_fixtures::locked_client;
+    use bitcoin::Network;
+    use tauri::async_runtime::block_on;
+
+    /// Devices from the REGTEST enumerate fixture: the first one stays locked, the second one
+    /// shows up unlocked
+    const WRONG_PIN_PATH: &str = "webusb:001:2";
+    const RIGHT_PIN_PATH: &str = "webusb:001:3";
+
+    #[test]
+    fn unlocks_with_the_right_pin() {
+        let client = locked_client(RIGHT_PIN_PATH);
+        let mut pin = PinUnlock::new(client.device());
+        assert_eq!(pin.status().state, PinState::Locked);
+
+        let chain = HWIChain::from(Network::Regtest);
+        assert!(block_on(pin.send(&client, "1234", chain.clone())).is_err());
+
+        assert_eq!(
+            block_on(pin.prompt(&client)).unwrap().state,
+            PinState::Prompted
+        );
+        let (status, device) = block_on(pin.send(&client, "1234", chain)).unwrap();
+        assert_eq!(status.state, PinState::Unlocked);
+        assert_eq!(
+            device.and_then(|d| d.fingerprint).map(|f| f.to_string()),
+            Some("0f056943".to_string())
+        );
+    }
+
+    #[test]
+    fn locks_out_after_too_many_wrong_pins() {
+        let client = locked_client(WRONG_PIN_PATH);
+        let mut pin = PinUnlock::new(client.device());
+        let chain = HWIChain::from(Network::Regtest);
+
+        block_on(pin.prompt(&client)).unwrap();
+        for attempt in 1..MAX_PIN_ATTEMPTS {
+            let (status, device) = block_on(pin.send(&client, "1111", chain.clone())).unwrap();
+            assert_eq!(status.state, PinState::Prompted);
+            assert_eq!(status.failed_attempts, attempt);
+            assert!(device.is_none());
+        }
+
+        let (status, _) = block_on(pin.send(&client, "1111", chain.clone())).unwrap();
+        assert_eq!(status.state, PinState::LockedOut);
+        assert_eq!(status.remaining_attempts, 0);
+        assert!(block_on(pin.prompt(&client)).is_err());
+        assert!(block_on(pin.send(&client, "1111", chain)).is_err());
+
+        // Selecting the device again keeps the count, unplugging it resets it
+        assert_eq!(
+            PinUnlock::new(client.device()).status().state,
+            PinState::LockedOut
+        );
+        forget_failed_attempts(WRONG_PIN_PATH);
+        let status = PinUnlock::new(client.device()).status();
+        assert_eq!(status.state, PinState::Locked);
+        assert_eq!(status.remaining_attempts, MAX_PIN_ATTEMPTS);
+    }
+}
@@ -0,0 +1,121 @@
+use bitcoin::Psbt;
+use log::{error, info};
+use serde_json::{json, Value};
+use tauri::{Manager, Runtime};
+
+use crate::channel::Channel;
+
+/// Intermediate states of a long-running device operation
+pub enum ProgressStage {
+    DeviceFound,
+    RegisteringWallet,
+    WaitingForConfirmation,
+    SignedInputs { signed: usize, total: usize },
+}
+
+impl ProgressStage {
+    fn code(&self) -> &'static str {
+        match self {
+            ProgressStage::DeviceFound => "DEVICE_FOUND",
+            ProgressStage::RegisteringWallet => "REGISTERING_WALLET",
+            ProgressStage::WaitingForConfirmation => "WAITING_FOR_CONFIRMATION",
+            ProgressStage::SignedInputs { .. } => "SIGNED_INPUTS",
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            ProgressStage::DeviceFound => "Device found".to_string(),
+            ProgressStage::RegisteringWallet => "Registering wallet on the device".to_string(),
+            ProgressStage::WaitingForConfirmation => {
+                "Waiting for confirmation on the device".to_string()
+            }
+            ProgressStage::SignedInputs { signed, total } => {
+                format!("Signed {} of {} inputs", signed, total)
+            }
+        }
+    }
+}
+
+/// Reports the progress of a device operation to the webview and, encrypted, to the phone
+pub struct ProgressReporter<'a, R: Runtime> {
+    app_handle: &'a tauri::AppHandle<R>,
+    channel: &'a Channel,
+    request_id: Option<String>,
+    action: &'static str,
+    network: String,
+}
+
+impl<'a, R: Runtime> ProgressReporter<'a, R> {
+    pub fn new(
+        app_handle: &'a tauri::AppHandle<R>,
+        channel: &'a Channel,
+        request_id: Option<String>,
+        action: &'static str,
+        network: bitcoin::Network,
+    ) -> Self {
+        ProgressReporter {
+            app_handle,
+            channel,
+            request_id,
+            action,
+            network: network.to_string(),
+        }
+    }
+
+    pub fn report(&self, stage: ProgressStage) {
+        let mut progress = json!({
+            "requestId": self.request_id,
+            "action": self.action,
+            "stage": stage.code(),
+            "message": stage.message(),
+        });
+        if let ProgressStage::SignedInputs { signed, total } = stage {
+            progress["signedInputs"] = json!(signed);
+            progress["totalInputs"] = json!(total);
+        }
+        info!("{} progress: {}", self.action, progress["message"]);
+
+        if let Err(e) = self.app_handle.emit_all("device-progress", &progress) {
+            error!("Failed to emit device-progress event: {:?}", e);
+        }
+
+        // Progress only makes sense for requests which came in over the channel
+        if self.request_id.is_some() && self.channel.client.is_some() {
+            if let Err(e) = self.channel.emit(
+                "CHANNEL_MESSAGE",
+                progress_message(progress),
+                false,
+                Some(&self.network),
+            ) {
+                error!("Failed to send progress to channel: {}", e);
+            }
+        }
+    }
+}
+
+fn progress_message(progress: Value) -> Value {
+    json!({
+        "event": "CHANNEL_MESSAGE",
+        "data": {
+            "progressData": progress
+        }
+    })
+}
+
+/// Counts the inputs which gained a signature between two versions of the same PSBT
+pub fn count_newly_signed_inputs(before: &Psbt, after: &Psbt) -> usize {
+    before
+        .inputs
+        .iter()
+        .zip(after.inputs.iter())
+        .filter(|(before, after)| signature_count(after) > signature_count(before))
+        .count()
+}
+
+fn signature_count(input: &bitcoin::psbt::Input) -> usize {
+    input.partial_sigs.len()
+        + input.tap_script_sigs.len()
+        + usize::from(input.tap_key_sig.is_some())
+        + usize::from(input.final_script_witness.is_some() || input.final_script_sig.is_some())
+}
@@ -0,0 +1,846 @@
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+use tauri::api::process::{Command, CommandChild, CommandEvent};
+use tokio::sync::oneshot;
+
+use crate::approval::unix_time;
+use crate::diagnostics::{self, Secrets, Transcript};
+use crate::emulators;
+use crate::hwi::error::{Error, ErrorCode};
+use crate::hwi::types::HWIBinaryExecutor;
+
+/// Timeout for commands which only talk to the device
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Timeout for commands which wait for the user to confirm on the device
+const INTERACTIVE_TIMEOUT: Duration = Duration::from_secs(300);
+
+const INTERACTIVE_COMMANDS: [&str; 10] = [
+    "signtx",
+    "signmessage",
+    "displayaddress",
+    "promptpin",
+    "togglepassphrase",
+    "setup",
+    "restore",
+    "backup",
+    "wipe",
+    "register",
+];
+
+/// SHA-256 of the sidecar the app was built with, embedded by build.rs for unsigned builds
+const SIDECAR_SHA256: Option<&str> = option_env!("HWI_SIDECAR_SHA256");
+
+/// SHA-256 of the HWI worker, only embedded for unsigned builds which bundle it
+const WORKER_SHA256: Option<&str> = option_env!("HWI_WORKER_SHA256");
+
+/// Who signed the sidecars of signed builds, "apple:<team id>" or "windows:<thumbprint>"
+///
+/// The bundler signs the sidecars after build.rs ran, which changes their hashes, so these
+/// builds check the code signature instead.
+const SIDECAR_SIGNER: Option<&str> = option_env!("HWI_SIDECAR_SIGNER");
+
+/// Supported HWI versions, the minimum is inclusive and the maximum exclusive
+///
+/// 2.2.0 is the first release with `register` and descriptor support in `displayaddress`.
+const MIN_HWI_VERSION: (u32, u32, u32) = (2, 2, 0);
+const MAX_HWI_VERSION: (u32, u32, u32) = (4, 0, 0);
+
+/// Result of the last HWI version check, `None` until it ran
+static VERIFICATION: tokio::sync::Mutex<Option<VersionCheck>> = tokio::sync::Mutex::const_new(None);
+
+#[derive(Clone, Debug, PartialEq)]
+enum VersionCheck {
+    Supported(String),
+    Unsupported(String),
+    /// The version could not be read, e.g. because the process timed out or could not be
+    /// spawned. The check runs again before the next command.
+    Failed(String),
+}
+
+impl VersionCheck {
+    fn result(&self) -> Result<String, String> {
+        match self {
+            VersionCheck::Supported(version) => Ok(version.clone()),
+            VersionCheck::Unsupported(e) | VersionCheck::Failed(e) => Err(e.clone()),
+        }
+    }
+}
+
+/// HWI processes which are still running, keyed by pid
+///
+/// Kept outside of the app state so that commands can be canceled while a device operation
+/// holds the state lock.
+static IN_FLIGHT: Mutex<BTreeMap<u32, CommandChild>> = Mutex::new(BTreeMap::new());
+
+/// The warm worker, `None` until the first request and after it exited
+static WORKER: Mutex<Option<Worker>> = Mutex::new(None);
+
+/// Requests sent to the worker which wait for their response, keyed by request id
+static PENDING: Mutex<BTreeMap<u64, Pending>> = Mutex::new(BTreeMap::new());
+
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Sidecar name of the long-lived HWI worker, see hwi-worker/hwi_worker.py
+const WORKER_SIDECAR: &str = "hwi-worker";
+
+struct Worker {
+    pid: u32,
+    child: CommandChild,
+}
+
+struct Pending {
+    /// Worker the request was sent to
+    pid: u32,
+    reply: oneshot::Sender<Outcome>,
+}
+
+#[derive(Serialize)]
+struct WorkerRequest<'a> {
+    id: u64,
+    args: &'a [String],
+    stdin: &'a str,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct WorkerResponse {
+    id: u64,
+    code: Option<i32>,
+    stdout: String,
+    stderr: String,
+}
+
+/// Runs HWI commands in the bundled sidecars
+///
+/// Commands go to a warm `hwi-worker` process which keeps Python and the device libraries
+/// loaded. It reads one JSON request per line, tagged with an id, and answers each with the
+/// output the `hwi` CLI would have produced. A worker which crashes or times out is killed and
+/// the next command starts a new one. Builds without the worker spawn an `hwi` process for
+/// every command instead.
+#[cfg_attr(test, allow(dead_code))]
+pub struct HWIBinaryExecutorImpl;
+
+impl HWIBinaryExecutor for HWIBinaryExecutorImpl {
+    async fn execute_command(args: Vec<String>, stdin_args: Vec<String>) -> Result<String, Error> {
+        verify().await?;
+        run(args, stdin_args).await
+    }
+}
+
+/// Checks the HWI version the sidecar reports
+///
+/// A supported or unsupported version is reused for every later command, any other failure is
+/// retried by the next command. The binaries themselves are checked before every spawn, see
+/// `check_binary`.
+pub async fn verify() -> Result<String, Error> {
+    let mut verification = VERIFICATION.lock().await;
+    let check = match &*verification {
+        Some(check @ (VersionCheck::Supported(_) | VersionCheck::Unsupported(_))) => check.clone(),
+        _ => {
+            let check = check_version(run(vec!["--version".to_string()], vec![]).await);
+            match &check {
+                VersionCheck::Supported(version) => {
+                    info!("Verified HWI sidecar, version {}", version)
+                }
+                VersionCheck::Unsupported(e) | VersionCheck::Failed(e) => {
+                    error!("HWI sidecar verification failed: {}", e)
+                }
+            }
+            *verification = Some(check.clone());
+            check
+        }
+    };
+    check.result().map_err(Error::UntrustedBinary)
+}
+
+fn check_version(output: Result<String, Error>) -> VersionCheck {
+    let output = match output {
+        Ok(output) => output,
+        Err(e) => return VersionCheck::Failed(format!("failed to get the HWI version: {}", e)),
+    };
+    let Some(version) = parse_version(&output) else {
+        return VersionCheck::Failed(format!("unexpected HWI version output: {}", output.trim()));
+    };
+    if version < MIN_HWI_VERSION || version >= MAX_HWI_VERSION {
+        return VersionCheck::Unsupported(format!(
+            "HWI {} is not supported, expected a version from {} up to {}",
+            format_version(version),
+            format_version(MIN_HWI_VERSION),
+            format_version(MAX_HWI_VERSION)
+        ));
+    }
+    VersionCheck::Supported(format_version(version))
+}
+
+/// Checks a sidecar against what build.rs embedded, right before it is spawned
+///
+/// Checking on every spawn also catches a binary which was swapped after the first command, a
+/// swapped binary could sign arbitrary transactions.
+fn check_binary(name: &str) -> Result<(), Error> {
+    let result = sidecar_path(name).and_then(|path| {
+        let expected_hash = if name == WORKER_SIDECAR {
+            WORKER_SHA256
+        } else {
+            SIDECAR_SHA256
+        };
+        match (SIDECAR_SIGNER, expected_hash) {
+            (Some(signer), _) => check_signature(&path, signer),
+            (None, Some(hash)) => check_hash(&path, hash),
+            (None, None) => Err(format!(
+                "nothing to check {} against was embedded at build time",
+                path.display()
+            )),
+        }
+    });
+    result.map_err(|e| {
+        error!("HWI sidecar verification failed: {}", e);
+        Error::UntrustedBinary(e)
+    })
+}
+
+fn check_hash(path: &Path, expected: &str) -> Result<(), String> {
+    let binary =
+        std::fs::read(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let hash = hex::encode(Sha256::digest(binary));
+    if hash != expected {
+        return Err(format!(
+            "{} has SHA-256 {}, expected {}",
+            path.display(),
+            hash,
+            expected
+        ));
+    }
+    Ok(())
+}
+
+fn check_signature(path: &Path, signer: &str) -> Result<(), String> {
+    match signer.split_once(':') {
+        Some(("apple", team_id)) => check_apple_signature(path, team_id),
+        Some(("windows", thumbprint)) => check_authenticode_signature(path, thumbprint),
+        _ => Err(format!("unsupported sidecar signer {}", signer)),
+    }
+}
+
+/// Verifies the code signature and that it was made with a Developer ID of our team
+fn check_apple_signature(path: &Path, team_id: &str) -> Result<(), String> {
+    let requirement = format!(
+        "=anchor apple generic and certificate leaf[subject.OU] = \"{}\"",
+        team_id
+    );
+    let output = std::process::Command::new("/usr/bin/codesign")
+        .args(["--verify", "--strict", "-R"])
+        .arg(&requirement)
+        .arg(path)
+        .output()
+        .map_err(|e| format!("failed to run codesign: {}", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} is not signed by team {}: {}",
+            path.display(),
+            team_id,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// Verifies the Authenticode signature and that it was made with our certificate
+fn check_authenticode_signature(path: &Path, thumbprint: &str) -> Result<(), String> {
+    let powershell = std::env::var_os("SystemRoot")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(r"C:\Windows"))
+        .join(r"System32\WindowsPowerShell\v1.0\powershell.exe");
+    let mut command = std::process::Command::new(powershell);
+    command
+        .args([
+            "-NoProfile",
+            "-NonInteractive",
+            "-Command",
+            "$s = Get-AuthenticodeSignature -LiteralPath $env:KEEPER_SIDECAR; \
+             \"$($s.Status) $($s.SignerCertificate.Thumbprint)\"",
+        ])
+        .env("KEEPER_SIDECAR", path);
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        // CREATE_NO_WINDOW, the check would flash a console window otherwise
+        command.creation_flags(0x0800_0000);
+    }
+    let output = command
+        .output()
+        .map_err(|e| format!("failed to run Get-AuthenticodeSignature: {}", e))?;
+
+    let signature = String::from_utf8_lossy(&output.stdout);
+    if is_valid_authenticode_signature(&signature, thumbprint) {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} is not signed with certificate {}: {}",
+            path.display(),
+            thumbprint,
+            signature.trim()
+        ))
+    }
+}
+
+/// Parses "<status> <thumbprint>" as printed by `check_authenticode_signature`
+fn is_valid_authenticode_signature(signature: &str, thumbprint: &str) -> bool {
+    matches!(
+        signature.split_whitespace().collect::<Vec<_>>()[..],
+        ["Valid", signer] if signer.eq_ignore_ascii_case(thumbprint)
+    )
+}
+
+/// Same location `Command::new_sidecar` runs the binary from
+fn sidecar_path(name: &str) -> Result<PathBuf, String> {
+    let exe = tauri::utils::platform::current_exe()
+        .map_err(|e| format!("failed to locate the app executable: {}", e))?;
+    let dir = exe
+        .parent()
+        .ok_or("failed to locate the app directory".to_string())?;
+    Ok(dir.join(if cfg!(windows) {
+        format!("{}.exe", name)
+    } else {
+        name.to_string()
+    }))
+}
+
+/// Parses the output of `hwi --version`, e.g. "hwi 2.3.1" or "hwi 3.0.0rc1"
+fn parse_version(output: &str) -> Option<(u32, u32, u32)> {
+    let version = output.split_whitespace().last()?.trim_start_matches('v');
+    let mut parts = version.splitn(3, '.').map(|part| {
+        let digits: String = part.chars().take_while(char::is_ascii_digit).collect();
+        digits.parse::<u32>().ok()
+    });
+    Some((parts.next()??, parts.next()??, parts.next()??))
+}
+
+fn format_version((major, minor, patch): (u32, u32, u32)) -> String {
+    format!("{}.{}.{}", major, minor, patch)
+}
+
+/// Output of one HWI command, `code` is `None` if the process was killed
+struct Output {
+    code: Option<i32>,
+    stdout: String,
+    stderr: String,
+}
+
+enum Outcome {
+    Finished(Output),
+    TimedOut,
+    Canceled,
+}
+
+async fn run(args: Vec<String>, stdin_args: Vec<String>) -> Result<String, Error> {
+    let mut args = args;
+
+    if emulators::hwi_probe_needed() {
+        args.insert(0, "--emulators".to_string());
+    }
+    let subcommand = diagnostics::subcommand(&args, &stdin_args);
+    let is_enumerate = subcommand == "enumerate";
+
+    let stdin = stdin_payload(&stdin_args)?;
+    let timeout = command_timeout(subcommand);
+    let secrets = Secrets::from_stdin_args(&stdin_args);
+    let started_at = unix_time();
+    let start = Instant::now();
+    let outcome = if worker_available() {
+        run_in_worker(
+            &args,
+            stdin.as_deref().unwrap_or_default(),
+            timeout,
+            &secrets,
+        )
+        .await?
+    } else {
+        run_process(&args, stdin, timeout, &secrets).await?
+    };
+
+    let (exit_code, stdout, stderr) = match &outcome {
+        Outcome::Finished(output) => (output.code, output.stdout.as_str(), output.stderr.as_str()),
+        Outcome::TimedOut | Outcome::Canceled => (None, "", ""),
+    };
+    diagnostics::record(
+        &secrets,
+        Transcript {
+            started_at,
+            duration_ms: start.elapsed().as_millis() as u64,
+            args: args.clone(),
+            stdin_args,
+            exit_code,
+            stdout: stdout.to_string(),
+            stderr: stderr.to_string(),
+        },
+    );
+
+    match outcome {
+        Outcome::TimedOut => Err(Error::Hwi(
+            format!("HWI command timed out after {}s", timeout.as_secs()),
+            None,
+        )),
+        Outcome::Canceled => Err(Error::Hwi(
+            "HWI command was canceled".to_string(),
+            Some(ErrorCode::ActionCanceled),
+        )),
+        Outcome::Finished(Output {
+            code: Some(0),
+            stdout,
+            ..
+        }) if is_enumerate => Ok(emulators::filter_enumerate_output(stdout)),
+        Outcome::Finished(Output {
+            code: Some(0),
+            stdout,
+            ..
+        }) => Ok(stdout),
+        // HWI prints its error object on stdout, anything else (e.g. a traceback) on stderr
+        Outcome::Finished(Output { stdout, stderr, .. }) if stdout.trim().is_empty() => {
+            Err(Error::Hwi(stderr, None))
+        }
+        Outcome::Finished(Output { stdout, .. }) => Err(Error::Hwi(stdout, None)),
+    }
+}
+
+/// Runs one command in a new `hwi` process
+async fn run_process(
+    args: &[String],
+    stdin: Option<String>,
+    timeout: Duration,
+    secrets: &Secrets,
+) -> Result<Outcome, Error> {
+    check_binary("hwi")?;
+    let (mut rx, mut child) = Command::new_sidecar("hwi")
+        .map_err(|e| Error::Hwi(format!("Failed to create sidecar command: {}", e), None))?
+        .args(args)
+        .spawn()
+        .map_err(|e| Error::Hwi(format!("Failed to execute command: {}", e), None))?;
+
+    let pid = child.pid();
+    if let Some(stdin) = stdin {
+        if let Err(e) = child.write(stdin.as_bytes()) {
+            let _ = child.kill();
+            return Err(Error::Hwi(
+                format!("Failed to write to HWI stdin: {}", e),
+                None,
+            ));
+        }
+    }
+    register(pid, child);
+
+    let output = tokio::time::timeout(timeout, async {
+        let mut code = None;
+        let mut stdout = String::new();
+        let mut stderr = String::new();
+        while let Some(event) = rx.recv().await {
+            match event {
+                CommandEvent::Terminated(payload) => code = payload.code,
+                CommandEvent::Stdout(line) => {
+                    stdout.push_str(&line);
+                    stdout.push('\n');
+                }
+                CommandEvent::Stderr(line) => {
+                    // HWI writes its `--debug` output to stderr
+                    debug!(target: "hwi", "[{}] {}", pid, secrets.redact(&line));
+                    stderr.push_str(&line);
+                    stderr.push('\n');
+                }
+                CommandEvent::Error(e) => warn!("HWI process {} error: {}", pid, e),
+                _ => {}
+            }
+        }
+        Output {
+            code,
+            stdout,
+            stderr,
+        }
+    })
+    .await;
+
+    // A process which is no longer registered was killed by `cancel_all`
+    let child = unregister(pid);
+
+    match output {
+        Err(_) => {
+            if let Some(child) = child {
+                if let Err(e) = child.kill() {
+                    warn!("Failed to kill HWI process {}: {}", pid, e);
+                }
+            }
+            Ok(Outcome::TimedOut)
+        }
+        Ok(_) if child.is_none() => Ok(Outcome::Canceled),
+        Ok(output) => Ok(Outcome::Finished(output)),
+    }
+}
+
+/// Whether the worker was bundled, older builds only ship the `hwi` CLI
+fn worker_available() -> bool {
+    sidecar_path(WORKER_SIDECAR).is_ok_and(|path| path.exists())
+}
+
+/// Runs one command in the warm worker, starting it first if it is not running
+async fn run_in_worker(
+    args: &[String],
+    stdin: &str,
+    timeout: Duration,
+    secrets: &Secrets,
+) -> Result<Outcome, Error> {
+    let id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
+    let mut line = serde_json::to_string(&WorkerRequest { id, args, stdin })
+        .map_err(|e| Error::Hwi(format!("Failed to encode HWI worker request: {}", e), None))?;
+    line.push('\n');
+
+    let (reply, response) = oneshot::channel();
+    let pid = send_to_worker(id, &line, reply)?;
+
+    match tokio::time::timeout(timeout, response).await {
+        Ok(Ok(outcome)) => {
+            if let Outcome::Finished(output) = &outcome {
+                for line in output.stderr.lines() {
+                    debug!(target: "hwi", "[{}#{}] {}", pid, id, secrets.redact(line));
+                }
+            }
+            Ok(outcome)
+        }
+        // The reader drops the requests of a worker which exited
+        Ok(Err(_)) => Err(Error::Hwi(
+            "HWI worker exited unexpectedly".to_string(),
+            None,
+        )),
+        Err(_) => {
+            // The worker handles one request at a time and is stuck on this one
+            pending().remove(&id);
+            stop_worker(pid);
+            Ok(Outcome::TimedOut)
+        }
+    }
+}
+
+fn send_to_worker(id: u64, line: &str, reply: oneshot::Sender<Outcome>) -> Result<u32, Error> {
+    let mut worker = worker();
+    let current = match &mut *worker {
+        Some(current) => current,
+        slot @ None => slot.insert(spawn_worker()?),
+    };
+    let pid = current.pid;
+
+    pending().insert(id, Pending { pid, reply });
+    if let Err(e) = current.child.write(line.as_bytes()) {
+        pending().remove(&id);
+        if let Some(worker) = worker.take() {
+            let _ = worker.child.kill();
+        }
+        return Err(Error::Hwi(
+            format!("Failed to write to the HWI worker: {}", e),
+            None,
+        ));
+    }
+    Ok(pid)
+}
+
+fn spawn_worker() -> Result<Worker, Error> {
+    check_binary(WORKER_SIDECAR)?;
+    let (mut rx, child) = Command::new_sidecar(WORKER_SIDECAR)
+        .map_err(|e| Error::Hwi(format!("Failed to create sidecar command: {}", e), None))?
+        .spawn()
+        .map_err(|e| Error::Hwi(format!("Failed to start the HWI worker: {}", e), None))?;
+
+    let pid = child.pid();
+    info!("Started HWI worker {}", pid);
+    tauri::async_runtime::spawn(async move {
+        while let Some(event) = rx.recv().await {
+            match event {
+                CommandEvent::Stdout(line) => match serde_json::from_str::<WorkerResponse>(&line) {
+                    Ok(response) => deliver(response),
+                    Err(e) => warn!("Unexpected output of HWI worker {}: {}", pid, e),
+                },
+                // Request output is part of the response, only the worker itself logs here
+                CommandEvent::Stderr(line) => debug!(target: "hwi", "[{}] {}", pid, line),
+                CommandEvent::Error(e) => warn!("HWI worker {} error: {}", pid, e),
+                CommandEvent::Terminated(payload) => {
+                    warn!("HWI worker {} exited with code {:?}", pid, payload.code);
+                    break;
+                }
+                _ => {}
+            }
+        }
+        worker_exited(pid);
+    });
+    Ok(Worker { pid, child })
+}
+
+fn deliver(response: WorkerResponse) {
+    let Some(request) = pending().remove(&response.id) else {
+        // The request already timed out
+        return;
+    };
+    let _ = request.reply.send(Outcome::Finished(Output {
+        code: response.code,
+        stdout: response.stdout,
+        stderr: response.stderr,
+    }));
+}
+
+/// Forgets an exited worker, so that the next request starts a new one, and fails its requests
+fn worker_exited(pid: u32) {
+    {
+        let mut worker = worker();
+        if worker.as_ref().is_some_and(|worker| worker.pid == pid) {
+            *worker = None;
+        }
+    }
+    pending().retain(|_, request| request.pid != pid);
+}
+
+fn stop_worker(pid: u32) {
+    let stopped = {
+        let mut worker = worker();
+        match &*worker {
+            Some(current) if current.pid == pid => worker.take(),
+            _ => None,
+        }
+    };
+    if let Some(worker) = stopped {
+        info!("Stopping HWI worker {}", pid);
+        if let Err(e) = worker.child.kill() {
+            warn!("Failed to kill HWI worker {}: {}", pid, e);
+        }
+    }
+}
+
+/// Result of the last version check, `None` while it has not run yet or is running
+pub fn verification() -> Option<Result<String, String>> {
+    VERIFICATION
+        .try_lock()
+        .ok()?
+        .as_ref()
+        .map(VersionCheck::result)
+}
+
+/// Kills every running HWI process, returning how many commands were stopped
+///
+/// The worker is only stopped if it is busy, an idle worker stays warm.
+pub fn cancel_all() -> usize {
+    let children = std::mem::take(&mut *in_flight());
+
+    let mut count = children.len();
+    for (pid, child) in children {
+        info!("Canceling HWI process {}", pid);
+        if let Err(e) = child.kill() {
+            warn!("Failed to kill HWI process {}: {}", pid, e);
+        }
+    }
+
+    let mut worker = worker();
+    if let Some(pid) = worker.as_ref().map(|worker| worker.pid) {
+        let (canceled, others) = std::mem::take(&mut *pending())
+            .into_iter()
+            .partition::<BTreeMap<_, _>, _>(|(_, request)| request.pid == pid);
+        *pending() = others;
+
+        if !canceled.is_empty() {
+            info!(
+                "Canceling {} requests of HWI worker {}",
+                canceled.len(),
+                pid
+            );
+            count += canceled.len();
+            for request in canceled.into_values() {
+                let _ = request.reply.send(Outcome::Canceled);
+            }
+            if let Some(worker) = worker.take() {
+                if let Err(e) = worker.child.kill() {
+                    warn!("Failed to kill HWI worker {}: {}", pid, e);
+                }
+            }
+        }
+    }
+    count
+}
+
+fn in_flight() -> MutexGuard<'static, BTreeMap<u32, CommandChild>> {
+    // The map stays consistent even if a holder panicked, so poisoning can be ignored
+    IN_FLIGHT.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn worker() -> MutexGuard<'static, Option<Worker>> {
+    WORKER.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn pending() -> MutexGuard<'static, BTreeMap<u64, Pending>> {
+    PENDING.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn register(pid: u32, child: CommandChild) {
+    in_flight().insert(pid, child);
+}
+
+fn unregister(pid: u32) -> Option<CommandChild> {
+    in_flight().remove(&pid)
+}
+
+/// Formats arguments the way `hwi --stdin` reads them: one shell-quoted line per argument,
+/// terminated by an empty line
+fn stdin_payload(stdin_args: &[String]) -> Result<Option<String>, Error> {
+    if stdin_args.is_empty() {
+        return Ok(None);
+    }
+
+    let mut payload = String::new();
+    for arg in stdin_args {
+        if arg.contains(['\n', '\r']) {
+            return Err(Error::Hwi(
+                "HWI arguments cannot contain line breaks".to_string(),
+                Some(ErrorCode::BadArgument),
+            ));
+        }
+        payload.push_str(&shell_quote(arg));
+        payload.push('\n');
+    }
+    payload.push('\n');
+    Ok(Some(payload))
+}
+
+fn shell_quote(arg: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "+/=-_.,:@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\"'\"'"))
+    }
+}
+
+/// Timeout of a subcommand, which may come from stdin when a password is used
+fn command_timeout(subcommand: &str) -> Duration {
+    if INTERACTIVE_COMMANDS.contains(&subcommand) {
+        INTERACTIVE_TIMEOUT
+    } else {
+        DEFAULT_TIMEOUT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hwi_versions() {
+        assert_eq!(parse_version("hwi 2.3.1\n"), Some((2, 3, 1)));
+        assert_eq!(parse_version("hwi 3.0.0rc1"), Some((3, 0, 0)));
+        assert_eq!(parse_version("hwi"), None);
+        assert_eq!(parse_version(""), None);
+    }
+
+    #[test]
+    fn only_keeps_definitive_version_checks() {
+        assert_eq!(
+            check_version(Ok("hwi 2.3.1\n".to_string())),
+            VersionCheck::Supported("2.3.1".to_string())
+        );
+        assert!(matches!(
+            check_version(Ok("hwi 4.0.0\n".to_string())),
+            VersionCheck::Unsupported(_)
+        ));
+        assert!(matches!(
+            check_version(Err(Error::Hwi(
+                "HWI command timed out after 60s".to_string(),
+                None
+            ))),
+            VersionCheck::Failed(_)
+        ));
+    }
+
+    #[test]
+    fn waits_longer_for_interactive_commands() {
+        let strings = |args: &[&str]| args.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let timeout = |args: &[&str], stdin_args: &[&str]| {
+            command_timeout(diagnostics::subcommand(
+                &strings(args),
+                &strings(stdin_args),
+            ))
+        };
+
+        assert_eq!(
+            timeout(&["--chain", "test", "signtx"], &[]),
+            INTERACTIVE_TIMEOUT
+        );
+        assert_eq!(
+            timeout(&["--chain", "test", "--stdin"], &["signtx", "cHNidP8B"]),
+            INTERACTIVE_TIMEOUT
+        );
+        assert_eq!(
+            timeout(&["--stdin"], &["--password", "wipe", "getxpub", "m/84h"]),
+            DEFAULT_TIMEOUT
+        );
+        assert_eq!(
+            timeout(&["setup", "--label", "wipe"], &["--backup_passphrase", "x"]),
+            INTERACTIVE_TIMEOUT
+        );
+        assert_eq!(
+            timeout(&["--device-type", "setup", "enumerate"], &[]),
+            DEFAULT_TIMEOUT
+        );
+    }
+
+    #[test]
+    fn checks_authenticode_status_and_signer() {
+        let thumbprint = "C65BED6047BF3E1B42777C049CBC921F88E1B167";
+        assert!(is_valid_authenticode_signature(
+            "Valid C65BED6047BF3E1B42777C049CBC921F88E1B167\r\n",
+            thumbprint
+        ));
+        assert!(is_valid_authenticode_signature(
+            "Valid c65bed6047bf3e1b42777c049cbc921f88e1b167",
+            thumbprint
+        ));
+        assert!(!is_valid_authenticode_signature(
+            "HashMismatch C65BED6047BF3E1B42777C049CBC921F88E1B167",
+            thumbprint
+        ));
+        assert!(!is_valid_authenticode_signature(
+            "Valid 0000000000000000000000000000000000000000",
+            thumbprint
+        ));
+        assert!(!is_valid_authenticode_signature("NotSigned ", thumbprint));
+    }
+
+    #[test]
+    fn speaks_the_worker_protocol() {
+        let args = [
+            "--chain".to_string(),
+            "test".to_string(),
+            "--stdin".to_string(),
+        ];
+        let request = serde_json::to_string(&WorkerRequest {
+            id: 7,
+            args: &args,
+            stdin: "enumerate\n\n",
+        })
+        .unwrap();
+        assert_eq!(
+            request,
+            r#"{"id":7,"args":["--chain","test","--stdin"],"stdin":"enumerate\n\n"}"#
+        );
+
+        let response: WorkerResponse =
+            serde_json::from_str(r#"{"id": 7, "code": 0, "stdout": "[]\n", "stderr": ""}"#)
+                .unwrap();
+        assert_eq!(
+            response,
+            WorkerResponse {
+                id: 7,
+                code: Some(0),
+                stdout: "[]\n".to_string(),
+                stderr: String::new(),
+            }
+        );
+    }
+}
@@ -18,10 +18,29 @@ const HWI_ACTIONS = {
   signTx: "signTx",
   registerMultisig: "registerMultisig",
   verifyAddress: "verifyAddress",
+  signMessage: "signMessage",
+  setupDevice: "setupDevice",
+  restoreDevice: "restoreDevice",
+  backupDevice: "backupDevice",
+  wipeDevice: "wipeDevice",
+  togglePassphrase: "togglePassphrase",
 };
 
 type HWI_ACTION = keyof typeof HWI_ACTIONS;
 
+const LIFECYCLE_ACTIONS = [
+  "setupDevice",
+  "restoreDevice",
+  "backupDevice",
+  "wipeDevice",
+  "togglePassphrase",
+] as const;
+
+type LifecycleAction = (typeof LIFECYCLE_ACTIONS)[number];
+
+const isLifecycleAction = (action: HWI_ACTION): action is LifecycleAction =>
+  (LIFECYCLE_ACTIONS as readonly string[]).includes(action);
+
 const HWI_DEVICES = {
   ledger: {
     icon: ledgerIcon,
@@ -56,7 +75,10 @@ interface HWIDevice {
 
 interface DeviceContent {
   icon: string;
-  content: Record<HWI_ACTION, { text: string; list: string[] }>;
+  content: Record<
+    Exclude<HWI_ACTION, LifecycleAction>,
+    { text: string; list: string[] }
+  >;
 }
 
 type HWIDeviceType = keyof typeof HWI_DEVICES;
@@ -99,6 +121,12 @@ const deviceContent: Record<HWIDeviceType, DeviceContent> = {
           "In case the address on your Ledger is different than the address on the Keeper mobile app please contact support immediately.",
         ],
       },
+      signMessage: {
+        text: "Your mobile app is asking to sign a message with one of your Ledger addresses, e.g. to prove you own it. Please approve the message on your Ledger.",
+        list: [
+          "Make sure the message shown on your Ledger is the one you intend to sign.",
+        ],
+      },
     },
   },
   trezor: {
@@ -136,6 +164,12 @@ const deviceContent: Record<HWIDeviceType, DeviceContent> = {
           "In case the address on your Trezor is different than the address on the Keeper mobile app please contact support immediately.",
         ],
       },
+      signMessage: {
+        text: "Your mobile app is asking to sign a message with one of your Trezor addresses, e.g. to prove you own it. Please approve the message on your Trezor.",
+        list: [
+          "Make sure the message shown on your Trezor is the one you intend to sign.",
+        ],
+      },
     },
   },
   bitbox02: {
@@ -175,6 +209,12 @@ const deviceContent: Record<HWIDeviceType, DeviceContent> = {
           "In case the address on your BitBox02 is different than the address on the Keeper mobile app please contact support immediately.",
         ],
       },
+      signMessage: {
+        text: "Your mobile app is asking to sign a message with one of your BitBox02 addresses, e.g. to prove you own it. Please approve the message on your BitBox02.",
+        list: [
+          "Make sure the message shown on your BitBox02 is the one you intend to sign.",
+        ],
+      },
     },
   },
   coldcard: {
@@ -212,6 +252,12 @@ const deviceContent: Record<HWIDeviceType, DeviceContent> = {
           "In case the address on your Coldcard is different than the address on the Keeper mobile app please contact support immediately.",
         ],
       },
+      signMessage: {
+        text: "Your mobile app is asking to sign a message with one of your Coldcard addresses, e.g. to prove you own it. Please approve the message on your Coldcard.",
+        list: [
+          "Make sure the message shown on your Coldcard is the one you intend to sign.",
+        ],
+      },
     },
   },
   jade: {
@@ -251,15 +297,60 @@ const deviceContent: Record<HWIDeviceType, DeviceContent> = {
           "In case the address on your Jade is different than the address on the Keeper mobile app please contact support immediately.",
         ],
       },
+      signMessage: {
+        text: "Your mobile app is asking to sign a message with one of your Jade addresses, e.g. to prove you own it. Please approve the message on your Jade.",
+        list: [
+          "Make sure the message shown on your Jade is the one you intend to sign.",
+        ],
+      },
     },
   },
 };
 
+// Device lifecycle operations read the same on every device
+const lifecycleContent = (
+  deviceName: string,
+): Record<LifecycleAction, { text: string; list: string[] }> => ({
+  setupDevice: {
+    text: `Your mobile app is asking to set up your ${deviceName} with a new seed. Follow the instructions on the device screen.`,
+    list: [
+      `Write down the recovery words shown on your ${deviceName} and keep them offline.`,
+    ],
+  },
+  restoreDevice: {
+    text: `Your mobile app is asking to restore your ${deviceName} from a recovery phrase. Enter the words as requested on the device.`,
+    list: [
+      "Never type your recovery phrase on a computer or phone, only on the device.",
+    ],
+  },
+  backupDevice: {
+    text: `Your mobile app is asking to back up your ${deviceName}. Follow the instructions on the device screen.`,
+    list: [],
+  },
+  wipeDevice: {
+    text: `Your mobile app is asking to wipe your ${deviceName}. This erases its keys, which can only be recovered from the recovery phrase.`,
+    list: [
+      `Make sure you have a backup of the recovery phrase of your ${deviceName} before continuing.`,
+      "Confirm the wipe on the device screen.",
+    ],
+  },
+  togglePassphrase: {
+    text: `Your mobile app is asking to turn passphrase protection on or off on your ${deviceName}. Confirm the change on the device screen.`,
+    list: [
+      "Wallets using a passphrase are different from the wallet without one.",
+    ],
+  },
+});
+
 export {
   HWI_DEVICES,
   HWI_ACTIONS,
+  LIFECYCLE_ACTIONS,
   deviceContent,
+  isLifecycleAction,
+  lifecycleContent,
   type HWI_ACTION,
+  type LifecycleAction,
   type HWIDeviceType,
   type HWIDevice,
   type NetworkType,
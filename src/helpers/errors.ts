@@ -0,0 +1,60 @@
+export type AppErrorKind =
+  | "HWI"
+  | "DEVICE"
+  | "CHANNEL"
+  | "APPROVAL"
+  | "AUDIT"
+  | "PIN"
+  | "LIFECYCLE"
+  | "MESSAGE"
+  | "EXPORT"
+  | "CAPABILITY"
+  | "BUSY"
+  | "NO_DEVICE"
+  | "ADDRESS_MISMATCH"
+  | "INVALID_REQUEST"
+  | "OTHER";
+
+export interface AppError {
+  kind: AppErrorKind;
+  code: string | null;
+  message: string;
+  device: string | null;
+  retryable: boolean;
+}
+
+const errorCodeMessages: Record<string, string> = {
+  ACTION_CANCELED: "The action was canceled on the device",
+  USER_REFUSED: "The action was canceled on the device",
+  DEVICE_NOT_READY: "The device is not ready. Please unlock it and try again",
+  DEVICE_BUSY: "The device is busy. Please finish the current action first",
+  NEED_TO_BE_ROOT:
+    "Permission denied while accessing the device. Please check your udev rules",
+  UNTRUSTED_BINARY:
+    "The bundled HWI binary failed verification. Please reinstall the app",
+  WRONG_KEY:
+    "The device signed with an unexpected key. Please check the derivation path",
+  CONFIRMATION_EXPIRED: "The confirmation has expired. Please try again",
+};
+
+const errorKindMessages: Partial<Record<AppErrorKind, string>> = {
+  BUSY: "Another operation is in progress. Please wait for it to finish",
+  NO_DEVICE: "No device is connected. Please connect your device again",
+};
+
+export const isAppError = (error: unknown): error is AppError =>
+  typeof error === "object" &&
+  error !== null &&
+  "kind" in error &&
+  "message" in error;
+
+export const getErrorMessage = (error: unknown): string => {
+  if (isAppError(error)) {
+    return (
+      (error.code && errorCodeMessages[error.code]) ||
+      errorKindMessages[error.kind] ||
+      error.message
+    );
+  }
+  return String(error);
+};
@@ -1,11 +1,17 @@
 import { useState } from "react";
 import hwiService from "../services/hwiService";
+import { getErrorMessage } from "../helpers/errors";
 import {
   HWI_ACTION,
   HWIDevice,
   HWIDeviceType,
   NetworkType,
 } from "../helpers/devices";
+import {
+  HWIAddressType,
+  LifecycleOptions,
+  MessageFormat,
+} from "../services/hwiService";
 
 interface UseDeviceActionsProps {
   network: NetworkType | null;
@@ -19,8 +25,14 @@ interface UseDeviceActionsProps {
   walletName: string | null;
   hmac: string | null;
   expectedAddress: string | null;
+  message: string | null;
+  derivationPath: string | null;
+  messageFormat: MessageFormat | null;
+  addressType: HWIAddressType | null;
+  lifecycleOptions: LifecycleOptions | null;
   onConnectResult: (devices: HWIDevice[]) => void;
   onActionSuccess: () => void;
+  onConfirmationRequired: () => void;
   onError: (error: string) => void;
 }
 
@@ -36,8 +48,14 @@ export const useDeviceActions = ({
   walletName,
   hmac,
   expectedAddress,
+  message,
+  derivationPath,
+  messageFormat,
+  addressType,
+  lifecycleOptions,
   onConnectResult,
   onActionSuccess,
+  onConfirmationRequired,
   onError,
 }: UseDeviceActionsProps) => {
   const [isLoading, setIsLoading] = useState(false);
@@ -85,8 +103,14 @@ export const useDeviceActions = ({
           onActionSuccess();
           break;
         case "verifyAddress":
-          if (!descriptor && (!miniscriptPolicy || addressIndex === null)) {
-            onError("Descriptor or miniscript policy and index are required");
+          if (
+            !descriptor &&
+            !derivationPath &&
+            (!miniscriptPolicy || addressIndex === null)
+          ) {
+            onError(
+              "Descriptor, derivation path or miniscript policy and index are required",
+            );
             return;
           }
           await hwiService.verifyAddress(
@@ -96,15 +120,58 @@ export const useDeviceActions = ({
             walletName,
             hmac,
             expectedAddress ?? "",
+            derivationPath,
+            addressType,
+          );
+          onActionSuccess();
+          break;
+        case "signMessage":
+          if (message === null || !derivationPath) {
+            onError("Message and derivation path are required");
+            return;
+          }
+          await hwiService.signMessage(
+            message,
+            derivationPath,
+            messageFormat ?? "LEGACY",
+          );
+          onActionSuccess();
+          break;
+        case "setupDevice":
+          await hwiService.setupDevice(
+            lifecycleOptions?.label ?? null,
+            lifecycleOptions?.passphrase ?? null,
           );
           onActionSuccess();
           break;
+        case "restoreDevice":
+          await hwiService.restoreDevice(
+            lifecycleOptions?.label ?? null,
+            lifecycleOptions?.wordCount ?? null,
+          );
+          onActionSuccess();
+          break;
+        case "backupDevice":
+          await hwiService.backupDevice(
+            lifecycleOptions?.label ?? null,
+            lifecycleOptions?.passphrase ?? null,
+          );
+          onActionSuccess();
+          break;
+        case "togglePassphrase":
+          await hwiService.togglePassphrase();
+          onActionSuccess();
+          break;
+        case "wipeDevice":
+          // Wiping waits for the device to be named in the confirmation modal
+          onConfirmationRequired();
+          break;
         default:
           throw new Error("Unsupported action type");
       }
     } catch (error) {
       console.error(`Error performing ${actionType} action:`, error);
-      onError((error as any).toString()); // eslint-disable-line @typescript-eslint/no-explicit-any
+      onError(getErrorMessage(error));
     } finally {
       setIsLoading(false);
     }
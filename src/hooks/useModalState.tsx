@@ -7,6 +7,8 @@ export type ModalType =
   | "multipleDevices"
   | "error"
   | "pin"
+  | "passphrase"
+  | "confirmation"
   | null;
 
 const useModalState = () => {
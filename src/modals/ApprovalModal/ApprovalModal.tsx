@@ -0,0 +1,101 @@
+import { useState } from "react";
+import BaseModal from "../BaseModal/BaseModal";
+import styles from "./ApprovalModal.module.css";
+import baseStyles from "../BaseModal/BaseModal.module.css";
+import InstructionsIcon from "../../assets/instructions-icon.svg";
+import { PendingApproval } from "../../services/approvalService";
+
+const ACTION_TITLES: Record<string, string> = {
+  ADD_DEVICE: "Share device xPubs",
+  HEALTH_CHECK: "Run a health check",
+  SIGN_TX: "Sign a transaction",
+  REGISTER_MULTISIG: "Register a vault",
+  VERIFY_ADDRESS: "Verify an address",
+  SIGN_MESSAGE: "Sign a message",
+  SETUP_DEVICE: "Set up the device",
+  RESTORE_DEVICE: "Restore the device",
+  BACKUP_DEVICE: "Back up the device",
+  WIPE_DEVICE: "Wipe the device",
+  TOGGLE_PASSPHRASE: "Toggle the device passphrase",
+};
+
+interface ApprovalModalProps {
+  approval: PendingApproval | null;
+  onApprove: (remember: boolean) => void;
+  onReject: () => void;
+}
+
+const ApprovalModal = ({
+  approval,
+  onApprove,
+  onReject,
+}: ApprovalModalProps) => {
+  const [remember, setRemember] = useState(false);
+
+  if (!approval) return null;
+
+  const handleApprove = () => {
+    onApprove(remember);
+    setRemember(false);
+  };
+
+  const handleReject = () => {
+    onReject();
+    setRemember(false);
+  };
+
+  const modalContent = {
+    image: (
+      <img src={InstructionsIcon} alt="Approval" className={baseStyles.icon} />
+    ),
+    title: (
+      <h2 className={baseStyles.title}>
+        {ACTION_TITLES[approval.action] ?? approval.action}
+      </h2>
+    ),
+    content: (
+      <>
+        <p className={baseStyles.text}>
+          Your Keeper app requested this action. Review the details before
+          continuing.
+        </p>
+        <pre className={styles.summary}>
+          {JSON.stringify(approval.summary, null, 2)}
+        </pre>
+        {approval.rememberable && (
+          <label className={styles.remember}>
+            <input
+              type="checkbox"
+              checked={remember}
+              onChange={(e) => setRemember(e.target.checked)}
+            />
+            Always allow this from this phone
+          </label>
+        )}
+      </>
+    ),
+    button: (
+      <div className={styles.buttonContainer}>
+        <button
+          className={`${baseStyles.continueButton} ${styles.rejectButton}`}
+          onClick={handleReject}
+        >
+          Reject
+        </button>
+        <button className={baseStyles.continueButton} onClick={handleApprove}>
+          Approve
+        </button>
+      </div>
+    ),
+  };
+
+  return (
+    <BaseModal
+      isOpen={true}
+      onClose={handleReject}
+      modalContent={modalContent}
+    />
+  );
+};
+
+export default ApprovalModal;
@@ -0,0 +1,134 @@
+import { useEffect, useState } from "react";
+import BaseModal from "../BaseModal/BaseModal";
+import styles from "./ConfirmationModal.module.css";
+import baseStyles from "../BaseModal/BaseModal.module.css";
+import loader from "../../assets/loader.svg";
+import hwiService, {
+  ConfirmationChallenge,
+  LifecycleOperation,
+} from "../../services/hwiService";
+import { getErrorMessage } from "../../helpers/errors";
+import {
+  HWI_DEVICES,
+  HWIDeviceType,
+  deviceContent,
+} from "../../helpers/devices";
+
+interface ConfirmationModalProps {
+  isOpen: boolean;
+  deviceType: HWIDeviceType;
+  operation: LifecycleOperation;
+  onClose: () => void;
+  onSuccess: () => void;
+  onError: (error: string) => void;
+}
+
+const ConfirmationModal = ({
+  isOpen,
+  deviceType,
+  operation,
+  onClose,
+  onSuccess,
+  onError,
+}: ConfirmationModalProps) => {
+  const [challenge, setChallenge] = useState<ConfirmationChallenge | null>(
+    null,
+  );
+  const [typed, setTyped] = useState("");
+  const [isLoading, setIsLoading] = useState(false);
+
+  useEffect(() => {
+    setTyped("");
+    setChallenge(null);
+    if (!isOpen) {
+      return;
+    }
+    hwiService
+      .getConfirmationChallenge(operation)
+      .then(setChallenge)
+      .catch((e) => onError(getErrorMessage(e)));
+  }, [isOpen, operation, onError]);
+
+  const matches =
+    challenge !== null &&
+    typed.trim().toLowerCase() === challenge.phrase.toLowerCase();
+
+  const handleConfirm = async () => {
+    if (!matches) return;
+    setIsLoading(true);
+    try {
+      // The token is only issued for the typed phrase and used right away
+      const confirmation = await hwiService.requestConfirmation(
+        operation,
+        typed,
+      );
+      switch (operation) {
+        case "WIPE_DEVICE":
+          await hwiService.wipeDevice(confirmation);
+          break;
+        default:
+          throw new Error(`${operation} does not need a confirmation`);
+      }
+      onSuccess();
+    } catch (e) {
+      onError(getErrorMessage(e));
+    } finally {
+      setIsLoading(false);
+    }
+  };
+
+  const name = HWI_DEVICES[deviceType].name;
+
+  const modalContent = {
+    image: (
+      <img
+        src={deviceContent[deviceType].icon}
+        alt={name}
+        className={baseStyles.icon}
+      />
+    ),
+    title: <h2 className={baseStyles.title}>Confirm the wipe</h2>,
+    content: (
+      <>
+        <p className={baseStyles.text}>
+          This erases the keys on your {name}. Type{" "}
+          <strong className={styles.phrase}>{challenge?.phrase}</strong> to
+          confirm that this is the device you want to wipe.
+        </p>
+        <input
+          type="text"
+          autoComplete="off"
+          spellCheck={false}
+          value={typed}
+          disabled={isLoading || !challenge}
+          onChange={(e) => setTyped(e.target.value)}
+          onKeyDown={(e) => e.key === "Enter" && handleConfirm()}
+          className={styles.input}
+        />
+      </>
+    ),
+    button: (
+      <button
+        disabled={isLoading || !matches}
+        onClick={handleConfirm}
+        className={`${baseStyles.continueButton} ${styles.confirmButton}`}
+      >
+        {isLoading ? (
+          <img
+            src={loader}
+            alt="Loading..."
+            className={styles.loadingSpinner}
+          />
+        ) : (
+          "Wipe"
+        )}
+      </button>
+    ),
+  };
+
+  return (
+    <BaseModal isOpen={isOpen} onClose={onClose} modalContent={modalContent} />
+  );
+};
+
+export default ConfirmationModal;
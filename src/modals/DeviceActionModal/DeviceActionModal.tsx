@@ -5,6 +5,8 @@ import {
   HWI_DEVICES,
   HWIDevice,
   HWIDeviceType,
+  isLifecycleAction,
+  lifecycleContent,
   NetworkType,
 } from "../../helpers/devices";
 import styles from "./DeviceActionModal.module.css";
@@ -12,7 +14,13 @@ import baseStyles from "../BaseModal/BaseModal.module.css";
 import loader from "../../assets/loader.svg";
 import verifyAddressIcon from "../../assets/verify-address-icon.svg";
 import { useDeviceActions } from "../../hooks/useDeviceActions";
-import { useMemo } from "react";
+import hwiService from "../../services/hwiService";
+import { useEffect, useMemo, useState } from "react";
+import {
+  HWIAddressType,
+  LifecycleOptions,
+  MessageFormat,
+} from "../../services/hwiService";
 
 interface DeviceActionModalProps {
   isOpen: boolean;
@@ -28,9 +36,15 @@ interface DeviceActionModalProps {
   walletName: string | null;
   hmac: string | null;
   expectedAddress: string | null;
+  message: string | null;
+  derivationPath: string | null;
+  messageFormat: MessageFormat | null;
+  addressType: HWIAddressType | null;
   pairingCode: string | null;
+  lifecycleOptions: LifecycleOptions | null;
   onConnectResult: (devices: HWIDevice[]) => void;
   onActionSuccess: () => void;
+  onConfirmationRequired: () => void;
   onError: (error: string) => void;
 }
 
@@ -41,6 +55,12 @@ const actionTitle = (deviceType: HWIDeviceType) => ({
   signTx: "Sign Transaction",
   registerMultisig: `Register Multisig on ${HWI_DEVICES[deviceType].name}`,
   verifyAddress: `Verify Address on your ${HWI_DEVICES[deviceType].name}`,
+  signMessage: `Sign Message with ${HWI_DEVICES[deviceType].name}`,
+  setupDevice: `Set up ${HWI_DEVICES[deviceType].name}`,
+  restoreDevice: `Restore ${HWI_DEVICES[deviceType].name}`,
+  backupDevice: `Back up ${HWI_DEVICES[deviceType].name}`,
+  wipeDevice: `Wipe ${HWI_DEVICES[deviceType].name}`,
+  togglePassphrase: `${HWI_DEVICES[deviceType].name} Passphrase`,
 });
 
 const DeviceActionModal = ({
@@ -57,9 +77,15 @@ const DeviceActionModal = ({
   walletName,
   hmac,
   expectedAddress,
+  message,
+  derivationPath,
+  messageFormat,
+  addressType,
   pairingCode,
+  lifecycleOptions,
   onConnectResult,
   onActionSuccess,
+  onConfirmationRequired,
   onError,
 }: DeviceActionModalProps) => {
   const { isLoading, handleContinue } = useDeviceActions({
@@ -74,12 +100,44 @@ const DeviceActionModal = ({
     walletName,
     hmac,
     expectedAddress,
+    message,
+    derivationPath,
+    messageFormat,
+    addressType,
+    lifecycleOptions,
     onConnectResult,
     onActionSuccess,
+    onConfirmationRequired,
     onError,
   });
 
+  const [progress, setProgress] = useState<string | null>(null);
+
+  useEffect(() => {
+    if (!isLoading) {
+      setProgress(null);
+      return;
+    }
+    const unsubscribe = hwiService.onDeviceProgress(({ message }) =>
+      setProgress(message),
+    );
+    return () => {
+      unsubscribe.then((f) => f());
+    };
+  }, [isLoading]);
+
+  const handleClose = () => {
+    // Closing the modal mid-operation stops the device command as well
+    if (isLoading) {
+      hwiService.cancelOperation().catch(console.error);
+    }
+    onClose();
+  };
+
   const content = deviceContent[deviceType];
+  const actionContent = isLifecycleAction(actionType)
+    ? lifecycleContent(HWI_DEVICES[deviceType].name)[actionType]
+    : content.content[actionType];
   const isVerifyAddress = actionType === "verifyAddress";
   const iconSrc = isVerifyAddress ? verifyAddressIcon : content.icon;
 
@@ -93,7 +151,7 @@ const DeviceActionModal = ({
       deviceType === "coldcard" &&
       actionType === "registerMultisig"
         ? "Please approve the registration of the wallet on the connected Coldcard device"
-        : content.content[actionType].text;
+        : actionContent.text;
 
     const listContent =
       miniscriptPolicy &&
@@ -102,7 +160,7 @@ const DeviceActionModal = ({
         ? [
             "Make sure to verify the public keys and wallet details shown on the Coldcard screen match the expected public keys of your cosigners and wallet details.",
           ]
-        : content.content[actionType].list;
+        : actionContent.list;
 
     const hasListItems = listContent.length > 0;
 
@@ -166,6 +224,9 @@ const DeviceActionModal = ({
               <li key={index}>{item}</li>
             ))}
           </ul>
+          {isLoading && progress && (
+            <span className={styles.progress}>{progress}</span>
+          )}
         </p>
       ),
       button: (
@@ -191,15 +252,20 @@ const DeviceActionModal = ({
     actionType,
     pairingCode,
     isLoading,
+    progress,
     handleContinue,
-    content,
+    actionContent,
     iconSrc,
     isVerifyAddress,
     miniscriptPolicy,
   ]);
 
   return (
-    <BaseModal isOpen={isOpen} onClose={onClose} modalContent={modalContent} />
+    <BaseModal
+      isOpen={isOpen}
+      onClose={handleClose}
+      modalContent={modalContent}
+    />
   );
 };
 
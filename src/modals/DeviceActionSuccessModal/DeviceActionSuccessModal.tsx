@@ -62,6 +62,36 @@ const DeviceActionSuccessModal = ({
           title: `Address Verified`,
           text: `If the address displayed on your ${deviceName} matched the address on the Keeper mobile app, you can safely use it to receive funds. Otherwise, please contact support.`,
         };
+      case "signMessage":
+        return {
+          title: `Message Signed`,
+          text: `The message has been signed with your ${deviceName} and the signature was sent to the Keeper mobile app.`,
+        };
+      case "setupDevice":
+        return {
+          title: `${deviceName} Set Up`,
+          text: `Your ${deviceName} has been set up with a new seed.`,
+        };
+      case "restoreDevice":
+        return {
+          title: `${deviceName} Restored`,
+          text: `Your ${deviceName} has been restored from the recovery phrase.`,
+        };
+      case "backupDevice":
+        return {
+          title: `Backup Completed`,
+          text: `The backup of your ${deviceName} has been completed.`,
+        };
+      case "wipeDevice":
+        return {
+          title: `${deviceName} Wiped`,
+          text: `Your ${deviceName} has been wiped. Connect it again to set it up or restore it.`,
+        };
+      case "togglePassphrase":
+        return {
+          title: `Passphrase Setting Changed`,
+          text: `Passphrase protection has been changed on your ${deviceName}.`,
+        };
     }
   };
 
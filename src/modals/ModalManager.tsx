@@ -5,6 +5,8 @@ import {
   MultipleDevicesModal,
   ErrorModal,
   TrezorPinModal,
+  PassphraseModal,
+  ConfirmationModal,
 } from "./index";
 import {
   HWI_ACTION,
@@ -12,6 +14,11 @@ import {
   HWIDeviceType,
   NetworkType,
 } from "../helpers/devices";
+import {
+  HWIAddressType,
+  LifecycleOptions,
+  MessageFormat,
+} from "../services/hwiService";
 import { ModalType } from "../hooks/useModalState";
 
 interface ModalsManagerProps {
@@ -29,6 +36,11 @@ interface ModalsManagerProps {
   walletName: string | null;
   hmac: string | null;
   expectedAddress: string | null;
+  message: string | null;
+  derivationPath: string | null;
+  messageFormat: MessageFormat | null;
+  addressType: HWIAddressType | null;
+  lifecycleOptions: LifecycleOptions | null;
   pairingCode: string | null;
   errorMessage: string;
   handleConnectResult: (devices: HWIDevice[]) => Promise<void>;
@@ -53,6 +65,11 @@ const ModalsManager = ({
   walletName,
   hmac,
   expectedAddress,
+  message,
+  derivationPath,
+  messageFormat,
+  addressType,
+  lifecycleOptions,
   pairingCode,
   errorMessage,
   handleConnectResult,
@@ -77,9 +94,24 @@ const ModalsManager = ({
         walletName={walletName}
         hmac={hmac}
         expectedAddress={expectedAddress}
+        message={message}
+        derivationPath={derivationPath}
+        messageFormat={messageFormat}
+        addressType={addressType}
         pairingCode={pairingCode}
+        lifecycleOptions={lifecycleOptions}
         onConnectResult={handleConnectResult}
         onActionSuccess={handleActionSuccess}
+        onConfirmationRequired={() => openModalHandler("confirmation")}
+        onError={handleError}
+      />
+
+      <ConfirmationModal
+        isOpen={openModal === "confirmation"}
+        deviceType={deviceType as HWIDeviceType}
+        operation="WIPE_DEVICE"
+        onClose={closeModalHandler}
+        onSuccess={handleActionSuccess}
         onError={handleError}
       />
 
@@ -125,6 +157,17 @@ const ModalsManager = ({
       />
       <TrezorPinModal
         isOpen={openModal === "pin"}
+        onClose={closeModalHandler}
+        onSuccess={() => {
+          openModalHandler("deviceActionSuccess");
+        }}
+        onPassphraseRequired={() => {
+          openModalHandler("passphrase");
+        }}
+      />
+      <PassphraseModal
+        isOpen={openModal === "passphrase"}
+        deviceType={deviceType as HWIDeviceType}
         network={network}
         onClose={closeModalHandler}
         onSuccess={() => {
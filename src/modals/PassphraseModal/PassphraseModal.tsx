@@ -0,0 +1,130 @@
+import { useState } from "react";
+import BaseModal from "../BaseModal/BaseModal";
+import styles from "./PassphraseModal.module.css";
+import baseStyles from "../BaseModal/BaseModal.module.css";
+import loader from "../../assets/loader.svg";
+import ErrorIcon from "../../assets/error-popup-icon.svg";
+import hwiService from "../../services/hwiService";
+import { getErrorMessage } from "../../helpers/errors";
+import {
+  HWI_DEVICES,
+  HWIDeviceType,
+  NetworkType,
+  deviceContent,
+} from "../../helpers/devices";
+
+interface PassphraseModalProps {
+  isOpen: boolean;
+  deviceType: HWIDeviceType;
+  network: NetworkType | null;
+  onClose: () => void;
+  onSuccess: () => void;
+}
+
+const PassphraseModal = ({
+  isOpen,
+  deviceType,
+  network,
+  onClose,
+  onSuccess,
+}: PassphraseModalProps) => {
+  const [passphrase, setPassphrase] = useState("");
+  const [error, setError] = useState("");
+  const [isLoading, setIsLoading] = useState(false);
+
+  const handleClose = () => {
+    setPassphrase("");
+    onClose();
+  };
+
+  const handlePassphraseSubmit = async () => {
+    setIsLoading(true);
+    try {
+      // The passphrase is only kept by the backend for the current session
+      await hwiService.setHWIClient(
+        null,
+        deviceType,
+        network!.toLowerCase(),
+        passphrase,
+      );
+      setPassphrase("");
+      onSuccess();
+    } catch (e) {
+      showError(getErrorMessage(e));
+    } finally {
+      setIsLoading(false);
+    }
+  };
+
+  function showError(error: string) {
+    setError(error);
+    const timer = setTimeout(() => {
+      setError("");
+    }, 4000);
+    return () => clearTimeout(timer);
+  }
+
+  const name = HWI_DEVICES[deviceType].name;
+
+  const modalContent = {
+    image: (
+      <img
+        src={deviceContent[deviceType].icon}
+        alt={name}
+        className={baseStyles.icon}
+      />
+    ),
+    title: <h2 className={baseStyles.title}>Enter your passphrase</h2>,
+    content: (
+      <>
+        <div className={`${styles.errorContainer} ${error ? styles.show : ""}`}>
+          <div className={styles.error}>
+            <img src={ErrorIcon} alt="Error" className={styles.errorIcon} />
+            <span>{error}</span>
+          </div>
+        </div>
+        <p className={baseStyles.text}>
+          Your {name} is protected with a passphrase. Enter it to access the
+          wallet, or leave it empty to use the standard wallet.
+        </p>
+        <input
+          type="password"
+          autoComplete="off"
+          spellCheck={false}
+          value={passphrase}
+          disabled={isLoading}
+          onChange={(e) => setPassphrase(e.target.value)}
+          onKeyDown={(e) => e.key === "Enter" && handlePassphraseSubmit()}
+          className={styles.input}
+        />
+      </>
+    ),
+    button: (
+      <button
+        disabled={isLoading}
+        onClick={handlePassphraseSubmit}
+        className={baseStyles.continueButton}
+      >
+        {isLoading ? (
+          <img
+            src={loader}
+            alt="Loading..."
+            className={styles.loadingSpinner}
+          />
+        ) : (
+          "Continue"
+        )}
+      </button>
+    ),
+  };
+
+  return (
+    <BaseModal
+      isOpen={isOpen}
+      onClose={handleClose}
+      modalContent={modalContent}
+    />
+  );
+};
+
+export default PassphraseModal;
@@ -1,29 +1,48 @@
-import { useState } from "react";
+import { useEffect, useState } from "react";
 import BaseModal from "../BaseModal/BaseModal";
 import styles from "./TrezorPinModal.module.css";
 import baseStyles from "../BaseModal/BaseModal.module.css";
 import loader from "../../assets/loader.svg";
 import TrezorIcon from "../../assets/hww/icons-modal/trezor.svg";
 import ErrorIcon from "../../assets/error-popup-icon.svg";
-import hwiService from "../../services/hwiService";
-import { NetworkType } from "../../helpers/devices";
+import hwiService, { PinStatus } from "../../services/hwiService";
+import { getErrorMessage } from "../../helpers/errors";
 
 interface TrezorPinModalProps {
   isOpen: boolean;
-  network: NetworkType | null;
   onClose: () => void;
   onSuccess: () => void;
+  onPassphraseRequired: () => void;
 }
 
 const TrezorPinModal = ({
   isOpen,
-  network,
   onClose,
   onSuccess,
+  onPassphraseRequired,
 }: TrezorPinModalProps) => {
   const [pin, setPin] = useState("");
   const [error, setError] = useState("");
   const [isLoading, setIsLoading] = useState(false);
+  const [pinStatus, setPinStatus] = useState<PinStatus | null>(null);
+
+  // Wrong PINs are counted per device, also across earlier attempts to unlock it
+  useEffect(() => {
+    if (!isOpen) {
+      setPinStatus(null);
+      return;
+    }
+    const unsubscribe = hwiService.onPinState(setPinStatus);
+    hwiService
+      .getPinStatus()
+      .then(setPinStatus)
+      .catch(() => setPinStatus(null));
+    return () => {
+      unsubscribe.then((f) => f());
+    };
+  }, [isOpen]);
+
+  const isLockedOut = pinStatus?.state === "LOCKED_OUT";
 
   const handlePinClick = (value: string) => {
     setPin((prevPin) => prevPin + value);
@@ -36,21 +55,22 @@ const TrezorPinModal = ({
     await new Promise((resolve) => setTimeout(resolve, 100));
     setIsLoading(true);
     try {
-      await hwiService.sendPin(pin);
-      const devices = await hwiService.fetchDevices(
-        "trezor",
-        network?.toLowerCase(),
-      );
-      await hwiService.setHWIClient(
-        devices[0].fingerprint,
-        "trezor",
-        network!.toLowerCase(),
-      );
-      onSuccess();
-    } catch {
-      // TODO: Show exact error
-      await hwiService.promptPin();
-      return showError("Wrong PIN entered");
+      // The device shows a new matrix after a wrong PIN
+      const status = await hwiService.sendPin(pin);
+      switch (status.state) {
+        case "UNLOCKED":
+          return status.needsPassphrase ? onPassphraseRequired() : onSuccess();
+        case "LOCKED_OUT":
+          return showError(
+            "Too many wrong PINs. Please reconnect your Trezor to try again",
+          );
+        default:
+          return showError(
+            `Wrong PIN entered, ${status.remainingAttempts} attempts left`,
+          );
+      }
+    } catch (e) {
+      return showError(getErrorMessage(e));
     } finally {
       setIsLoading(false);
     }
@@ -88,6 +108,13 @@ const TrezorPinModal = ({
           <p className={`${baseStyles.text} ${styles.text}`}>
             Follow the keypad layout on your Trezor
           </p>
+          {pinStatus && pinStatus.failedAttempts > 0 && (
+            <p className={styles.attempts}>
+              {isLockedOut
+                ? "Too many wrong PINs, reconnect your Trezor to try again"
+                : `${pinStatus.remainingAttempts} attempts left`}
+            </p>
+          )}
         </div>
         <div className={styles.pinPadContainer}>
           <div className={styles.pinPad}>
@@ -104,7 +131,7 @@ const TrezorPinModal = ({
     ),
     button: (
       <button
-        disabled={isLoading}
+        disabled={isLoading || isLockedOut}
         onClick={handlePinSubmit}
         className={`${baseStyles.continueButton} ${styles.submitButton}`}
       >
@@ -4,4 +4,7 @@ export { default as DeviceNotFoundModal } from "./DeviceNotFoundModal/DeviceNotF
 export { default as MultipleDevicesModal } from "./MultipleDevicesModal/MultipleDevicesModal";
 export { default as ErrorModal } from "./ErrorModal/ErrorModal";
 export { default as TrezorPinModal } from "./TrezorPinModal/TrezorPinModal";
+export { default as PassphraseModal } from "./PassphraseModal/PassphraseModal";
 export { default as SubscriptionsModal } from "./SubscriptionsModal/SubscriptionsModal";
+export { default as ApprovalModal } from "./ApprovalModal/ApprovalModal";
+export { default as ConfirmationModal } from "./ConfirmationModal/ConfirmationModal";
@@ -17,7 +17,16 @@ import {
 } from "../../helpers/devices";
 import ModalsManager from "../../modals/ModalManager";
 import SubscriptionsModal from "../../modals/SubscriptionsModal/SubscriptionsModal";
-import hwiService from "../../services/hwiService";
+import ApprovalModal from "../../modals/ApprovalModal/ApprovalModal";
+import hwiService, {
+  HWIAddressType,
+  LifecycleOptions,
+  MessageFormat,
+} from "../../services/hwiService";
+import { getErrorMessage } from "../../helpers/errors";
+import approvalService, {
+  PendingApproval,
+} from "../../services/approvalService";
 import { version } from "../../../package.json";
 
 interface ChannelMessagePayload {
@@ -33,6 +42,16 @@ interface ChannelMessagePayload {
     hmac?: string;
     firstExtAdd?: string;
     receivingAddress?: string;
+    // Message signing data
+    message?: string;
+    derivationPath?: string;
+    messageFormat?: MessageFormat;
+    // Single-sig address verification data
+    addressType?: HWIAddressType;
+    // Device lifecycle data
+    label?: string;
+    wordCount?: 12 | 18 | 24;
+    passphrase?: string;
     // Subscription data
     appId?: string;
     roomId?: string;
@@ -55,35 +74,50 @@ const ConnectScreen = () => {
   const [walletName, setWalletName] = useState<string | null>(null);
   const [hmac, setHmac] = useState<string | null>(null);
   const [expectedAddress, setExpectedAddress] = useState<string | null>(null);
+  const [message, setMessage] = useState<string | null>(null);
+  const [derivationPath, setDerivationPath] = useState<string | null>(null);
+  const [messageFormat, setMessageFormat] = useState<MessageFormat | null>(
+    null,
+  );
+  const [addressType, setAddressType] = useState<HWIAddressType | null>(null);
+  const [lifecycleOptions, setLifecycleOptions] =
+    useState<LifecycleOptions | null>(null);
   const [errorMessage, setErrorMessage] = useState("");
   const [pairingCode, setPairingCode] = useState<string | null>(null);
+  const [pendingApproval, setPendingApproval] =
+    useState<PendingApproval | null>(null);
 
   // Subscriptions state variables
   const [isSubscriptionsModalOpen, setSubscriptionsModalOpen] = useState(false);
   const [subscriptionsData, setSubscriptionsData] = useState({});
 
-  const handleConnectResult = async (devices: HWIDevice[]) => {
-    setPairingCode(null);
-    if (devices.length > 1) {
-      openModalHandler("multipleDevices");
-    } else if (devices.length === 0) {
-      openModalHandler("notFound");
-    } else {
-      if (deviceType && network) {
-        await hwiService.setHWIClient(
-          devices[0].fingerprint,
-          deviceType,
-          network.toLowerCase(),
-        );
-        if (devices[0].needs_pin_sent) {
-          await hwiService.promptPin();
-          openModalHandler("pin");
-        } else {
-          openModalHandler("deviceActionSuccess");
+  const handleConnectResult = useCallback(
+    async (devices: HWIDevice[]) => {
+      setPairingCode(null);
+      if (devices.length > 1) {
+        openModalHandler("multipleDevices");
+      } else if (devices.length === 0) {
+        openModalHandler("notFound");
+      } else {
+        if (deviceType && network) {
+          await hwiService.setHWIClient(
+            devices[0].fingerprint,
+            deviceType,
+            network.toLowerCase(),
+          );
+          if (devices[0].needs_pin_sent) {
+            await hwiService.promptPin();
+            openModalHandler("pin");
+          } else if (devices[0].needs_passphrase_sent) {
+            openModalHandler("passphrase");
+          } else {
+            openModalHandler("deviceActionSuccess");
+          }
         }
       }
-    }
-  };
+    },
+    [deviceType, network, openModalHandler],
+  );
 
   const handleActionSuccess = () => {
     openModalHandler("deviceActionSuccess");
@@ -99,6 +133,58 @@ const ConnectScreen = () => {
     [openModalHandler],
   );
 
+  const awaitingDevice =
+    openModal === "notFound" ||
+    openModal === "multipleDevices" ||
+    (openModal === "deviceAction" && currentAction === "connect");
+
+  // Poll for devices only while the connect flow waits for one, and connect
+  // again as soon as a device of the requested type is plugged in or unplugged
+  useEffect(() => {
+    if (!awaitingDevice) {
+      return;
+    }
+    hwiService.setDevicePolling(true);
+
+    const retryConnect = async (device: HWIDevice) => {
+      const retrying =
+        openModal === "notFound" || openModal === "multipleDevices";
+      if (!retrying || device.device_type.toLowerCase() !== deviceType) {
+        return;
+      }
+      try {
+        const devices = await hwiService.fetchDevices(
+          deviceType,
+          network?.toLowerCase(),
+        );
+        await handleConnectResult(devices);
+      } catch (error) {
+        handleError(getErrorMessage(error));
+      }
+    };
+    const unsubscribeConnected = hwiService.onDeviceEvent(
+      "device-connected",
+      retryConnect,
+    );
+    const unsubscribeDisconnected = hwiService.onDeviceEvent(
+      "device-disconnected",
+      retryConnect,
+    );
+
+    return () => {
+      hwiService.setDevicePolling(false);
+      unsubscribeConnected.then((f) => f());
+      unsubscribeDisconnected.then((f) => f());
+    };
+  }, [
+    awaitingDevice,
+    openModal,
+    deviceType,
+    network,
+    handleConnectResult,
+    handleError,
+  ]);
+
   const { data: channelSecret, refetch: regenerateQR } = useQuery({
     queryKey: ["channelSecret"],
     queryFn: () => invoke<string>("generate_encryption_key"),
@@ -117,7 +203,17 @@ const ConnectScreen = () => {
         setMiniscriptPolicy(null);
         setPsbt(null);
         setWalletName(null);
+        setMessage(null);
+        setDerivationPath(null);
+        setMessageFormat(null);
+        setAddressType(null);
+        setLifecycleOptions(null);
         const { data, network } = channelMessage.payload;
+        const requestedOptions = {
+          label: data.label ?? null,
+          wordCount: data.wordCount ?? null,
+          passphrase: data.passphrase ?? null,
+        };
         switch (data.action) {
           case "ADD_DEVICE":
             if (data.accountNumber) {
@@ -191,8 +287,13 @@ const ConnectScreen = () => {
               } else {
                 setWalletName("Vault");
               }
+            } else if (data.derivationPath) {
+              setDerivationPath(data.derivationPath);
+              setAddressType(data.addressType ?? null);
             } else {
-              handleError("Descriptor or Miniscript policy is required");
+              handleError(
+                "Descriptor, derivation path or Miniscript policy is required",
+              );
             }
             if (data.hmac) {
               setHmac(data.hmac);
@@ -203,6 +304,38 @@ const ConnectScreen = () => {
               handleError("Expected address was not provided");
             }
             break;
+          case "SIGN_MESSAGE":
+            setActionType("signMessage");
+            if (typeof data.message === "string") {
+              setMessage(data.message);
+            } else {
+              handleError("Message was not provided");
+            }
+            if (data.derivationPath) {
+              setDerivationPath(data.derivationPath);
+            } else {
+              handleError("Derivation path was not provided");
+            }
+            setMessageFormat(data.messageFormat ?? "LEGACY");
+            break;
+          case "SETUP_DEVICE":
+            setActionType("setupDevice");
+            setLifecycleOptions(requestedOptions);
+            break;
+          case "RESTORE_DEVICE":
+            setActionType("restoreDevice");
+            setLifecycleOptions(requestedOptions);
+            break;
+          case "BACKUP_DEVICE":
+            setActionType("backupDevice");
+            setLifecycleOptions(requestedOptions);
+            break;
+          case "WIPE_DEVICE":
+            setActionType("wipeDevice");
+            break;
+          case "TOGGLE_PASSPHRASE":
+            setActionType("togglePassphrase");
+            break;
           case "PURCHASE_SUBS":
             setSubscriptionsData({
               appId: data.appId,
@@ -240,6 +373,48 @@ const ConnectScreen = () => {
     };
   }, [openModalHandler]);
 
+  useEffect(() => {
+    const unsubscribeRequest = listen(
+      "approval-request",
+      (event: { payload: PendingApproval }) => {
+        setPendingApproval(event.payload);
+      },
+    );
+    const unsubscribeResolved = listen(
+      "approval-resolved",
+      (event: { payload: { id: string } }) => {
+        setPendingApproval((current) =>
+          current?.id === event.payload.id ? null : current,
+        );
+      },
+    );
+
+    return () => {
+      unsubscribeRequest.then((f) => f());
+      unsubscribeResolved.then((f) => f());
+    };
+  }, []);
+
+  const handleApprove = async (remember: boolean) => {
+    if (!pendingApproval) return;
+    setPendingApproval(null);
+    try {
+      await approvalService.approve(pendingApproval.id, remember);
+    } catch (error) {
+      handleError(getErrorMessage(error));
+    }
+  };
+
+  const handleReject = async () => {
+    if (!pendingApproval) return;
+    setPendingApproval(null);
+    try {
+      await approvalService.reject(pendingApproval.id);
+    } catch (error) {
+      console.error("Failed to reject request:", error);
+    }
+  };
+
   const openSubscriptionsModal = () => {
     setSubscriptionsModalOpen(true);
   };
@@ -322,6 +497,11 @@ const ConnectScreen = () => {
           walletName={walletName}
           hmac={hmac}
           expectedAddress={expectedAddress}
+          message={message}
+          derivationPath={derivationPath}
+          messageFormat={messageFormat}
+          addressType={addressType}
+          lifecycleOptions={lifecycleOptions}
           errorMessage={errorMessage}
           handleConnectResult={handleConnectResult}
           handleActionSuccess={handleActionSuccess}
@@ -332,6 +512,11 @@ const ConnectScreen = () => {
         />
       )}
       <div className={styles.versionTag}>Version {version}</div>
+      <ApprovalModal
+        approval={pendingApproval}
+        onApprove={handleApprove}
+        onReject={handleReject}
+      />
       <SubscriptionsModal
         isOpen={isSubscriptionsModalOpen}
         onClose={closeSubscriptionsModal}
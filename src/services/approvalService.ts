@@ -0,0 +1,22 @@
+import { invoke } from "@tauri-apps/api/tauri";
+
+export interface PendingApproval {
+  id: string;
+  action: string;
+  origin: string;
+  summary: Record<string, unknown>;
+  rememberable: boolean;
+  expiresAt: number;
+}
+
+const approvalService = {
+  approve: async (id: string, remember: boolean): Promise<void> => {
+    await invoke<void>("approve_request", { id, remember });
+  },
+
+  reject: async (id: string): Promise<void> => {
+    await invoke<void>("reject_request", { id });
+  },
+};
+
+export default approvalService;
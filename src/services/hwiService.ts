@@ -1,3 +1,4 @@
+import { listen, UnlistenFn } from "@tauri-apps/api/event";
 import { invoke } from "@tauri-apps/api/tauri";
 import { HWIDevice, HWIDeviceType } from "../helpers/devices";
 
@@ -6,25 +7,131 @@ interface Result<T> {
   Err: string;
 }
 
-const emptyTrezorDevice: HWIDevice = {
+export type Emulator =
+  | "trezor"
+  | "keepkey"
+  | "ledger"
+  | "coldcard"
+  | "bitbox02"
+  | "jade"
+  | "specter";
+
+export type PinState = "LOCKED" | "PROMPTED" | "UNLOCKED" | "LOCKED_OUT";
+
+export interface PinStatus {
+  state: PinState;
+  failedAttempts: number;
+  remainingAttempts: number;
+  needsPassphrase: boolean;
+}
+
+export type LifecycleOperation =
+  | "SETUP_DEVICE"
+  | "RESTORE_DEVICE"
+  | "BACKUP_DEVICE"
+  | "WIPE_DEVICE"
+  | "TOGGLE_PASSPHRASE";
+
+export interface ConfirmationChallenge {
+  operation: LifecycleOperation;
+  phrase: string;
+}
+
+// Parameters the phone sends along with a lifecycle action
+export interface LifecycleOptions {
+  label: string | null;
+  wordCount: 12 | 18 | 24 | null;
+  passphrase: string | null;
+}
+
+export interface ConfirmationToken {
+  token: string;
+  operation: LifecycleOperation;
+  device: string;
+  expiresAt: number;
+}
+
+export type MessageFormat = "LEGACY" | "BIP322_SIMPLE";
+
+export interface MessageVerification {
+  valid: boolean;
+  format: MessageFormat;
+}
+
+export type HWIAddressType = "Legacy" | "Sh_Wit" | "Wit" | "Tap";
+
+export type ExportFormat = "CORE" | "SPARROW" | "ELECTRUM" | "TEXT";
+
+export interface ExportedFile {
+  path: string;
+  count: number;
+}
+
+export type Backend = "HWI" | "ASYNC_HWI";
+
+export type Feature =
+  | "MINISCRIPT"
+  | "TAPROOT"
+  | "WALLET_REGISTRATION"
+  | "REGISTRATION_CHECK"
+  | "HMAC"
+  | "MESSAGE_SIGNING"
+  | "DISPLAY_ADDRESS"
+  | "PSBT_GLOBAL_XPUBS";
+
+export interface EnumeratedDevice extends HWIDevice {
+  firmware_version: string | null;
+  backends: Backend[];
+  capabilities: Feature[];
+}
+
+export type DeviceEvent = "device-connected" | "device-disconnected";
+
+export type ProgressStage =
+  | "DEVICE_FOUND"
+  | "REGISTERING_WALLET"
+  | "WAITING_FOR_CONFIRMATION"
+  | "SIGNED_INPUTS";
+
+export interface DeviceProgress {
+  requestId: string | null;
+  action: string;
+  stage: ProgressStage;
+  message: string;
+  signedInputs?: number;
+  totalInputs?: number;
+}
+
+export type LogLevel = "DEBUG" | "INFO" | "WARNING" | "ERROR" | "CRITICAL";
+
+export interface EmulatorSetting {
+  emulator: Emulator;
+  address: string;
+  enabled: boolean;
+}
+
+const emptyTrezorDevice: EnumeratedDevice = {
   device_type: "trezor",
   needs_pin_sent: true,
   model: "",
   path: "",
   needs_passphrase_sent: false,
   fingerprint: null,
+  firmware_version: null,
+  backends: ["HWI"],
+  capabilities: [],
 };
 
 const hwiService = {
   fetchDevices: async (
     deviceType: HWIDeviceType | null = null,
     network: string | null = null,
-  ): Promise<HWIDevice[]> => {
+  ): Promise<EnumeratedDevice[]> => {
     if (network === "mainnet") {
       network = "bitcoin";
     }
-    const devices = await invoke<Result<HWIDevice>[]>(
-      deviceType === "bitbox02" ? "async_hwi_enumerate" : "hwi_enumerate",
+    const devices = await invoke<Result<EnumeratedDevice>[]>(
+      "enumerate_devices",
       {
         network,
       },
@@ -49,11 +156,17 @@ const hwiService = {
     fingerprint: string | null,
     deviceType: string,
     network: string,
-  ): Promise<void> => {
+    passphrase: string | null = null,
+  ): Promise<HWIDevice> => {
     if (network === "mainnet") {
       network = "bitcoin";
     }
-    await invoke<void>("set_hwi_client", { fingerprint, deviceType, network });
+    return await invoke<HWIDevice>("set_hwi_client", {
+      fingerprint,
+      deviceType,
+      network,
+      passphrase,
+    });
   },
 
   shareXpubs: async (account: number): Promise<void> => {
@@ -103,6 +216,8 @@ const hwiService = {
     walletName: string | null,
     hmac: string | null,
     expectedAddress: string,
+    path: string | null = null,
+    addressType: HWIAddressType | null = null,
   ): Promise<void> => {
     const eventData = await invoke<void>("hwi_verify_address", {
       descriptor,
@@ -110,17 +225,162 @@ const hwiService = {
       index,
       walletName,
       hmac,
+      path,
+      addressType,
       expectedAddress,
     });
     await invoke<void>("emit_to_channel", { eventData });
   },
 
-  promptPin: async (): Promise<void> => {
-    await invoke<void>("hwi_prompt_pin");
+  signMessage: async (
+    message: string,
+    path: string,
+    format: MessageFormat,
+  ): Promise<void> => {
+    const eventData = await invoke<void>("hwi_sign_message", {
+      message,
+      path,
+      format,
+    });
+    await invoke<void>("emit_to_channel", { eventData });
+  },
+
+  verifyMessage: async (
+    address: string,
+    message: string,
+    signature: string,
+  ): Promise<MessageVerification> => {
+    return await invoke<MessageVerification>("verify_message", {
+      address,
+      message,
+      signature,
+    });
+  },
+
+  setupDevice: async (
+    label: string | null = null,
+    passphrase: string | null = null,
+  ): Promise<void> => {
+    const eventData = await invoke<void>("hwi_setup_device", {
+      label,
+      passphrase,
+    });
+    await invoke<void>("emit_to_channel", { eventData });
   },
 
-  sendPin: async (pin: string): Promise<void> => {
-    await invoke<void>("hwi_send_pin", { pin });
+  restoreDevice: async (
+    label: string | null = null,
+    wordCount: 12 | 18 | 24 | null = null,
+  ): Promise<void> => {
+    const eventData = await invoke<void>("hwi_restore_device", {
+      label,
+      wordCount,
+    });
+    await invoke<void>("emit_to_channel", { eventData });
+  },
+
+  backupDevice: async (
+    label: string | null = null,
+    backupPassphrase: string | null = null,
+  ): Promise<void> => {
+    const eventData = await invoke<void>("hwi_backup_device", {
+      label,
+      backupPassphrase,
+    });
+    await invoke<void>("emit_to_channel", { eventData });
+  },
+
+  togglePassphrase: async (): Promise<void> => {
+    const eventData = await invoke<void>("hwi_toggle_passphrase");
+    await invoke<void>("emit_to_channel", { eventData });
+  },
+
+  getConfirmationChallenge: async (
+    operation: LifecycleOperation,
+  ): Promise<ConfirmationChallenge> => {
+    return await invoke<ConfirmationChallenge>("hwi_confirmation_challenge", {
+      operation,
+    });
+  },
+
+  requestConfirmation: async (
+    operation: LifecycleOperation,
+    typed: string,
+  ): Promise<ConfirmationToken> => {
+    return await invoke<ConfirmationToken>("hwi_request_confirmation", {
+      operation,
+      typed,
+    });
+  },
+
+  wipeDevice: async (confirmation: ConfirmationToken): Promise<void> => {
+    const eventData = await invoke<void>("hwi_wipe_device", {
+      confirmation: confirmation.token,
+    });
+    await invoke<void>("emit_to_channel", { eventData });
+  },
+
+  promptPin: async (): Promise<PinStatus> => {
+    return await invoke<PinStatus>("hwi_prompt_pin");
+  },
+
+  sendPin: async (pin: string): Promise<PinStatus> => {
+    return await invoke<PinStatus>("hwi_send_pin", { pin });
+  },
+
+  getPinStatus: async (): Promise<PinStatus> => {
+    return await invoke<PinStatus>("hwi_pin_status");
+  },
+
+  cancelOperation: async (): Promise<number> => {
+    return await invoke<number>("hwi_cancel");
+  },
+  setLogLevel: async (level: LogLevel): Promise<void> => {
+    await invoke("hwi_set_log_level", { level });
+  },
+  exportDiagnostics: async (
+    count: number | null = null,
+  ): Promise<ExportedFile> => {
+    return await invoke<ExportedFile>("export_diagnostics", { count });
+  },
+  exportAuditLog: async (): Promise<ExportedFile> => {
+    return await invoke<ExportedFile>("export_audit_log");
+  },
+  exportDescriptors: async (
+    account: number,
+    addressType: HWIAddressType,
+    format: ExportFormat,
+  ): Promise<ExportedFile> => {
+    return await invoke<ExportedFile>("export_descriptors", {
+      account,
+      addressType,
+      format,
+    });
+  },
+  getEmulators: async (): Promise<EmulatorSetting[]> => {
+    return await invoke<EmulatorSetting[]>("get_emulators");
+  },
+  setEmulators: async (enabled: Emulator[]): Promise<EmulatorSetting[]> => {
+    return await invoke<EmulatorSetting[]>("set_emulators", { enabled });
+  },
+  setDevicePolling: async (enabled: boolean): Promise<void> => {
+    await invoke("set_device_polling", { enabled });
+  },
+  onDeviceEvent: (
+    event: DeviceEvent,
+    handler: (device: HWIDevice) => void,
+  ): Promise<UnlistenFn> => {
+    return listen<HWIDevice>(event, ({ payload }) => handler(payload));
+  },
+  onDeviceProgress: (
+    handler: (progress: DeviceProgress) => void,
+  ): Promise<UnlistenFn> => {
+    return listen<DeviceProgress>("device-progress", ({ payload }) =>
+      handler(payload),
+    );
+  },
+  onPinState: (handler: (status: PinStatus) => void): Promise<UnlistenFn> => {
+    return listen<PinStatus>("pin-state", ({ payload }) => handler(payload));
   },
 };
 